@@ -1,27 +1,9 @@
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize)]
-pub struct CreateContainerRequest {
-    pub image: String,
-    pub command: Option<Vec<String>>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CreateContainerResponse {
-    pub id: String,
-    pub message: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct RemoveContainerResponse {
-    pub id: String,
-    pub message: String,
-}
+pub use xxx_types::container::{CreateContainerRequest, CreateContainerResponse, RemoveContainerResponse};
 
 pub struct ContainerClient {
-    base_url: String,
-    client: reqwest::Client,
+    client: xxx_types::ContainerClient,
 }
 
 impl ContainerClient {
@@ -30,42 +12,29 @@ impl ContainerClient {
     }
 
     pub fn with_tls(base_url: String, tls_mode: super::TlsMode) -> Self {
-        let client = match tls_mode {
-            super::TlsMode::None => reqwest::Client::new(),
-            super::TlsMode::SelfSigned => {
-                reqwest::Client::builder()
-                    .danger_accept_invalid_certs(true)
-                    .build()
-                    .expect("Failed to build HTTP client with self-signed cert support")
-            }
-        };
-
-        Self { base_url, client }
+        let client = super::http_client(tls_mode, None).expect("Failed to build HTTP client");
+        Self::with_client(base_url, client)
     }
 
-    pub async fn list_containers(&self) -> Result<Vec<Vec<String>>> {
-        let url = format!("{}/api/containers/list", self.base_url);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send list containers request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to list containers: {}", error_text);
+    /// Use a preconfigured client, e.g. from [`super::http_client`] with a CA certificate
+    pub fn with_client(base_url: String, client: reqwest::Client) -> Self {
+        Self {
+            client: xxx_types::ContainerClient::with_client(base_url, client),
         }
+    }
 
-        let containers: Vec<Vec<String>> = response
-            .json()
-            .await
-            .context("Failed to parse list containers response")?;
+    pub fn base_url(&self) -> &str {
+        self.client.base_url()
+    }
+
+    /// Check the server's API version, warning on stderr if it may not understand this CLI
+    pub async fn negotiate(&mut self) {
+        let negotiation = self.client.negotiate().await;
+        super::warn_on_version(self.client.base_url(), negotiation);
+    }
 
-        Ok(containers)
+    pub async fn list_containers(&self) -> Result<Vec<Vec<String>>> {
+        self.client.list_containers().await.context("Failed to list containers")
     }
 
     pub async fn create_container(
@@ -73,57 +42,22 @@ impl ContainerClient {
         image: String,
         command: Option<Vec<String>>,
     ) -> Result<CreateContainerResponse> {
-        let url = format!("{}/api/containers/create", self.base_url);
-        let request = CreateContainerRequest { image, command };
-
-        let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send create container request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to create container: {}", error_text);
-        }
-
-        let container_response: CreateContainerResponse = response
-            .json()
+        let request = CreateContainerRequest {
+            image,
+            command,
+            ..Default::default()
+        };
+        self.client
+            .create_container(&request)
             .await
-            .context("Failed to parse create container response")?;
-
-        Ok(container_response)
+            .context("Failed to create container")
     }
 
     pub async fn remove_container(&self, id: String) -> Result<RemoveContainerResponse> {
-        let url = format!("{}/api/containers/{}", self.base_url, id);
-
-        let response = self
-            .client
-            .delete(&url)
-            .send()
-            .await
-            .context("Failed to send remove container request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to remove container: {}", error_text);
-        }
-
-        let remove_response: RemoveContainerResponse = response
-            .json()
+        self.client
+            .remove_container(&id)
             .await
-            .context("Failed to parse remove container response")?;
-
-        Ok(remove_response)
+            .context("Failed to remove container")
     }
 }
 
@@ -136,6 +70,7 @@ mod tests {
         let request = CreateContainerRequest {
             image: "python:3.11".to_string(),
             command: Some(vec!["python".to_string(), "-c".to_string(), "print('hello')".to_string()]),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -149,6 +84,7 @@ mod tests {
         let request = CreateContainerRequest {
             image: "nginx:latest".to_string(),
             command: None,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -167,13 +103,13 @@ mod tests {
     #[test]
     fn test_container_client_creation() {
         let client = ContainerClient::new("http://localhost:3000".to_string());
-        assert_eq!(client.base_url, "http://localhost:3000");
+        assert_eq!(client.base_url(), "http://localhost:3000");
     }
 
     #[test]
     fn test_container_client_with_custom_url() {
         let client = ContainerClient::new("http://example.com:8080".to_string());
-        assert_eq!(client.base_url, "http://example.com:8080");
+        assert_eq!(client.base_url(), "http://example.com:8080");
     }
 
     #[test]
@@ -181,6 +117,7 @@ mod tests {
         let request = CreateContainerRequest {
             image: "redis:alpine".to_string(),
             command: Some(vec![]),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -193,6 +130,7 @@ mod tests {
         let request = CreateContainerRequest {
             image: "alpine:latest".to_string(),
             command: Some(vec!["sh".to_string()]),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -1,7 +1,11 @@
 pub mod container;
+pub mod registry;
 pub mod repl;
 
+use anyhow::Context;
 use clap::ValueEnum;
+use std::path::Path;
+use xxx_types::{Negotiation, API_VERSION};
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum TlsMode {
@@ -9,4 +13,85 @@ pub enum TlsMode {
     None,
     /// Accept self-signed certificates (HTTPS)
     SelfSigned,
-}
\ No newline at end of file
+}
+
+/// Build the HTTP client for `tls_mode`
+///
+/// `ca_cert` is a PEM root certificate trusted in addition to the system roots,
+/// e.g. repl-api's CA from `GET /api/v1/repl/tls/ca.pem`.
+pub fn http_client(tls_mode: TlsMode, ca_cert: Option<&Path>) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+    if let TlsMode::SelfSigned = tls_mode {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(path) = ca_cert {
+        let pem = std::fs::read(path).with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    builder.build().context("Failed to build HTTP client")
+}
+
+/// What to tell the user about the server at `base_url`, if it may not
+/// understand this CLI
+pub fn version_warning(base_url: &str, negotiation: &Negotiation) -> Option<String> {
+    match negotiation {
+        Negotiation::Compatible(_) => None,
+        Negotiation::Incompatible(versions) => Some(format!(
+            "{} {} at {} serves API versions [{}], not {}; upgrade the CLI or the server",
+            versions.service,
+            versions.version,
+            base_url,
+            versions.supported.join(", "),
+            API_VERSION
+        )),
+        Negotiation::Unversioned => Some(format!(
+            "{} predates API versioning; using its unversioned routes, which newer servers will drop",
+            base_url
+        )),
+    }
+}
+
+/// Print [`version_warning`] to stderr
+///
+/// A server that cannot be asked is left for the command itself to report.
+pub fn warn_on_version<E>(base_url: &str, negotiation: Result<Negotiation, E>) {
+    if let Some(warning) = negotiation.ok().and_then(|n| version_warning(base_url, &n)) {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xxx_types::ApiVersions;
+
+    #[test]
+    fn test_version_warning() {
+        let versions = |supported: &str| ApiVersions {
+            service: "repl-api".to_string(),
+            version: "2.0.0".to_string(),
+            supported: vec![supported.to_string()],
+            legacy_sunset: None,
+        };
+        assert_eq!(version_warning("http://x", &Negotiation::Compatible(versions("v1"))), None);
+        assert_eq!(
+            version_warning("http://x", &Negotiation::Incompatible(versions("v2"))).unwrap(),
+            "repl-api 2.0.0 at http://x serves API versions [v2], not v1; upgrade the CLI or the server"
+        );
+        assert!(version_warning("http://x", &Negotiation::Unversioned).unwrap().contains("predates API versioning"));
+    }
+
+    #[test]
+    fn test_http_client_without_ca() {
+        assert!(http_client(TlsMode::None, None).is_ok());
+        assert!(http_client(TlsMode::SelfSigned, None).is_ok());
+    }
+
+    #[test]
+    fn test_http_client_rejects_missing_ca_file() {
+        let err = http_client(TlsMode::None, Some(Path::new("/nonexistent/ca.pem"))).unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/ca.pem"));
+    }
+}
@@ -1,12 +1,17 @@
 use clap::{Parser, Subcommand};
 use cli::container::ContainerClient;
+use cli::registry::{RegistryClient, RegistrySnapshot};
 use cli::repl::{Language, ReplClient};
 use cli::TlsMode;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "xxx-cli")]
 #[command(about = "CLI for interacting with container and REPL APIs", long_about = None)]
 struct Cli {
+    /// PEM CA certificate to trust, e.g. repl-api's from /api/v1/repl/tls/ca.pem
+    #[arg(long, global = true, env = "XXX_CA_CERT")]
+    ca_cert: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -23,6 +28,11 @@ enum Commands {
         #[command(subcommand)]
         command: ReplCommands,
     },
+    /// Service registry administration commands
+    Registry {
+        #[command(subcommand)]
+        command: RegistryCommands,
+    },
 }
 
 #[derive(Subcommand)]
@@ -96,14 +106,77 @@ enum ReplCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum RegistryCommands {
+    /// Export all registered services, leases and remaining TTLs as JSON
+    Export {
+        /// Write the snapshot to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Service registry URL
+        #[arg(long, default_value = "http://localhost:3003")]
+        api_url: String,
+        /// TLS mode (none or self-signed)
+        #[arg(long, value_enum, default_value = "none")]
+        tls: TlsMode,
+        /// Admin registration token
+        #[arg(long, env = "SERVICE_REGISTRY_TOKEN")]
+        token: Option<String>,
+    },
+    /// Import a snapshot as static entries, or under a new lease
+    Import {
+        /// Snapshot file to import
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Replace entries that already exist
+        #[arg(long)]
+        overwrite: bool,
+        /// Import entries under a new lease that expires unless kept alive
+        #[arg(long)]
+        leased: bool,
+        /// Service registry URL
+        #[arg(long, default_value = "http://localhost:3003")]
+        api_url: String,
+        /// TLS mode (none or self-signed)
+        #[arg(long, value_enum, default_value = "none")]
+        tls: TlsMode,
+        /// Admin registration token
+        #[arg(long, env = "SERVICE_REGISTRY_TOKEN")]
+        token: Option<String>,
+    },
+    /// Diff two snapshots, or a snapshot against the live registry
+    Diff {
+        /// Older snapshot file
+        before: PathBuf,
+        /// Newer snapshot file (defaults to the live registry)
+        after: Option<PathBuf>,
+        /// Service registry URL
+        #[arg(long, default_value = "http://localhost:3003")]
+        api_url: String,
+        /// TLS mode (none or self-signed)
+        #[arg(long, value_enum, default_value = "none")]
+        tls: TlsMode,
+        /// Admin registration token
+        #[arg(long, env = "SERVICE_REGISTRY_TOKEN")]
+        token: Option<String>,
+    },
+}
+
+fn read_snapshot(path: &Path) -> anyhow::Result<RegistrySnapshot> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let ca_cert = cli.ca_cert.as_deref();
 
     match cli.command {
         Commands::Container { command } => match command {
             ContainerCommands::List { api_url, tls } => {
-                let client = ContainerClient::with_tls(api_url, tls);
+                let mut client = ContainerClient::with_client(api_url, cli::http_client(tls, ca_cert)?);
+                client.negotiate().await;
                 let containers = client.list_containers().await?;
 
                 if containers.is_empty() {
@@ -121,7 +194,8 @@ async fn main() -> anyhow::Result<()> {
                 api_url,
                 tls,
             } => {
-                let client = ContainerClient::with_tls(api_url, tls);
+                let mut client = ContainerClient::with_client(api_url, cli::http_client(tls, ca_cert)?);
+                client.negotiate().await;
                 println!("Creating container with image: {}", image);
                 if let Some(ref cmd) = command {
                     println!("Command: {}", cmd.join(" "));
@@ -130,9 +204,13 @@ async fn main() -> anyhow::Result<()> {
                 let response = client.create_container(image, command).await?;
                 println!("✓ {}", response.message);
                 println!("Container ID: {}", response.id);
+                if let Some(output) = response.output.filter(|output| !output.is_empty()) {
+                    print!("{}", output);
+                }
             }
             ContainerCommands::Remove { id, api_url, tls } => {
-                let client = ContainerClient::with_tls(api_url, tls);
+                let mut client = ContainerClient::with_client(api_url, cli::http_client(tls, ca_cert)?);
+                client.negotiate().await;
                 println!("Removing container: {}", id);
 
                 let response = client.remove_container(id).await?;
@@ -141,7 +219,8 @@ async fn main() -> anyhow::Result<()> {
         },
         Commands::Repl { command } => match command {
             ReplCommands::Languages { api_url, tls } => {
-                let client = ReplClient::with_tls(api_url, tls);
+                let mut client = ReplClient::with_client(api_url, cli::http_client(tls, ca_cert)?);
+                client.negotiate().await;
                 let languages = client.list_languages().await?;
 
                 println!("Available languages:");
@@ -156,7 +235,8 @@ async fn main() -> anyhow::Result<()> {
                 api_url,
                 tls,
             } => {
-                let client = ReplClient::with_tls(api_url, tls);
+                let mut client = ReplClient::with_client(api_url, cli::http_client(tls, ca_cert)?);
+                client.negotiate().await;
                 let lang: Language = language.parse()?;
 
                 if !dependencies.is_empty() {
@@ -167,6 +247,83 @@ async fn main() -> anyhow::Result<()> {
                 println!(); // Add newline after streaming output
             }
         },
+        Commands::Registry { command } => match command {
+            RegistryCommands::Export {
+                output,
+                api_url,
+                tls,
+                token,
+            } => {
+                let mut client = RegistryClient::with_client(api_url, cli::http_client(tls, ca_cert)?).with_token(token);
+                client.negotiate().await;
+                let snapshot = client.export_snapshot().await?;
+                let json = serde_json::to_string_pretty(&snapshot)?;
+
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, json)?;
+                        eprintln!(
+                            "✓ Exported {} entries to {}",
+                            snapshot.entries.len(),
+                            path.display()
+                        );
+                    }
+                    None => println!("{}", json),
+                }
+            }
+            RegistryCommands::Import {
+                file,
+                overwrite,
+                leased,
+                api_url,
+                tls,
+                token,
+            } => {
+                let mut client = RegistryClient::with_client(api_url, cli::http_client(tls, ca_cert)?).with_token(token);
+                client.negotiate().await;
+                let snapshot = read_snapshot(&file)?;
+
+                let summary = client.import_snapshot(&snapshot, overwrite, leased).await?;
+                match summary.lease_id {
+                    Some(lease_id) => println!(
+                        "✓ Imported {} entries under lease {}; they expire unless it is kept alive",
+                        summary.imported, lease_id
+                    ),
+                    None => println!("✓ Imported {} static entries", summary.imported),
+                }
+                for key in summary.skipped {
+                    println!("  skipped existing {}", key);
+                }
+            }
+            RegistryCommands::Diff {
+                before,
+                after,
+                api_url,
+                tls,
+                token,
+            } => {
+                let mut client = RegistryClient::with_client(api_url, cli::http_client(tls, ca_cert)?).with_token(token);
+                client.negotiate().await;
+                let before = read_snapshot(&before)?;
+                let after = after.as_deref().map(read_snapshot).transpose()?;
+
+                let diff = client.diff_snapshots(&before, after.as_ref()).await?;
+                if diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty() {
+                    println!("No differences");
+                }
+                for entry in diff.added {
+                    println!("+ {}", entry.key);
+                }
+                for entry in diff.removed {
+                    println!("- {}", entry.key);
+                }
+                for entry in diff.changed {
+                    println!("~ {}", entry.key);
+                    println!("    before: {}", entry.before);
+                    println!("    after:  {}", entry.after);
+                }
+            }
+        },
     }
 
     Ok(())
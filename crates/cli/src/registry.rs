@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub service: serde_json::Value,
+    pub lease_id: Option<i64>,
+    pub ttl_remaining_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    pub taken_at: u64,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: Vec<String>,
+    /// Lease of the imported entries; None for a static import
+    #[serde(default)]
+    pub lease_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangedEntry {
+    pub key: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotEntry>,
+    pub removed: Vec<SnapshotEntry>,
+    pub changed: Vec<ChangedEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffRequest<'a> {
+    before: &'a RegistrySnapshot,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<&'a RegistrySnapshot>,
+}
+
+pub struct RegistryClient {
+    base_url: String,
+    /// `/api/v1`, or `/api` for servers that predate versioning
+    prefix: String,
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl RegistryClient {
+    pub fn new(base_url: String) -> Self {
+        Self::with_tls(base_url, super::TlsMode::None)
+    }
+
+    pub fn with_tls(base_url: String, tls_mode: super::TlsMode) -> Self {
+        let client = super::http_client(tls_mode, None).expect("Failed to build HTTP client");
+        Self::with_client(base_url, client)
+    }
+
+    /// Use a preconfigured client, e.g. from [`super::http_client`] with a CA certificate
+    pub fn with_client(base_url: String, client: reqwest::Client) -> Self {
+        Self {
+            base_url,
+            prefix: format!("/api/{}", xxx_types::API_VERSION),
+            client,
+            token: None,
+        }
+    }
+
+    /// Send `Authorization: Bearer <token>` with every request
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Check the server's API version, warning on stderr if it may not understand this CLI
+    pub async fn negotiate(&mut self) {
+        let negotiation = xxx_types::client::negotiate(&self.client, &self.base_url).await;
+        if let Ok(xxx_types::Negotiation::Unversioned) = negotiation {
+            self.prefix = "/api".to_string();
+        }
+        super::warn_on_version(&self.base_url, negotiation);
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    pub async fn export_snapshot(&self) -> Result<RegistrySnapshot> {
+        let url = format!("{}{}/registry/admin/snapshot", self.base_url, self.prefix);
+        let response = self
+            .authorize(self.client.get(&url))
+            .send()
+            .await
+            .context("Failed to send export snapshot request")?;
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Failed to export registry snapshot: {}", error_text);
+        }
+
+        let snapshot: RegistrySnapshot = response
+            .json()
+            .await
+            .context("Failed to parse registry snapshot")?;
+
+        Ok(snapshot)
+    }
+
+    pub async fn import_snapshot(
+        &self,
+        snapshot: &RegistrySnapshot,
+        overwrite: bool,
+        leased: bool,
+    ) -> Result<ImportSummary> {
+        let url = format!("{}{}/registry/admin/import", self.base_url, self.prefix);
+        let response = self
+            .authorize(self.client.post(&url))
+            .query(&[("overwrite", overwrite), ("leased", leased)])
+            .json(snapshot)
+            .send()
+            .await
+            .context("Failed to send import snapshot request")?;
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Failed to import registry snapshot: {}", error_text);
+        }
+
+        let summary: ImportSummary = response
+            .json()
+            .await
+            .context("Failed to parse import summary")?;
+
+        Ok(summary)
+    }
+
+    /// Diff two snapshots; `after` defaults to the live registry
+    pub async fn diff_snapshots(
+        &self,
+        before: &RegistrySnapshot,
+        after: Option<&RegistrySnapshot>,
+    ) -> Result<SnapshotDiff> {
+        let url = format!("{}{}/registry/admin/diff", self.base_url, self.prefix);
+        let response = self
+            .authorize(self.client.post(&url))
+            .json(&DiffRequest { before, after })
+            .send()
+            .await
+            .context("Failed to send diff snapshots request")?;
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Failed to diff registry snapshots: {}", error_text);
+        }
+
+        let diff: SnapshotDiff = response
+            .json()
+            .await
+            .context("Failed to parse snapshot diff")?;
+
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_deserialization() {
+        let json = r#"{"taken_at":1700000000,"entries":[{"key":"/services/coreos/coreos-primary",
+            "service":{"name":"coreos","id":"coreos-primary"},"lease_id":42,"ttl_remaining_secs":17}]}"#;
+        let snapshot: RegistrySnapshot = serde_json::from_str(json).unwrap();
+        assert_eq!(snapshot.taken_at, 1700000000);
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].lease_id, Some(42));
+        assert_eq!(snapshot.entries[0].service["name"], "coreos");
+    }
+
+    #[test]
+    fn test_diff_request_omits_missing_after() {
+        let before = RegistrySnapshot {
+            taken_at: 1,
+            entries: vec![],
+        };
+        let json = serde_json::to_string(&DiffRequest {
+            before: &before,
+            after: None,
+        })
+        .unwrap();
+        assert!(json.contains("before"));
+        assert!(!json.contains("after"));
+    }
+
+    #[test]
+    fn test_registry_client_creation() {
+        let client = RegistryClient::new("http://localhost:3003".to_string())
+            .with_token(Some("secret".to_string()));
+        assert_eq!(client.base_url, "http://localhost:3003");
+        assert_eq!(client.token.as_deref(), Some("secret"));
+    }
+}
@@ -1,53 +1,11 @@
 use anyhow::{Context, Result};
 use futures_util::StreamExt;
-use serde::{Deserialize, Serialize};
+use std::io::Write;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Language {
-    Python,
-    Node,
-    Rust,
-    Go,
-    Ruby,
-}
-
-impl std::str::FromStr for Language {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        match s.to_lowercase().as_str() {
-            "python" => Ok(Language::Python),
-            "node" => Ok(Language::Node),
-            "rust" => Ok(Language::Rust),
-            "go" => Ok(Language::Go),
-            "ruby" => Ok(Language::Ruby),
-            _ => anyhow::bail!("Unknown language: {}", s),
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
-pub struct ExecuteReplRequest {
-    pub language: Language,
-    pub code: String,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub dependencies: Vec<String>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct ExecuteReplResponse {
-    pub result: String,
-    pub success: bool,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct LanguagesResponse {
-    pub languages: Vec<String>,
-}
+pub use xxx_types::repl::{ExecuteReplRequest, ExecuteReplResponse, Language, LanguagesResponse};
 
 pub struct ReplClient {
-    base_url: String,
-    client: reqwest::Client,
+    client: xxx_types::ReplClient,
 }
 
 impl ReplClient {
@@ -56,42 +14,30 @@ impl ReplClient {
     }
 
     pub fn with_tls(base_url: String, tls_mode: super::TlsMode) -> Self {
-        let client = match tls_mode {
-            super::TlsMode::None => reqwest::Client::new(),
-            super::TlsMode::SelfSigned => {
-                reqwest::Client::builder()
-                    .danger_accept_invalid_certs(true)
-                    .build()
-                    .expect("Failed to build HTTP client with self-signed cert support")
-            }
-        };
-
-        Self { base_url, client }
+        let client = super::http_client(tls_mode, None).expect("Failed to build HTTP client");
+        Self::with_client(base_url, client)
     }
 
-    pub async fn list_languages(&self) -> Result<Vec<String>> {
-        let url = format!("{}/api/repl/languages", self.base_url);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send list languages request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to list languages: {}", error_text);
+    /// Use a preconfigured client, e.g. from [`super::http_client`] with a CA certificate
+    pub fn with_client(base_url: String, client: reqwest::Client) -> Self {
+        Self {
+            client: xxx_types::ReplClient::with_client(base_url, client),
         }
+    }
 
-        let languages_response: LanguagesResponse = response
-            .json()
-            .await
-            .context("Failed to parse list languages response")?;
+    pub fn base_url(&self) -> &str {
+        self.client.base_url()
+    }
+
+    /// Check the server's API version, warning on stderr if it may not understand this CLI
+    pub async fn negotiate(&mut self) {
+        let negotiation = self.client.negotiate().await;
+        super::warn_on_version(self.client.base_url(), negotiation);
+    }
 
-        Ok(languages_response.languages)
+    pub async fn list_languages(&self) -> Result<Vec<String>> {
+        let response = self.client.list_languages().await.context("Failed to list languages")?;
+        Ok(response.languages)
     }
 
     pub async fn execute(
@@ -100,106 +46,43 @@ impl ReplClient {
         code: String,
         dependencies: Vec<String>,
     ) -> Result<ExecuteReplResponse> {
-        let url = format!("{}/api/repl/execute", self.base_url);
         let request = ExecuteReplRequest {
-            language,
-            code,
             dependencies,
+            ..ExecuteReplRequest::new(language, code)
         };
-
-        let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
+        self.client
+            .execute(&request)
             .await
-            .context("Failed to send execute REPL request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to execute REPL code: {}", error_text);
-        }
-
-        let execute_response: ExecuteReplResponse = response
-            .json()
-            .await
-            .context("Failed to parse execute REPL response")?;
-
-        Ok(execute_response)
+            .context("Failed to execute REPL code")
     }
 
+    /// Print the output as it streams; errors go to stderr
     pub async fn execute_stream(
         &self,
         language: Language,
         code: String,
         dependencies: Vec<String>,
     ) -> Result<()> {
-        let url = format!("{}/api/repl/execute/stream", self.base_url);
         let request = ExecuteReplRequest {
-            language,
-            code,
             dependencies,
+            ..ExecuteReplRequest::new(language, code)
         };
-
-        let response = self
+        let mut events = self
             .client
-            .post(&url)
-            .json(&request)
-            .send()
+            .execute_stream(&request)
             .await
-            .context("Failed to send execute REPL stream request")?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Failed to execute REPL code: {}", error_text);
-        }
-
-        // Stream the response
-        let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
-
-        while let Some(chunk_result) = stream.next().await {
-            match chunk_result {
-                Ok(chunk) => {
-                    let text = String::from_utf8_lossy(&chunk);
-                    buffer.push_str(&text);
-
-                    // Process complete SSE events
-                    while let Some(event_end) = buffer.find("\n\n") {
-                        let event: String = buffer.drain(..event_end).collect();
-                        buffer.drain(..2); // remove the separator (the +2 part)
-
-                        // Parse SSE event
-                        for line in event.lines() {
-                            if line.starts_with("data:") {
-                                let data = line.strip_prefix("data:").unwrap_or("").trim();
-                                if !data.is_empty() {
-                                    // Print the output as it streams
-                                    if data.starts_with("ERROR:") {
-                                        eprintln!("{}", data);
-                                    } else {
-                                        print!("{}", data);
-                                        use std::io::Write;
-                                        std::io::stdout().flush().unwrap();
-                                    }
-                                }
-                            } else if line.starts_with("event:") {
-                                let event_type = line.strip_prefix("event:").unwrap_or("").trim();
-                                if event_type == "done" {
-                                    return Ok(());
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    anyhow::bail!("Stream error: {}", e);
+            .context("Failed to execute REPL code")?;
+
+        while let Some(event) = events.next().await {
+            let event = event.context("Stream error")?;
+            match event.event.as_deref() {
+                Some("done") => return Ok(()),
+                // Named events such as `network` describe the execution, not its output
+                Some(_) => {}
+                None if event.data.starts_with("ERROR:") => eprintln!("{}", event.data),
+                None => {
+                    print!("{}", event.data);
+                    std::io::stdout().flush().unwrap();
                 }
             }
         }
@@ -251,11 +134,7 @@ mod tests {
 
     #[test]
     fn test_execute_repl_request_serialization() {
-        let request = ExecuteReplRequest {
-            language: Language::Python,
-            code: "print('hello')".to_string(),
-            dependencies: vec![],
-        };
+        let request = ExecuteReplRequest::new(Language::Python, "print('hello')");
 
         let json = serde_json::to_string(&request).unwrap();
         assert!(json.contains("Python"));
@@ -267,9 +146,8 @@ mod tests {
     #[test]
     fn test_execute_repl_request_serialization_with_dependencies() {
         let request = ExecuteReplRequest {
-            language: Language::Python,
-            code: "import requests".to_string(),
             dependencies: vec!["requests".to_string(), "numpy".to_string()],
+            ..ExecuteReplRequest::new(Language::Python, "import requests")
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -308,7 +186,7 @@ mod tests {
     #[test]
     fn test_repl_client_creation() {
         let client = ReplClient::new("http://localhost:3001".to_string());
-        assert_eq!(client.base_url, "http://localhost:3001");
+        assert_eq!(client.base_url(), "http://localhost:3001");
     }
 
     #[test]
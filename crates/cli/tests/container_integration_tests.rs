@@ -16,7 +16,7 @@ async fn test_list_containers_success() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/containers/list")
+        .mock("GET", "/api/v1/containers/list")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"[["container1", "alias1"], ["container2"]]"#)
@@ -39,7 +39,7 @@ async fn test_list_containers_empty() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/containers/list")
+        .mock("GET", "/api/v1/containers/list")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body("[]")
@@ -60,7 +60,7 @@ async fn test_list_containers_error() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/containers/list")
+        .mock("GET", "/api/v1/containers/list")
         .with_status(500)
         .with_body("Internal server error")
         .create_async()
@@ -80,7 +80,7 @@ async fn test_create_container_success() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/containers/create")
+        .mock("POST", "/api/v1/containers/create")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"id":"abc123","message":"Container created successfully"}"#)
@@ -107,7 +107,7 @@ async fn test_create_container_no_command() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/containers/create")
+        .mock("POST", "/api/v1/containers/create")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"id":"xyz789","message":"Container started"}"#)
@@ -129,7 +129,7 @@ async fn test_create_container_error() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/containers/create")
+        .mock("POST", "/api/v1/containers/create")
         .with_status(500)
         .with_body("Failed to pull image")
         .create_async()
@@ -151,7 +151,7 @@ async fn test_create_container_with_empty_command() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/containers/create")
+        .mock("POST", "/api/v1/containers/create")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"id":"def456","message":"Success"}"#)
@@ -0,0 +1,135 @@
+use mockito::{Matcher, Server, ServerGuard};
+
+// Re-export the registry module types for testing
+mod registry {
+    pub use cli::registry::*;
+}
+
+use registry::{RegistryClient, RegistrySnapshot};
+
+async fn setup_mock_server() -> ServerGuard {
+    Server::new_async().await
+}
+
+const SNAPSHOT: &str = r#"{"taken_at":1700000000,"entries":[
+    {"key":"/services/coreos/coreos-primary","service":{"name":"coreos","id":"coreos-primary"},
+     "lease_id":42,"ttl_remaining_secs":17}]}"#;
+
+#[tokio::test]
+async fn test_export_snapshot_sends_token() {
+    let mut server = setup_mock_server().await;
+
+    let mock = server
+        .mock("GET", "/api/v1/registry/admin/snapshot")
+        .match_header("authorization", "Bearer admin-token")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(SNAPSHOT)
+        .create_async()
+        .await;
+
+    let client = RegistryClient::new(server.url()).with_token(Some("admin-token".to_string()));
+    let result = client.export_snapshot().await;
+
+    mock.assert_async().await;
+    let snapshot = result.unwrap();
+    assert_eq!(snapshot.entries.len(), 1);
+    assert_eq!(snapshot.entries[0].key, "/services/coreos/coreos-primary");
+}
+
+#[tokio::test]
+async fn test_export_snapshot_from_unversioned_registry() {
+    let mut server = setup_mock_server().await;
+
+    server
+        .mock("GET", "/api/version")
+        .with_status(404)
+        .create_async()
+        .await;
+    let mock = server
+        .mock("GET", "/api/registry/admin/snapshot")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(SNAPSHOT)
+        .create_async()
+        .await;
+
+    let mut client = RegistryClient::new(server.url());
+    client.negotiate().await;
+    let result = client.export_snapshot().await;
+
+    mock.assert_async().await;
+    assert_eq!(result.unwrap().entries.len(), 1);
+}
+
+#[tokio::test]
+async fn test_export_snapshot_unauthorized() {
+    let mut server = setup_mock_server().await;
+
+    let mock = server
+        .mock("GET", "/api/v1/registry/admin/snapshot")
+        .with_status(401)
+        .with_body("missing or invalid registration token")
+        .create_async()
+        .await;
+
+    let client = RegistryClient::new(server.url());
+    let result = client.export_snapshot().await;
+
+    mock.assert_async().await;
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("Failed to export registry snapshot"));
+    assert!(err.contains("missing or invalid registration token"));
+}
+
+#[tokio::test]
+async fn test_import_snapshot_with_overwrite() {
+    let mut server = setup_mock_server().await;
+
+    let mock = server
+        .mock("POST", "/api/v1/registry/admin/import")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("overwrite".into(), "true".into()),
+            Matcher::UrlEncoded("leased".into(), "true".into()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"imported":1,"skipped":[],"lease_id":42}"#)
+        .create_async()
+        .await;
+
+    let snapshot: RegistrySnapshot = serde_json::from_str(SNAPSHOT).unwrap();
+    let client = RegistryClient::new(server.url());
+    let summary = client.import_snapshot(&snapshot, true, true).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(summary.imported, 1);
+    assert!(summary.skipped.is_empty());
+    assert_eq!(summary.lease_id, Some(42));
+}
+
+#[tokio::test]
+async fn test_diff_against_live_registry() {
+    let mut server = setup_mock_server().await;
+
+    let mock = server
+        .mock("POST", "/api/v1/registry/admin/diff")
+        .match_body(Matcher::PartialJsonString(r#"{"before":{"taken_at":1700000000}}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"added":[],"removed":[{"key":"/services/coreos/coreos-primary",
+                "service":{"name":"coreos"},"lease_id":42,"ttl_remaining_secs":17}],"changed":[]}"#,
+        )
+        .create_async()
+        .await;
+
+    let snapshot: RegistrySnapshot = serde_json::from_str(SNAPSHOT).unwrap();
+    let client = RegistryClient::new(server.url());
+    let diff = client.diff_snapshots(&snapshot, None).await.unwrap();
+
+    mock.assert_async().await;
+    assert!(diff.added.is_empty());
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].key, "/services/coreos/coreos-primary");
+}
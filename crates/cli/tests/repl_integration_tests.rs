@@ -16,7 +16,7 @@ async fn test_list_languages_success() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/repl/languages")
+        .mock("GET", "/api/v1/repl/languages")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"languages":["Python","Node","Rust","Go","Ruby"]}"#)
@@ -39,7 +39,7 @@ async fn test_list_languages_empty() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/repl/languages")
+        .mock("GET", "/api/v1/repl/languages")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"languages":[]}"#)
@@ -60,7 +60,7 @@ async fn test_list_languages_error() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("GET", "/api/repl/languages")
+        .mock("GET", "/api/v1/repl/languages")
         .with_status(503)
         .with_body("Service unavailable")
         .create_async()
@@ -80,7 +80,7 @@ async fn test_execute_repl_python_success() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Executed successfully","success":true}"#)
@@ -104,7 +104,7 @@ async fn test_execute_repl_node_success() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Output: hello","success":true}"#)
@@ -128,7 +128,7 @@ async fn test_execute_repl_failure() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Syntax error in code","success":false}"#)
@@ -152,7 +152,7 @@ async fn test_execute_repl_server_error() {
     let mut server = setup_mock_server().await;
 
     let mock = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(500)
         .with_body("Internal server error")
         .create_async()
@@ -175,7 +175,7 @@ async fn test_execute_repl_all_languages() {
 
     // Test Python
     let mock_python = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Python output","success":true}"#)
@@ -191,7 +191,7 @@ async fn test_execute_repl_all_languages() {
 
     // Test Node
     let mock_node = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Node output","success":true}"#)
@@ -206,7 +206,7 @@ async fn test_execute_repl_all_languages() {
 
     // Test Rust
     let mock_rust = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Rust output","success":true}"#)
@@ -221,7 +221,7 @@ async fn test_execute_repl_all_languages() {
 
     // Test Go
     let mock_go = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Go output","success":true}"#)
@@ -236,7 +236,7 @@ async fn test_execute_repl_all_languages() {
 
     // Test Ruby
     let mock_ruby = server
-        .mock("POST", "/api/repl/execute")
+        .mock("POST", "/api/v1/repl/execute")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"result":"Ruby output","success":true}"#)
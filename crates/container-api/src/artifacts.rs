@@ -0,0 +1,448 @@
+use crate::auth::Caller;
+use futures_util::TryStreamExt;
+use podman_api::api::Container;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use xxx_types::container::ArtifactInfo;
+
+/// Globs accepted per request
+pub const MAX_ARTIFACT_GLOBS: usize = 16;
+
+const DEFAULT_STORE_MAX_BYTES: u64 = 512 * 1024 * 1024;
+const DEFAULT_EXECUTION_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// An artifact's metadata and the callers whose executions produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredArtifact {
+    #[serde(flatten)]
+    info: ArtifactInfo,
+    owners: BTreeSet<String>,
+}
+
+/// Local, size-capped store for artifacts, keyed by content hash
+///
+/// Each artifact is kept as `<id>` plus `<id>.json` metadata under the store
+/// directory. When the store would grow past its cap, the oldest artifacts
+/// are evicted. Artifacts can only be read by the callers whose executions
+/// produced them, and by admins. Files are written on the blocking thread pool.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+    max_bytes: u64,
+    execution_max_bytes: u64,
+    index: Arc<Mutex<HashMap<String, StoredArtifact>>>,
+}
+
+impl ArtifactStore {
+    /// Open (or create) a store at `root`, indexing artifacts already on disk
+    pub fn open(root: impl Into<PathBuf>, max_bytes: u64, execution_max_bytes: u64) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+
+        let mut index = HashMap::new();
+        for entry in std::fs::read_dir(&root)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let stored = std::fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<StoredArtifact>(&bytes).ok());
+                if let Some(stored) = stored {
+                    index.insert(stored.info.id.clone(), stored);
+                }
+            }
+        }
+
+        Ok(Self {
+            root,
+            max_bytes,
+            execution_max_bytes,
+            index: Arc::new(Mutex::new(index)),
+        })
+    }
+
+    /// Open the store configured by the environment
+    ///
+    /// - `ARTIFACT_DIR`: store directory (default `<tmp>/xxx-artifacts`)
+    /// - `ARTIFACT_STORE_MAX_BYTES`: total size cap (default 512 MiB)
+    /// - `ARTIFACT_EXECUTION_MAX_BYTES`: cap on what one execution may collect (default 64 MiB)
+    pub fn from_env() -> std::io::Result<Self> {
+        let root = std::env::var("ARTIFACT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("xxx-artifacts"));
+        let env_bytes = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Self::open(
+            root,
+            env_bytes("ARTIFACT_STORE_MAX_BYTES", DEFAULT_STORE_MAX_BYTES),
+            env_bytes("ARTIFACT_EXECUTION_MAX_BYTES", DEFAULT_EXECUTION_MAX_BYTES),
+        )
+    }
+
+    pub fn execution_max_bytes(&self) -> u64 {
+        self.execution_max_bytes
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.root.join(id)
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+
+    /// Store `contents` collected from `path` for `owner`, evicting old artifacts if needed
+    pub async fn put(&self, path: &str, contents: Vec<u8>, owner: &Caller) -> std::io::Result<ArtifactInfo> {
+        let store = self.clone();
+        let (path, owner) = (path.to_string(), owner.name.clone());
+        tokio::task::spawn_blocking(move || store.put_blocking(&path, &contents, owner))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    fn put_blocking(&self, path: &str, contents: &[u8], owner: String) -> std::io::Result<ArtifactInfo> {
+        let size = contents.len() as u64;
+        if size > self.max_bytes {
+            return Err(std::io::Error::other(format!(
+                "artifact {} ({} bytes) exceeds the store cap of {} bytes",
+                path, size, self.max_bytes
+            )));
+        }
+
+        let id = hex::encode(Sha256::digest(contents));
+        let info = ArtifactInfo {
+            id: id.clone(),
+            path: path.to_string(),
+            size,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+
+        let mut index = self.index.lock().unwrap();
+        if let Some(existing) = index.get_mut(&id) {
+            if existing.owners.insert(owner) {
+                std::fs::write(self.meta_path(&id), serde_json::to_vec(&*existing)?)?;
+            }
+            return Ok(existing.info.clone());
+        }
+
+        // Evict the oldest artifacts until the new one fits
+        let mut used: u64 = index.values().map(|a| a.info.size).sum();
+        while used + size > self.max_bytes {
+            let Some(oldest) = index
+                .values()
+                .min_by_key(|a| (a.info.created_at, a.info.id.clone()))
+                .map(|a| a.info.id.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = index.remove(&oldest) {
+                tracing::info!("Evicting artifact {} ({} bytes)", evicted.info.id, evicted.info.size);
+                used -= evicted.info.size;
+                let _ = std::fs::remove_file(self.data_path(&evicted.info.id));
+                let _ = std::fs::remove_file(self.meta_path(&evicted.info.id));
+            }
+        }
+
+        let stored = StoredArtifact {
+            info: info.clone(),
+            owners: BTreeSet::from([owner]),
+        };
+        std::fs::write(self.data_path(&id), contents)?;
+        std::fs::write(self.meta_path(&id), serde_json::to_vec(&stored)?)?;
+        index.insert(id, stored);
+
+        Ok(info)
+    }
+
+    /// The artifact, if it exists and `caller` owns it or is an admin
+    pub fn get(&self, id: &str, caller: &Caller) -> Option<ArtifactInfo> {
+        let index = self.index.lock().unwrap();
+        let stored = index.get(id)?;
+        caller.owns(&stored.owners).then(|| stored.info.clone())
+    }
+
+    /// Read an artifact's contents, if `caller` may see it
+    pub async fn read(&self, id: &str, caller: &Caller) -> Option<(ArtifactInfo, Vec<u8>)> {
+        let info = self.get(id, caller)?;
+        let contents = tokio::fs::read(self.data_path(&info.id)).await.ok()?;
+        Some((info, contents))
+    }
+}
+
+/// Check the requested globs before running anything
+pub fn validate_globs(globs: &[String]) -> Result<(), String> {
+    if globs.len() > MAX_ARTIFACT_GLOBS {
+        return Err(format!("At most {} artifact globs are allowed", MAX_ARTIFACT_GLOBS));
+    }
+    for glob in globs {
+        if !glob.starts_with('/') {
+            return Err(format!("Artifact glob '{}' must be an absolute path", glob));
+        }
+        if glob.split('/').any(|segment| segment == "..") {
+            return Err(format!("Artifact glob '{}' must not contain '..'", glob));
+        }
+    }
+    Ok(())
+}
+
+/// The directory to export for a glob: everything before its first wildcard segment
+fn glob_base(glob: &str) -> String {
+    let segments: Vec<&str> = glob
+        .split('/')
+        .take_while(|segment| !segment.contains(['*', '?', '[']))
+        .collect();
+    let base = segments.join("/");
+    if base.is_empty() { "/".to_string() } else { base }
+}
+
+/// Match a path against a glob supporting `*`, `?` and `**` (any number of directories)
+pub fn glob_matches(glob: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = glob.split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                match_segment(segment.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+/// Copy the files matching `globs` out of a finished container into the store,
+/// owned by the caller that ran it
+///
+/// Collection stops at the store's per-execution cap. Problems with a single
+/// glob are logged and skipped so the execution result is still returned.
+pub async fn collect(
+    store: &ArtifactStore,
+    container: &Container,
+    globs: &[String],
+    owner: &Caller,
+) -> Vec<ArtifactInfo> {
+    let mut collected: Vec<ArtifactInfo> = Vec::new();
+    let mut budget = store.execution_max_bytes();
+
+    for glob in globs {
+        let base = glob_base(glob);
+        let archive = match read_archive(container, &base, budget).await {
+            Ok(archive) => archive,
+            Err(e) => {
+                tracing::warn!("Failed to export {} for artifacts: {}", base, e);
+                continue;
+            }
+        };
+
+        // Entries are relative to the parent of the exported path
+        let parent = Path::new(&base).parent().unwrap_or(Path::new("/")).to_path_buf();
+        let files = match matching_files(&archive, &parent, glob) {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::warn!("Failed to read archive of {}: {}", base, e);
+                continue;
+            }
+        };
+
+        for (path, contents) in files {
+            let size = contents.len() as u64;
+            if size > budget {
+                tracing::warn!("Skipping artifact {}: execution artifact cap reached", path);
+                continue;
+            }
+            if collected.iter().any(|a| a.path == path) {
+                continue;
+            }
+            match store.put(&path, contents, owner).await {
+                Ok(mut info) => {
+                    budget -= size;
+                    info.path = path;
+                    collected.push(info);
+                }
+                Err(e) => tracing::warn!("Failed to store artifact {}: {}", path, e),
+            }
+        }
+    }
+
+    collected
+}
+
+async fn read_archive(container: &Container, path: &str, limit: u64) -> Result<Vec<u8>, String> {
+    let mut stream = Box::pin(container.copy_from(path));
+    let mut archive = Vec::new();
+    // Tar headers and padding add overhead on top of file contents
+    let limit = limit.saturating_mul(2).max(1024 * 1024);
+
+    while let Some(chunk) = stream.try_next().await.map_err(|e| e.to_string())? {
+        archive.extend_from_slice(&chunk);
+        if archive.len() as u64 > limit {
+            return Err(format!("archive of {} exceeds {} bytes", path, limit));
+        }
+    }
+
+    Ok(archive)
+}
+
+fn matching_files(archive: &[u8], parent: &Path, glob: &str) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(archive);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = parent.join(entry.path()?);
+        let path = path.to_string_lossy().to_string();
+        if glob_matches(glob, &path) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            files.push((path, contents));
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(name: &str, admin: bool) -> Caller {
+        Caller {
+            name: name.to_string(),
+            admin,
+        }
+    }
+
+    fn temp_store(max_bytes: u64) -> (tempfile::TempDir, ArtifactStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open(dir.path(), max_bytes, max_bytes).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("/work/*.png", "/work/plot.png"));
+        assert!(!glob_matches("/work/*.png", "/work/sub/plot.png"));
+        assert!(glob_matches("/work/**/*.csv", "/work/data.csv"));
+        assert!(glob_matches("/work/**/*.csv", "/work/a/b/data.csv"));
+        assert!(glob_matches("/work/out?.txt", "/work/out1.txt"));
+        assert!(!glob_matches("/work/out?.txt", "/work/out12.txt"));
+        assert!(glob_matches("/work/target/app", "/work/target/app"));
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("/work/*.png"), "/work");
+        assert_eq!(glob_base("/work/out/**/*.csv"), "/work/out");
+        assert_eq!(glob_base("/work/app"), "/work/app");
+        assert_eq!(glob_base("/*.txt"), "/");
+    }
+
+    #[test]
+    fn test_validate_globs() {
+        assert!(validate_globs(&["/work/*.png".to_string()]).is_ok());
+        assert!(validate_globs(&["work/*.png".to_string()]).is_err());
+        assert!(validate_globs(&["/work/../etc/passwd".to_string()]).is_err());
+        let too_many = vec!["/work/*".to_string(); MAX_ARTIFACT_GLOBS + 1];
+        assert!(validate_globs(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_matching_files_from_archive() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [("work/plot.png", &b"png"[..]), ("work/notes.txt", &b"txt"[..])] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let archive = builder.into_inner().unwrap();
+
+        let files = matching_files(&archive, Path::new("/"), "/work/*.png").unwrap();
+        assert_eq!(files, vec![("/work/plot.png".to_string(), b"png".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn test_store_put_and_reopen() {
+        let (dir, store) = temp_store(1024);
+        let alice = caller("alice", false);
+        let info = store.put("/work/out.csv", b"a,b\n1,2\n".to_vec(), &alice).await.unwrap();
+        assert_eq!(info.size, 8);
+        assert_eq!(info.file_name(), "out.csv");
+        assert_eq!(store.get(&info.id, &alice), Some(info.clone()));
+
+        let reopened = ArtifactStore::open(dir.path(), 1024, 1024).unwrap();
+        assert_eq!(reopened.get(&info.id, &alice), Some(info));
+    }
+
+    #[tokio::test]
+    async fn test_store_evicts_oldest() {
+        let (_dir, store) = temp_store(10);
+        let alice = caller("alice", false);
+        let first = store.put("/a", b"123456".to_vec(), &alice).await.unwrap();
+        // Make the first artifact strictly older
+        store.index.lock().unwrap().get_mut(&first.id).unwrap().info.created_at = 0;
+
+        let second = store.put("/b", b"abcdef".to_vec(), &alice).await.unwrap();
+        assert!(store.get(&first.id, &alice).is_none());
+        assert!(store.get(&second.id, &alice).is_some());
+
+        assert!(store.put("/c", vec![0u8; 11], &alice).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_read() {
+        let (_dir, store) = temp_store(1024);
+        let alice = caller("alice", false);
+        let info = store.put("/work/bin", b"\x7fELF".to_vec(), &alice).await.unwrap();
+        let (read_info, contents) = store.read(&info.id, &alice).await.unwrap();
+        assert_eq!(read_info, info);
+        assert_eq!(contents, b"\x7fELF");
+        assert!(store.read("missing", &alice).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_artifacts_are_private_to_their_owners() {
+        let (dir, store) = temp_store(1024);
+        let (alice, bob, admin) = (caller("alice", false), caller("bob", false), caller("ops", true));
+        let info = store.put("/work/out.csv", b"secret".to_vec(), &alice).await.unwrap();
+
+        assert!(store.read(&info.id, &bob).await.is_none());
+        assert!(store.read(&info.id, &admin).await.is_some());
+
+        // Producing the same file makes bob an owner too, also after reopening
+        store.put("/work/copy.csv", b"secret".to_vec(), &bob).await.unwrap();
+        let reopened = ArtifactStore::open(dir.path(), 1024, 1024).unwrap();
+        assert!(reopened.get(&info.id, &bob).is_some());
+    }
+}
@@ -0,0 +1,176 @@
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// The caller behind a request, identified by its bearer token
+///
+/// Inserted into request extensions by [`identify_caller`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    pub name: String,
+    /// Admin callers may use privileged options such as full network egress
+    pub admin: bool,
+}
+
+impl Caller {
+    /// Caller used when no tokens are configured
+    pub fn anonymous() -> Self {
+        Self {
+            name: "anonymous".to_string(),
+            admin: false,
+        }
+    }
+
+    /// Whether the request carried no token
+    pub fn is_anonymous(&self) -> bool {
+        *self == Self::anonymous()
+    }
+
+    /// Whether the caller may use a stored item recorded for `owners`; admins may use any
+    pub fn owns(&self, owners: &BTreeSet<String>) -> bool {
+        self.admin || owners.contains(&self.name)
+    }
+}
+
+/// Tokens accepted by container-api
+#[derive(Debug, Clone, Default)]
+pub struct CallerTokens {
+    tokens: Arc<HashMap<String, Caller>>,
+}
+
+impl CallerTokens {
+    pub fn new(tokens: Vec<(String, Caller)>) -> Self {
+        Self {
+            tokens: Arc::new(tokens.into_iter().collect()),
+        }
+    }
+
+    /// Load tokens from `CONTAINER_API_TOKENS`, formatted as
+    /// `token=caller;other=ops:admin`
+    ///
+    /// Returns None when unset, which leaves the API open to anonymous callers.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("CONTAINER_API_TOKENS") {
+            Ok(inline) => parse_tokens(&inline).map(|tokens| Some(Self::new(tokens))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn caller(&self, token: &str) -> Option<&Caller> {
+        self.tokens.get(token)
+    }
+}
+
+fn parse_tokens(inline: &str) -> Result<Vec<(String, Caller)>, String> {
+    inline
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (token, caller) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected token=caller, got '{}'", entry))?;
+            let (name, admin) = match caller.trim().split_once(':') {
+                Some((name, "admin")) => (name, true),
+                Some((_, role)) => return Err(format!("unknown role '{}' for token entry", role)),
+                None => (caller.trim(), false),
+            };
+            if name == Caller::anonymous().name {
+                return Err("the caller name 'anonymous' is reserved".to_string());
+            }
+
+            Ok((
+                token.trim().to_string(),
+                Caller {
+                    name: name.to_string(),
+                    admin,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Middleware that resolves the bearer token into a [`Caller`]
+///
+/// Without configured tokens every request is treated as [`Caller::anonymous`].
+pub async fn identify_caller(
+    State(tokens): State<Option<CallerTokens>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let caller = match &tokens {
+        None => Caller::anonymous(),
+        Some(tokens) => {
+            let caller = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .and_then(|token| tokens.caller(token.trim()))
+                .cloned();
+            match caller {
+                Some(caller) => caller,
+                None => {
+                    tracing::warn!("Rejected request to {} without a valid token", request.uri());
+                    return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+                }
+            }
+        }
+    };
+
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{Extension, Router, middleware};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_parse_tokens() {
+        let tokens = parse_tokens("abc=repl-api; root=ops:admin").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].0, "abc");
+        assert_eq!(tokens[0].1, Caller { name: "repl-api".to_string(), admin: false });
+        assert_eq!(tokens[1].1, Caller { name: "ops".to_string(), admin: true });
+
+        assert!(parse_tokens("no-separator").is_err());
+        assert!(parse_tokens("abc=ops:root").is_err());
+        assert!(parse_tokens("abc=anonymous").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_identify_caller() {
+        let app = |tokens: Option<CallerTokens>| {
+            Router::new()
+                .route("/", get(|Extension(caller): Extension<Caller>| async move { caller.name }))
+                .layer(middleware::from_fn_with_state(tokens, identify_caller))
+        };
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/");
+            if let Some(auth) = auth {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app(None).oneshot(request(None)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"anonymous");
+
+        let tokens = Some(CallerTokens::new(parse_tokens("abc=ci").unwrap()));
+        let response = app(tokens.clone()).oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app(tokens).oneshot(request(Some("Bearer abc"))).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"ci");
+    }
+}
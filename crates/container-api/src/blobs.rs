@@ -0,0 +1,290 @@
+use crate::auth::Caller;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+const DEFAULT_STORE_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Largest single upload accepted by `POST /api/blobs`
+pub const DEFAULT_BLOB_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// An uploaded input file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BlobInfo {
+    /// sha256 of the contents
+    pub id: String,
+    pub size: u64,
+}
+
+/// Who uploaded a blob, kept as `<id>.json` next to it
+#[derive(Debug, Serialize, Deserialize)]
+struct BlobMeta {
+    owners: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlobError {
+    /// Storing the blob would exceed the store's cap
+    StoreFull,
+    Io(String),
+}
+
+impl std::fmt::Display for BlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlobError::StoreFull => write!(f, "Blob store is full; delete unused blobs first"),
+            BlobError::Io(e) => write!(f, "Failed to store blob: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BlobError {}
+
+/// Content-addressed store for files uploaded as execution inputs
+///
+/// Unlike artifacts, blobs are never evicted: uploads are rejected once the
+/// store is full and blobs stay until deleted. Every caller that uploaded a
+/// blob is recorded as an owner; other non-admin callers cannot see, mount or
+/// delete it. Files are touched on the blocking thread pool.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+    max_bytes: u64,
+    /// Serializes writes so the size cap holds under concurrent uploads
+    lock: Arc<Mutex<()>>,
+}
+
+impl BlobStore {
+    pub fn open(root: impl Into<PathBuf>, max_bytes: u64) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            max_bytes,
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Open the store configured by the environment
+    ///
+    /// - `BLOB_DIR`: store directory (default `<tmp>/xxx-blobs`)
+    /// - `BLOB_STORE_MAX_BYTES`: total size cap (default 1 GiB)
+    pub fn from_env() -> std::io::Result<Self> {
+        let root = std::env::var("BLOB_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("xxx-blobs"));
+        let max_bytes = std::env::var("BLOB_STORE_MAX_BYTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_STORE_MAX_BYTES);
+        Self::open(root, max_bytes)
+    }
+
+    /// Blob ids are lowercase hex sha256 digests; anything else is never a valid path
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let valid = id.len() == 64
+            && id
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+        valid.then(|| self.root.join(id))
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+
+    /// Owners of a stored blob
+    fn owners(&self, id: &str) -> std::io::Result<BTreeSet<String>> {
+        let meta: BlobMeta = serde_json::from_slice(&std::fs::read(self.meta_path(id))?)?;
+        Ok(meta.owners)
+    }
+
+    fn write_owners(&self, id: &str, owners: BTreeSet<String>) -> std::io::Result<()> {
+        std::fs::write(self.meta_path(id), serde_json::to_vec(&BlobMeta { owners })?)
+    }
+
+    fn used_bytes(&self) -> u64 {
+        std::fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.metadata().ok())
+                    .map(|meta| meta.len())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Store `contents` for `owner`, adding them as an owner if the blob exists
+    pub async fn put(&self, contents: axum::body::Bytes, owner: &Caller) -> Result<BlobInfo, BlobError> {
+        let store = self.clone();
+        let owner = owner.name.clone();
+        tokio::task::spawn_blocking(move || store.put_blocking(&contents, owner))
+            .await
+            .map_err(|e| BlobError::Io(e.to_string()))?
+    }
+
+    fn put_blocking(&self, contents: &[u8], owner: String) -> Result<BlobInfo, BlobError> {
+        let id = hex::encode(Sha256::digest(contents));
+        let info = BlobInfo {
+            id: id.clone(),
+            size: contents.len() as u64,
+        };
+        let path = self.root.join(&id);
+
+        let _guard = self.lock.lock().unwrap();
+        let io = |e: std::io::Error| BlobError::Io(e.to_string());
+        if path.exists() {
+            let mut owners = self.owners(&id).map_err(io)?;
+            if owners.insert(owner) {
+                self.write_owners(&id, owners).map_err(io)?;
+            }
+            return Ok(info);
+        }
+        if self.used_bytes() + info.size > self.max_bytes {
+            return Err(BlobError::StoreFull);
+        }
+
+        // Write to a temporary name first so readers never see a partial blob
+        let tmp = self.root.join(format!(".{}.tmp", id));
+        self.write_owners(&id, BTreeSet::from([owner]))
+            .and_then(|_| std::fs::write(&tmp, contents))
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(io)?;
+
+        Ok(info)
+    }
+
+    /// The blob, if it exists and `caller` owns it or is an admin
+    pub async fn get(&self, id: &str, caller: &Caller) -> Option<BlobInfo> {
+        let store = self.clone();
+        let (id, caller) = (id.to_string(), caller.clone());
+        tokio::task::spawn_blocking(move || store.get_blocking(&id, &caller))
+            .await
+            .ok()?
+    }
+
+    fn get_blocking(&self, id: &str, caller: &Caller) -> Option<BlobInfo> {
+        let meta = std::fs::metadata(self.path(id)?).ok()?;
+        caller.owns(&self.owners(id).ok()?).then(|| BlobInfo {
+            id: id.to_string(),
+            size: meta.len(),
+        })
+    }
+
+    /// Contents of a blob whose ownership was checked with [`BlobStore::get`]
+    pub async fn read(&self, id: &str) -> Option<Vec<u8>> {
+        tokio::fs::read(self.path(id)?).await.ok()
+    }
+
+    /// Drop `caller` as an owner, removing the blob once it has none; admins
+    /// remove it outright. Returns whether `caller` could see the blob.
+    pub async fn delete(&self, id: &str, caller: &Caller) -> bool {
+        let store = self.clone();
+        let (id, caller) = (id.to_string(), caller.clone());
+        tokio::task::spawn_blocking(move || store.delete_blocking(&id, &caller))
+            .await
+            .unwrap_or(false)
+    }
+
+    fn delete_blocking(&self, id: &str, caller: &Caller) -> bool {
+        let Some(path) = self.path(id) else {
+            return false;
+        };
+        let _guard = self.lock.lock().unwrap();
+        let Ok(mut owners) = self.owners(id) else {
+            return false;
+        };
+        if !path.exists() || !caller.owns(&owners) {
+            return false;
+        }
+
+        owners.remove(&caller.name);
+        if caller.admin || owners.is_empty() {
+            let _ = std::fs::remove_file(self.meta_path(id));
+            std::fs::remove_file(path).is_ok()
+        } else {
+            self.write_owners(id, owners).is_ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+
+    fn caller(name: &str, admin: bool) -> Caller {
+        Caller {
+            name: name.to_string(),
+            admin,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_is_content_addressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path(), 1024).unwrap();
+        let alice = caller("alice", false);
+
+        let first = store.put(Bytes::from_static(b"hello"), &alice).await.unwrap();
+        assert_eq!(
+            first.id,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(store.put(Bytes::from_static(b"hello"), &alice).await.unwrap(), first);
+        assert_eq!(store.get(&first.id, &alice).await, Some(first.clone()));
+
+        assert!(store.delete(&first.id, &alice).await);
+        assert!(store.get(&first.id, &alice).await.is_none());
+        assert!(!store.delete(&first.id, &alice).await);
+    }
+
+    #[tokio::test]
+    async fn test_blobs_are_private_to_their_owners() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path(), 1024).unwrap();
+        let (alice, bob, admin) = (caller("alice", false), caller("bob", false), caller("ops", true));
+
+        let blob = store.put(Bytes::from_static(b"data"), &alice).await.unwrap();
+        assert!(store.get(&blob.id, &bob).await.is_none());
+        assert!(!store.delete(&blob.id, &bob).await);
+        assert_eq!(store.get(&blob.id, &admin).await, Some(blob.clone()));
+
+        // Uploading the same contents shares the blob, and each owner's delete only drops them
+        store.put(Bytes::from_static(b"data"), &bob).await.unwrap();
+        assert!(store.delete(&blob.id, &alice).await);
+        assert!(store.get(&blob.id, &alice).await.is_none());
+        assert!(store.get(&blob.id, &bob).await.is_some());
+
+        assert!(store.delete(&blob.id, &admin).await);
+        assert!(store.get(&blob.id, &bob).await.is_none());
+        assert!(store.read(&blob.id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_store_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path(), 64).unwrap();
+        let alice = caller("alice", false);
+
+        store.put(Bytes::from_static(b"12345"), &alice).await.unwrap();
+        assert_eq!(
+            store.put(Bytes::from(vec![0; 64]), &alice).await.unwrap_err(),
+            BlobError::StoreFull
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_digest_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path(), 1024).unwrap();
+        let admin = caller("ops", true);
+
+        assert!(store.get("../etc/passwd", &admin).await.is_none());
+        assert!(store.read("ABC").await.is_none());
+        assert!(!store.delete(".", &admin).await);
+    }
+}
@@ -0,0 +1,224 @@
+use podman_api::Podman;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::scheduler::Scheduler;
+
+/// How often cached connections are pinged and pruned
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Timeout for a single `_ping`
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The endpoint could not be turned into a Podman client
+    Connect(podman_api::Error),
+    /// The endpoint failed its last health check and still does not answer
+    Unhealthy(String),
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::Connect(e) => write!(f, "Failed to connect to Podman: {}", e),
+            ConnectionError::Unhealthy(endpoint) => {
+                write!(f, "Podman at {} is not responding", endpoint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {}
+
+#[derive(Debug)]
+struct Connection {
+    podman: Podman,
+    healthy: bool,
+}
+
+/// Podman clients shared across requests, keyed by endpoint
+///
+/// Clients are created on first use, pinged in the background, and dropped
+/// once their endpoint is no longer among the scheduler's hosts.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionManager {
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+}
+
+impl ConnectionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the client for `endpoint`, connecting if needed
+    ///
+    /// A connection that failed its last health check is pinged again first,
+    /// so a recovered host is usable without waiting for the next check.
+    pub async fn get(&self, endpoint: &str) -> Result<Podman, ConnectionError> {
+        let cached = self
+            .connections
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .map(|c| (c.podman.clone(), c.healthy));
+
+        match cached {
+            Some((podman, true)) => Ok(podman),
+            Some((podman, false)) => {
+                if ping(&podman).await {
+                    self.set_healthy(endpoint, true);
+                    Ok(podman)
+                } else {
+                    Err(ConnectionError::Unhealthy(endpoint.to_string()))
+                }
+            }
+            None => {
+                let podman = Podman::new(endpoint).map_err(ConnectionError::Connect)?;
+                tracing::debug!("Opened Podman connection to {}", endpoint);
+                self.connections.lock().unwrap().insert(
+                    endpoint.to_string(),
+                    Connection {
+                        podman: podman.clone(),
+                        healthy: true,
+                    },
+                );
+                Ok(podman)
+            }
+        }
+    }
+
+    /// Mark a connection unhealthy after a request on it failed
+    pub fn mark_unhealthy(&self, endpoint: &str) {
+        self.set_healthy(endpoint, false);
+    }
+
+    fn set_healthy(&self, endpoint: &str, healthy: bool) {
+        if let Some(connection) = self.connections.lock().unwrap().get_mut(endpoint) {
+            connection.healthy = healthy;
+        }
+    }
+
+    /// Drop connections to endpoints that are not in `endpoints`
+    pub fn retain(&self, endpoints: &HashSet<String>) {
+        self.connections.lock().unwrap().retain(|endpoint, _| {
+            let keep = endpoints.contains(endpoint);
+            if !keep {
+                tracing::info!("Evicting Podman connection to {}", endpoint);
+            }
+            keep
+        });
+    }
+
+    /// Ping every cached connection and record the result
+    pub async fn check_all(&self) {
+        let connections: Vec<(String, Podman)> = self
+            .connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, c)| (endpoint.clone(), c.podman.clone()))
+            .collect();
+
+        for (endpoint, podman) in connections {
+            let healthy = ping(&podman).await;
+            if !healthy {
+                tracing::warn!("Podman at {} failed its health check", endpoint);
+            }
+            self.set_healthy(&endpoint, healthy);
+        }
+    }
+
+    /// Endpoints with a cached connection and whether they are healthy
+    pub fn endpoints(&self) -> Vec<(String, bool)> {
+        let mut endpoints: Vec<(String, bool)> = self
+            .connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, c)| (endpoint.clone(), c.healthy))
+            .collect();
+        endpoints.sort();
+        endpoints
+    }
+
+    /// Periodically evict endpoints the registry no longer reports and ping the rest
+    pub fn spawn_health_checks(&self, scheduler: Scheduler) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let endpoints = scheduler
+                    .hosts()
+                    .await
+                    .into_iter()
+                    .map(|host| host.endpoint)
+                    .collect();
+                manager.retain(&endpoints);
+                manager.check_all().await;
+            }
+        })
+    }
+}
+
+async fn ping(podman: &Podman) -> bool {
+    matches!(tokio::time::timeout(PING_TIMEOUT, podman.ping()).await, Ok(Ok(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_reuses_connection() {
+        let manager = ConnectionManager::new();
+        manager.get("http://coreos-a:8085").await.unwrap();
+        manager.get("http://coreos-a:8085").await.unwrap();
+        manager.get("http://coreos-b:8085").await.unwrap();
+
+        assert_eq!(
+            manager.endpoints(),
+            vec![
+                ("http://coreos-a:8085".to_string(), true),
+                ("http://coreos-b:8085".to_string(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_endpoint() {
+        let manager = ConnectionManager::new();
+        let err = manager.get("ftp://coreos-a").await.unwrap_err();
+        assert!(matches!(err, ConnectionError::Connect(_)));
+        assert!(manager.endpoints().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retain_evicts_missing_endpoints() {
+        let manager = ConnectionManager::new();
+        manager.get("http://coreos-a:8085").await.unwrap();
+        manager.get("http://coreos-b:8085").await.unwrap();
+
+        manager.retain(&HashSet::from(["http://coreos-b:8085".to_string()]));
+        assert_eq!(
+            manager.endpoints(),
+            vec![("http://coreos-b:8085".to_string(), true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_connection_is_rechecked() {
+        // Nothing listens on this port, so the re-check ping fails
+        let endpoint = "http://127.0.0.1:9";
+        let manager = ConnectionManager::new();
+        manager.get(endpoint).await.unwrap();
+
+        manager.mark_unhealthy(endpoint);
+        let err = manager.get(endpoint).await.unwrap_err();
+        assert!(matches!(err, ConnectionError::Unhealthy(_)));
+        assert_eq!(manager.endpoints(), vec![(endpoint.to_string(), false)]);
+    }
+}
@@ -1,60 +1,328 @@
-use axum::extract::Path;
-use axum::Json;
-use axum::http::StatusCode;
+pub mod artifacts;
+pub mod auth;
+pub mod blobs;
+pub mod connections;
+pub mod network;
+pub mod output;
+pub mod reaper;
+pub mod scheduler;
+pub mod secrets;
+pub mod volumes;
+
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::{Extension, Json, Router, middleware};
+use axum::http::{StatusCode, header};
 use axum::response::IntoResponse;
 use axum::response::sse::{Event, Sse};
-use futures_util::{Stream, TryStreamExt};
+use futures_util::Stream;
 use podman_api::Podman;
 use podman_api::models::Namespace;
 use podman_api::opts::{ContainerCreateOpts, ContainerStopOpts, ContainerWaitOpts};
-use podman_api::opts::{ContainerListOpts, PullOpts, SocketNotifyMode, SystemdEnabled};
+use podman_api::opts::{ContainerListFilter, ContainerListOpts, PullOpts, SocketNotifyMode, SystemdEnabled};
 use serde::Deserialize;
 use serde_json::json;
 use tokio_stream::StreamExt;
+use tracing::Instrument;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::time::Duration;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::router::{OpenApiRouter, UtoipaMethodRouterExt};
+use utoipa_axum::routes;
+
+use artifacts::ArtifactStore;
+use auth::{Caller, CallerTokens};
+use blobs::{BlobInfo, BlobStore};
+use connections::ConnectionManager;
+use network::{AppliedNetwork, NetworkPolicy};
+use output::{OutputLimiter, OutputLimits};
+use reaper::{ContainerGuard, ExecutionLabels, Reaper};
+use scheduler::{Placement, Scheduler};
+use secrets::{ExecutionEnv, SecretStore};
+use xxx_audit::{self as audit, AuditEntry, AuditLog, AuditOutcome, AuditPage, AuditQuery, AuditRecord};
+use service_registry::openapi::Defaults;
+use service_registry::versioning;
+use volumes::InputVolumes;
+
+pub use xxx_types::container::{CreateContainerRequest, CreateContainerResponse, RemoveContainerResponse};
 
 /// Maximum execution time for a container (30 seconds)
 const MAX_EXECUTION_TIME_SECS: u64 = 30;
 
+/// Maximum execution time for a streamed container (10 minutes), which reports output as it runs
+const MAX_STREAM_EXECUTION_TIME_SECS: u64 = 600;
+
+pub use xxx_types::labels::MANAGED_LABEL;
+
+/// Shared state for the container API handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub scheduler: Scheduler,
+    pub connections: ConnectionManager,
+    pub artifacts: ArtifactStore,
+    pub blobs: BlobStore,
+    pub secrets: SecretStore,
+    pub output_limits: OutputLimits,
+    pub audit: AuditLog,
+    pub reaper: Reaper,
+}
+
+impl AppState {
+    pub fn new(
+        scheduler: Scheduler,
+        artifacts: ArtifactStore,
+        blobs: BlobStore,
+        secrets: SecretStore,
+        audit: AuditLog,
+    ) -> Self {
+        Self {
+            scheduler,
+            connections: ConnectionManager::new(),
+            artifacts,
+            blobs,
+            secrets,
+            output_limits: OutputLimits::default(),
+            audit,
+            reaper: Reaper::new("container-api", None),
+        }
+    }
+
+    /// State with every store under `dir` and a Podman socket that doesn't
+    /// exist, for tests here and in the all-in-one binary
+    #[doc(hidden)]
+    pub fn for_tests(dir: &std::path::Path) -> Self {
+        Self::new(
+            Scheduler::new(Default::default(), 1).with_local_host("unix:///nonexistent/podman.sock"),
+            ArtifactStore::open(dir.join("artifacts"), 1024, 1024).unwrap(),
+            BlobStore::open(dir.join("blobs"), 8).unwrap(),
+            SecretStore::open(dir.join("secrets"), [1; 32]).unwrap(),
+            AuditLog::open(dir.join("audit"), "container-api", 1024 * 1024, 1).unwrap(),
+        )
+    }
+
+    pub fn from_env() -> Self {
+        let artifacts = ArtifactStore::from_env().expect("Failed to open artifact store");
+        let blobs = BlobStore::from_env().expect("Failed to open blob store");
+        let secrets = SecretStore::from_env().expect("Failed to open secret store");
+        let audit = AuditLog::from_env("container-api").expect("Failed to open audit log");
+        Self {
+            output_limits: OutputLimits::from_env(),
+            ..Self::new(Scheduler::from_env(), artifacts, blobs, secrets, audit)
+        }
+    }
+
+    /// Label new containers with this owner and sweep orphans with `reaper`
+    pub fn with_reaper(mut self, reaper: Reaper) -> Self {
+        self.reaper = reaper;
+        self
+    }
+
+    /// Start the background connection health checks and the container reaper
+    pub fn spawn_background_tasks(&self) {
+        self.connections.spawn_health_checks(self.scheduler.clone());
+        self.reaper.spawn(self.scheduler.clone(), self.connections.clone());
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Runs commands in short-lived Podman containers"),
+    modifiers(&Defaults),
+    components(schemas(output::Truncation)),
+    tags(
+        (name = "containers", description = "Executions"),
+        (name = "files", description = "Input blobs and collected artifacts"),
+        (name = "secrets", description = "Secrets injected into executions; require an admin token"),
+        (name = "audit", description = "Execution audit log; requires an admin token"),
+    )
+)]
+struct ApiDoc;
+
+/// The `/api/v1` routes of container-api and their deprecated `/api` aliases,
+/// identifying callers with `tokens`
+///
+/// Blob uploads are limited to `blob_max_bytes`.
+pub fn routes(state: AppState, tokens: Option<CallerTokens>, blob_max_bytes: usize) -> Router {
+    let (router, _) = api(blob_max_bytes).split_for_parts();
+    let router = router.route_layer(middleware::from_fn_with_state(tokens, auth::identify_caller));
+    versioning::versioned(router).with_state(state)
+}
+
+/// OpenAPI document of the `/api/v1` routes
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    versioning::document(api(blobs::DEFAULT_BLOB_MAX_BYTES).into_openapi())
+}
+
+fn api(blob_max_bytes: usize) -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(list_containers))
+        .routes(routes!(create_container))
+        .routes(routes!(create_container_stream))
+        .routes(routes!(remove_container))
+        .routes(routes!(get_artifact))
+        .routes(routes!(upload_blob).layer(DefaultBodyLimit::max(blob_max_bytes)))
+        .routes(routes!(get_blob, delete_blob))
+        .routes(routes!(list_secrets))
+        .routes(routes!(get_audit))
+        .routes(routes!(put_secret, delete_secret))
+}
+
 pub async fn health() -> &'static str {
     "Ok"
 }
 
-pub async fn list_containers() -> impl IntoResponse {
-    let podman_url = match service_registry::bootstrap::get_service_endpoint("coreos").await {
-        Some(url) => url,
-        None => std::env::var("COREOS_URL").unwrap_or("http://coreos:8085".to_string()),
-    };
-    let podman = Podman::new(podman_url).unwrap();
+/// Whether `caller` may see and remove a container with these labels: only
+/// execution containers, and only the caller's own unless it is an admin
+fn may_manage(labels: &HashMap<String, String>, caller: &Caller) -> bool {
+    labels.get(MANAGED_LABEL).is_some_and(|value| value == "true")
+        && (caller.admin || labels.get(reaper::CALLER_LABEL) == Some(&caller.name))
+}
 
-    let opts = ContainerListOpts::builder().all(true).build();
-    let containers = podman.containers().list(&opts).await.unwrap();
+/// Names of the caller's execution containers on every Podman host, or of all of them for admins
+#[utoipa::path(
+    get,
+    path = "/containers/list",
+    tag = "containers",
+    security(("bearer" = [])),
+    responses((status = OK, description = "Names of each container", body = Vec<Option<Vec<String>>>))
+)]
+pub async fn list_containers(State(state): State<AppState>, caller: Option<Extension<Caller>>) -> impl IntoResponse {
+    let caller = caller_or_anonymous(&caller);
+    let opts = ContainerListOpts::builder()
+        .all(true)
+        .filter([ContainerListFilter::LabelKeyVal(MANAGED_LABEL.to_string(), "true".to_string())])
+        .build();
+    let mut names = Vec::new();
 
-    let container_strings = containers.iter().map(|container| container.names.clone());
-    Json(container_strings.collect::<Vec<_>>())
+    for host in state.scheduler.hosts().await {
+        let podman = match state.connections.get(&host.endpoint).await {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!("Failed to connect to Podman host {}: {}", host.id, e);
+                continue;
+            }
+        };
+        match podman.containers().list(&opts).await {
+            Ok(containers) => names.extend(
+                containers
+                    .into_iter()
+                    .filter(|container| container.labels.as_ref().is_some_and(|labels| may_manage(labels, &caller)))
+                    .map(|container| container.names),
+            ),
+            Err(e) => {
+                xxx_metrics::podman_error("list");
+                tracing::warn!("Failed to list containers on {}: {}", host.id, e);
+            }
+        }
+    }
+
+    Json(names)
 }
 
-#[derive(Deserialize)]
-pub struct CreateContainerRequest {
-    pub image: String,
-    pub command: Option<Vec<String>>,
+/// The authenticated caller, or the anonymous one when no tokens are configured
+fn caller_or_anonymous(caller: &Option<Extension<Caller>>) -> Caller {
+    caller.as_ref().map_or_else(Caller::anonymous, |Extension(c)| c.clone())
 }
 
-pub async fn create_container(Json(payload): Json<CreateContainerRequest>) -> impl IntoResponse {
-    let podman_url = match service_registry::bootstrap::get_service_endpoint("coreos").await {
-        Some(url) => url,
-        None => std::env::var("COREOS_URL").unwrap_or("http://coreos:8085".to_string()),
-    };
-    let podman = Podman::new(podman_url).unwrap();
+/// Reject malformed artifact globs, mounts and environment before scheduling anything
+async fn validate_request(state: &AppState, caller: &Caller, payload: &CreateContainerRequest) -> Result<(), String> {
+    artifacts::validate_globs(&payload.artifacts)?;
+    volumes::validate_mounts(&payload.mounts, &state.blobs, caller).await?;
+    secrets::validate_env(&payload.env, &payload.secrets, &state.secrets)
+}
 
-    let opts = ContainerCreateOpts::builder()
+/// Decrypt the secrets the request refers to, if they are shared with the caller
+async fn resolve_env(
+    state: &AppState,
+    caller: &Option<Extension<Caller>>,
+    payload: &CreateContainerRequest,
+) -> Result<ExecutionEnv, (StatusCode, String)> {
+    let caller = caller_or_anonymous(caller);
+    let (env, secrets, store) = (payload.env.clone(), payload.secrets.clone(), state.secrets.clone());
+    let resolved = tokio::task::spawn_blocking(move || ExecutionEnv::resolve(&env, &secrets, &store, &caller))
+        .await
+        .unwrap_or_else(|e| Err(secrets::SecretError::Io(e.to_string())));
+    resolved.map_err(|e| match e {
+        secrets::SecretError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+        e => {
+            tracing::error!("Failed to resolve execution environment: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+    })
+}
+
+/// Start the audit record for an execution; it is written when the entry is dropped
+fn audit_entry(state: &AppState, caller: &Option<Extension<Caller>>, payload: &CreateContainerRequest) -> AuditEntry {
+    let caller = caller.as_ref().map_or("anonymous", |Extension(c)| c.name.as_str());
+    let command = payload.command.clone().unwrap_or_default().join("\0");
+    AuditEntry::new(
+        Some(&state.audit),
+        AuditRecord::new("container-api", caller, &payload.image, audit::code_hash(&command)),
+    )
+}
+
+/// Exit code of a finished container
+async fn exit_code(container: &podman_api::api::Container) -> Option<i64> {
+    let inspect = container.inspect().await.ok()?;
+    inspect.state?.exit_code.map(i64::from)
+}
+
+/// Check that the caller may use the requested network policy and resolve it
+fn resolve_network(caller: Option<Extension<Caller>>, policy: &NetworkPolicy) -> Result<AppliedNetwork, (StatusCode, String)> {
+    let caller = caller.map(|Extension(c)| c).unwrap_or_else(Caller::anonymous);
+    network::authorize(policy, &caller).map_err(|msg| (StatusCode::FORBIDDEN, msg))?;
+    let applied = network::resolve(policy);
+    tracing::info!("Caller {} requested network mode {}", caller.name, applied.mode.as_str());
+    Ok(applied)
+}
+
+/// Why an execution could not be started on a host
+struct StartError {
+    message: String,
+    /// The host itself misbehaved, so another host may succeed
+    host_failure: bool,
+}
+
+impl StartError {
+    /// A failed Podman `operation`, counted in `podman_errors_total`
+    fn new(operation: &str, message: String, error: &podman_api::Error) -> Self {
+        xxx_metrics::podman_error(operation);
+        Self {
+            message,
+            host_failure: is_host_failure(error),
+        }
+    }
+}
+
+/// Connection and protocol errors mean the host is unreachable or broken;
+/// API faults (bad image, bad options) would fail on every host
+fn is_host_failure(error: &podman_api::Error) -> bool {
+    matches!(
+        error,
+        podman_api::Error::IO(_) | podman_api::Error::Error(_) | podman_api::Error::InvalidResponse(_)
+    )
+}
+
+fn container_opts(
+    payload: &CreateContainerRequest,
+    network: &AppliedNetwork,
+    volumes: &InputVolumes,
+    env: &ExecutionEnv,
+    execution: &ExecutionLabels,
+) -> ContainerCreateOpts {
+    let mut builder = ContainerCreateOpts::builder()
         .image(&payload.image)
-        .command(payload.command.unwrap_or_default())
-        .net_namespace(Namespace {
-            nsmode: Some("private".to_string()),
-            value: None,
-        })
+        .command(payload.command.clone().unwrap_or_default());
+    if !volumes.is_empty() {
+        builder = builder.volumes(volumes.named_volumes());
+    }
+    if !env.vars().is_empty() {
+        builder = builder.env(env.vars().iter().cloned());
+    }
+    // Labelled so orphans left by a crashed run can be found and reaped
+    builder = builder.labels(execution.pairs());
+
+    network::apply(network, builder)
         .pid_namespace(Namespace {
             nsmode: Some("private".to_string()),
             value: None,
@@ -65,52 +333,184 @@ pub async fn create_container(Json(payload): Json<CreateContainerRequest>) -> im
         })
         .systemd(SystemdEnabled::False)
         .sdnotify_mode(SocketNotifyMode::Ignore)
-        .build();
+        .build()
+}
 
-    println!("Pulling image '{}'...", payload.image);
-    let pull_opts = PullOpts::builder().reference(&payload.image).build();
+/// A container created on a host, with the resources tied to its lifetime
+struct Execution {
+    placement: Placement,
+    podman: Podman,
+    id: String,
+    /// Removes the container and its input volumes, also when dropped early
+    guard: ContainerGuard,
+}
+
+async fn pull_image(podman: &Podman, image: &str) -> Result<(), StartError> {
+    let pull_opts = PullOpts::builder().reference(image).build();
     let images = podman.images();
+    let started = std::time::Instant::now();
     let mut stream = images.pull(&pull_opts);
 
     while let Some(result) = stream.next().await {
         match result {
             Ok(info) => {
-                println!("Pull progress: {:?}", info);
                 if let Some(error_msg) = &info.error {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Failed to pull image '{}': {}", payload.image, error_msg),
-                    )
-                        .into_response();
+                    return Err(StartError {
+                        message: format!("Failed to pull image '{}': {}", image, error_msg),
+                        host_failure: false,
+                    });
                 }
             }
             Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Failed to pull image '{}': {}", payload.image, e),
-                )
-                    .into_response();
+                return Err(StartError::new(
+                    "pull",
+                    format!("Failed to pull image '{}': {}", image, e),
+                    &e,
+                ));
             }
         }
     }
+    xxx_metrics::observe_image_pull(image, started.elapsed());
+    Ok(())
+}
+
+/// Pull the image and create the container on the placed host
+async fn prepare_container(
+    state: &AppState,
+    placement: &Placement,
+    payload: &CreateContainerRequest,
+    network: &AppliedNetwork,
+    env: &ExecutionEnv,
+    execution: &ExecutionLabels,
+) -> Result<(Podman, String, InputVolumes), StartError> {
+    let podman = state.connections.get(&placement.endpoint).await.map_err(|e| StartError {
+        message: e.to_string(),
+        host_failure: true,
+    })?;
+
+    println!("Pulling image '{}' on {}...", payload.image, placement.host_id);
+    pull_image(&podman, &payload.image)
+        .instrument(tracing::info_span!("pull", image = %payload.image, host = %placement.host_id))
+        .await?;
     println!("Successfully pulled image '{}'", payload.image);
 
-    let created = match podman.containers().create(&opts).await {
-        Ok(c) => c,
+    let volumes = InputVolumes::create(&podman, &payload.image, &payload.mounts, &state.blobs, execution)
+        .await
+        .map_err(|e| StartError::new("volume", format!("Failed to prepare input mounts: {}", e), &e))?;
+
+    let created = podman
+        .containers()
+        .create(&container_opts(payload, network, &volumes, env, execution))
+        .instrument(tracing::info_span!("create", image = %payload.image))
+        .await
+        .map_err(|e| StartError::new("create", format!("Failed to create container: {}", e), &e))?;
+
+    Ok((podman, created.id, volumes))
+}
+
+/// Place the execution and prepare its container, failing over to another
+/// host when one is unreachable
+async fn schedule_container(
+    state: &AppState,
+    payload: &CreateContainerRequest,
+    network: &AppliedNetwork,
+    env: &ExecutionEnv,
+    execution: &ExecutionLabels,
+) -> Result<Execution, (StatusCode, String)> {
+    let mut tried = Vec::new();
+
+    loop {
+        let placement = state
+            .scheduler
+            .acquire(&tried)
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+
+        match prepare_container(state, &placement, payload, network, env, execution).await {
+            Ok((podman, id, volumes)) => {
+                return Ok(Execution {
+                    placement,
+                    guard: ContainerGuard::new(podman.clone(), id.clone(), volumes),
+                    podman,
+                    id,
+                });
+            }
+            Err(e) if e.host_failure => {
+                let message = env.masker().mask(&e.message);
+                tracing::warn!("{} on {}; trying another host", message, placement.host_id);
+                state.scheduler.mark_failed(&placement.host_id);
+                state.connections.mark_unhealthy(&placement.endpoint);
+                tried.push(placement.host_id.clone());
+            }
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, env.masker().mask(&e.message))),
+        }
+    }
+}
+
+/// Run a container to completion and return its output
+#[utoipa::path(
+    post,
+    path = "/containers/create",
+    tag = "containers",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "The execution finished", body = CreateContainerResponse),
+        (status = BAD_REQUEST, description = "Invalid artifacts, mounts or environment", body = String),
+        (status = FORBIDDEN, description = "The caller may not use the network policy or a referenced secret", body = String),
+        (status = REQUEST_TIMEOUT, description = "The execution exceeded the time limit", body = String),
+    )
+)]
+pub async fn create_container(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Json(payload): Json<CreateContainerRequest>,
+) -> impl IntoResponse {
+    let mut audit = audit_entry(&state, &caller, &payload);
+    let owner = caller_or_anonymous(&caller);
+
+    if let Err(e) = validate_request(&state, &owner, &payload).await {
+        audit.fail(AuditOutcome::Blocked, &e);
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+
+    let env = match resolve_env(&state, &caller, &payload).await {
+        Ok(env) => env,
         Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to create container: {}", e),
-            )
-                .into_response();
+            let outcome = if e.0 == StatusCode::FORBIDDEN { AuditOutcome::Blocked } else { AuditOutcome::Error };
+            audit.fail(outcome, &e.1);
+            return e.into_response();
         }
     };
 
-    let id = created.id;
+    let network = match resolve_network(caller, &payload.network) {
+        Ok(network) => network,
+        Err(e) => {
+            audit.fail(AuditOutcome::Blocked, &e.1);
+            return e.into_response();
+        }
+    };
+
+    let execution = ExecutionLabels::new(state.reaper.owner(), &owner.name, Duration::from_secs(MAX_EXECUTION_TIME_SECS));
+    let Execution {
+        placement,
+        podman,
+        id,
+        guard,
+    } = match schedule_container(&state, &payload, &network, &env, &execution).await {
+        Ok(execution) => execution,
+        Err(e) => {
+            audit.fail(AuditOutcome::Error, &e.1);
+            return e.into_response();
+        }
+    };
+    audit.record.container_id = Some(id.clone());
+    audit.record.host = Some(placement.host_id.clone());
 
     let container = podman.containers().get(&id);
 
-    if let Err(e) = container.start(None).await {
+    if let Err(e) = container.start(None).instrument(tracing::info_span!("start", container = %id)).await {
+        xxx_metrics::podman_error("start");
+        audit.fail(AuditOutcome::Error, format!("Container failed to start: {}", e));
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Container created but failed to start: {}", e),
@@ -120,138 +520,158 @@ pub async fn create_container(Json(payload): Json<CreateContainerRequest>) -> im
 
     println!("Container '{}' started, waiting for completion...", id);
 
-    // Wait for the container to finish with timeout
-    let wait_result = tokio::time::timeout(
-        Duration::from_secs(MAX_EXECUTION_TIME_SECS),
-        container.wait(&ContainerWaitOpts::builder().build())
-    ).await;
+    // Follow the logs while the container runs, so the output ceiling can stop it early
+    let mut masker = env.masker().stream();
+    let mut limiter = OutputLimiter::new(state.output_limits);
+    let run = async {
+        let logs_opts = podman_api::opts::ContainerLogsOpts::builder()
+            .stdout(true)
+            .stderr(true)
+            .follow(true)
+            .build();
+        let mut logs = Box::pin(container.logs(&logs_opts));
+        let mut output = String::new();
 
-    match wait_result {
-        Ok(Ok(_)) => {
-            // Container finished successfully
+        async {
+            while let Some(chunk) = logs.next().await {
+                let chunk = chunk.map_err(|e| format!("Failed to get container logs: {}", e))?;
+                output.push_str(&limiter.push(&masker.push(&String::from_utf8_lossy(chunk.as_ref()))));
+                if limiter.exhausted() {
+                    tracing::warn!("Container '{}' reached the output limit, terminating", id);
+                    let _ = container.kill().await;
+                    break;
+                }
+            }
+            output.push_str(&limiter.push(&masker.finish()));
+            Ok::<_, String>(())
         }
+        .instrument(tracing::info_span!("logs", container = %id))
+        .await?;
+
+        container
+            .wait(&ContainerWaitOpts::builder().build())
+            .instrument(tracing::info_span!("wait", container = %id))
+            .await
+            .map_err(|e| format!("Error waiting for container to finish: {}", e))?;
+        Ok::<_, String>(output)
+    };
+
+    let logs = match tokio::time::timeout(Duration::from_secs(MAX_EXECUTION_TIME_SECS), run).await {
+        Ok(Ok(output)) => output,
         Ok(Err(e)) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error waiting for container to finish: {}", e),
-            )
-                .into_response();
+            audit.fail(AuditOutcome::Error, &e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
         }
         Err(_) => {
+            audit.fail(AuditOutcome::Timeout, "exceeded maximum execution time");
             // Timeout occurred - forcibly stop the container
             tracing::warn!("Container '{}' exceeded maximum execution time, terminating", id);
             let _ = container.stop(&ContainerStopOpts::builder().timeout(5).build()).await;
-            let _ = container.remove().await;
+            guard.cleanup().await;
             return (
                 StatusCode::REQUEST_TIMEOUT,
                 format!("Container execution exceeded maximum time limit of {} seconds", MAX_EXECUTION_TIME_SECS),
             )
                 .into_response();
         }
-    }
-
-    // Get container logs (stdout + stderr)
-    let logs = match container.logs(
-        &podman_api::opts::ContainerLogsOpts::builder()
-            .stdout(true)
-            .stderr(true)
-            .build()
-    ).try_collect::<Vec<_>>().await {
-        Ok(chunks) => {
-            chunks.iter()
-                .map(|chunk| String::from_utf8_lossy(chunk.as_ref()))
-                .collect::<String>()
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to get container logs: {}", e),
-            )
-                .into_response();
-        }
     };
 
-    // Clean up the container
-    let _ = container.remove().await;
+    let exit_code = exit_code(&container).await;
+    audit.complete(exit_code);
+
+    // Collect requested files before the container is gone
+    let artifacts = artifacts::collect(&state.artifacts, &container, &payload.artifacts, &owner).await;
+
+    // Clean up the container and its input volumes
+    guard.cleanup().await;
 
     println!("Container '{}' completed successfully", id);
     (
         StatusCode::OK,
-        Json(json!({
-            "id": id,
-            "message": "Container executed successfully",
-            "output": logs
-        })),
+        Json(CreateContainerResponse {
+            id,
+            execution_id: execution.execution_id,
+            host: placement.host_id.clone(),
+            network,
+            artifacts,
+            message: if limiter.exhausted() {
+                format!("Output limit of {} bytes reached; container terminated", state.output_limits.max_bytes)
+            } else {
+                "Container executed successfully".to_string()
+            },
+            output: Some(logs),
+            exit_code,
+            truncated: limiter.truncated(),
+        }),
     )
         .into_response()
 }
 
+/// Run a container, streaming its output as server-sent events
+///
+/// Unnamed events carry output; failures are sent as `ERROR: ...` data.
+/// Named events: `network` (the applied policy), `truncated` (a `Truncation`),
+/// `exit` (`{ "exit_code": ... }`), `artifacts` (an `ArtifactInfo` list) and
+/// finally `done`.
+#[utoipa::path(
+    post,
+    path = "/containers/create/stream",
+    tag = "containers",
+    security(("bearer" = [])),
+    responses((status = OK, description = "Event stream of the execution", content_type = "text/event-stream", body = String))
+)]
 pub async fn create_container_stream(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
     Json(payload): Json<CreateContainerRequest>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let stream = async_stream::stream! {
-        let podman_url = match service_registry::bootstrap::get_service_endpoint("coreos").await {
-            Some(url) => url,
-            None => std::env::var("COREOS_URL").unwrap_or("http://coreos:8085".to_string()),
-        };
+        // Written when the stream ends, including when the client disconnects
+        let mut audit = audit_entry(&state, &caller, &payload);
+        let owner = caller_or_anonymous(&caller);
 
-        let podman = match Podman::new(podman_url) {
-            Ok(p) => p,
-            Err(e) => {
-                yield Ok(Event::default().data(format!("ERROR: Failed to connect to Podman: {}", e)));
+        if let Err(e) = validate_request(&state, &owner, &payload).await {
+            audit.fail(AuditOutcome::Blocked, &e);
+            yield Ok(Event::default().data(format!("ERROR: {}", e)));
+            return;
+        }
+
+        let env = match resolve_env(&state, &caller, &payload).await {
+            Ok(env) => env,
+            Err((status, message)) => {
+                let outcome = if status == StatusCode::FORBIDDEN { AuditOutcome::Blocked } else { AuditOutcome::Error };
+                audit.fail(outcome, &message);
+                yield Ok(Event::default().data(format!("ERROR: {}", message)));
                 return;
             }
         };
 
-        let opts = ContainerCreateOpts::builder()
-            .image(&payload.image)
-            .command(payload.command.unwrap_or_default())
-            .net_namespace(Namespace {
-                nsmode: Some("private".to_string()),
-                value: None,
-            })
-            .pid_namespace(Namespace {
-                nsmode: Some("private".to_string()),
-                value: None,
-            })
-            .ipc_namespace(Namespace {
-                nsmode: Some("private".to_string()),
-                value: None,
-            })
-            .systemd(SystemdEnabled::False)
-            .sdnotify_mode(SocketNotifyMode::Ignore)
-            .build();
-
-        // Pull image
-        let pull_opts = PullOpts::builder().reference(&payload.image).build();
-        let images = podman.images();
-        let mut pull_stream = images.pull(&pull_opts);
-
-        while let Some(result) = pull_stream.next().await {
-            match result {
-                Ok(info) => {
-                    if let Some(error_msg) = &info.error {
-                        yield Ok(Event::default().data(format!("ERROR: Failed to pull image '{}': {}", payload.image, error_msg)));
-                        return;
-                    }
-                }
-                Err(e) => {
-                    yield Ok(Event::default().data(format!("ERROR: Failed to pull image '{}': {}", payload.image, e)));
-                    return;
-                }
+        let network = match resolve_network(caller, &payload.network) {
+            Ok(network) => network,
+            Err((_, message)) => {
+                audit.fail(AuditOutcome::Blocked, &message);
+                yield Ok(Event::default().data(format!("ERROR: {}", message)));
+                return;
             }
-        }
+        };
 
-        // Create container
-        let created = match podman.containers().create(&opts).await {
-            Ok(c) => c,
-            Err(e) => {
-                yield Ok(Event::default().data(format!("ERROR: Failed to create container: {}", e)));
+        // The placement holds the host slot until the stream ends. The guard removes the
+        // container and its volumes, in the background if the client disconnects first.
+        let execution = ExecutionLabels::new(state.reaper.owner(), &owner.name, Duration::from_secs(MAX_STREAM_EXECUTION_TIME_SECS));
+        let Execution { placement, podman, id, guard } = match schedule_container(&state, &payload, &network, &env, &execution).await {
+            Ok(execution) => execution,
+            Err((_, message)) => {
+                audit.fail(AuditOutcome::Error, &message);
+                yield Ok(Event::default().data(format!("ERROR: {}", message)));
                 return;
             }
         };
+        audit.record.container_id = Some(id.clone());
+        audit.record.host = Some(placement.host_id.clone());
+
+        // Report the applied policy before any output
+        yield Ok(Event::default().event("network").data(json!(network).to_string()));
 
-        let id = created.id.clone();
         let container = podman.containers().get(&id);
 
         // Attach to container to get output stream
@@ -264,24 +684,57 @@ pub async fn create_container_stream(
         let mut attach_stream = match container.attach(&attach_opts).await {
             Ok(stream) => stream,
             Err(e) => {
+                xxx_metrics::podman_error("attach");
+                audit.fail(AuditOutcome::Error, format!("Failed to attach to container: {}", e));
                 yield Ok(Event::default().data(format!("ERROR: Failed to attach to container: {}", e)));
                 return;
             }
         };
 
         // Start container after attaching
-        if let Err(e) = container.start(None).await {
+        if let Err(e) = container.start(None).instrument(tracing::info_span!("start", container = %id)).await {
+            xxx_metrics::podman_error("start");
+            audit.fail(AuditOutcome::Error, format!("Container failed to start: {}", e));
             yield Ok(Event::default().data(format!("ERROR: Container failed to start: {}", e)));
             return;
         }
 
-        // Stream output as it comes in
-        while let Some(chunk_result) = attach_stream.next().await {
+        // Stream output as it comes in, with secret values masked and output limits applied.
+        // The stream cannot be instrumented across yields, so the span only times the collection.
+        let logs_span = tracing::info_span!("logs", container = %id);
+        let mut masker = env.masker().stream();
+        let mut limiter = OutputLimiter::new(state.output_limits);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(MAX_STREAM_EXECUTION_TIME_SECS);
+        loop {
+            let Ok(next) = tokio::time::timeout_at(deadline, attach_stream.next()).await else {
+                audit.fail(AuditOutcome::Timeout, "exceeded maximum execution time");
+                tracing::warn!("Container '{}' exceeded maximum execution time, terminating", id);
+                let _ = container.stop(&ContainerStopOpts::builder().timeout(5).build()).await;
+                guard.cleanup().await;
+                yield Ok(Event::default().data(format!(
+                    "ERROR: Container execution exceeded maximum time limit of {} seconds",
+                    MAX_STREAM_EXECUTION_TIME_SECS
+                )));
+                return;
+            };
+            let Some(chunk_result) = next else {
+                break;
+            };
             match chunk_result {
                 Ok(chunk) => {
-                    let output = String::from_utf8_lossy(&chunk);
+                    let lines_truncated = limiter.lines_truncated();
+                    let output = limiter.push(&masker.push(&String::from_utf8_lossy(&chunk)));
                     if !output.is_empty() {
-                        yield Ok(Event::default().data(output.to_string()));
+                        yield Ok(Event::default().data(output));
+                    }
+                    if lines_truncated == 0 && limiter.lines_truncated() > 0 {
+                        yield Ok(Event::default().event("truncated").data(json!(limiter.line_truncation()).to_string()));
+                    }
+                    if limiter.exhausted() {
+                        tracing::warn!("Container '{}' reached the output limit, terminating", id);
+                        yield Ok(Event::default().event("truncated").data(json!(limiter.output_truncation()).to_string()));
+                        let _ = container.kill().await;
+                        break;
                     }
                 }
                 Err(e) => {
@@ -290,12 +743,28 @@ pub async fn create_container_stream(
                 }
             }
         }
+        let rest = limiter.push(&masker.finish());
+        if !rest.is_empty() {
+            yield Ok(Event::default().data(rest));
+        }
+        drop(logs_span);
 
         // Wait for container to finish
-        let _ = container.wait(&ContainerWaitOpts::builder().build()).await;
+        let _ = container
+            .wait(&ContainerWaitOpts::builder().build())
+            .instrument(tracing::info_span!("wait", container = %id))
+            .await;
+        let exit_code = exit_code(&container).await;
+        audit.complete(exit_code);
+        yield Ok(Event::default().event("exit").data(json!({ "exit_code": exit_code }).to_string()));
+
+        if !payload.artifacts.is_empty() {
+            let artifacts = artifacts::collect(&state.artifacts, &container, &payload.artifacts, &owner).await;
+            yield Ok(Event::default().event("artifacts").data(json!(artifacts).to_string()));
+        }
 
         // Clean up
-        let _ = container.remove().await;
+        guard.cleanup().await;
 
         yield Ok(Event::default().event("done").data("Container execution completed"));
     };
@@ -303,20 +772,292 @@ pub async fn create_container_stream(
     Sse::new(stream)
 }
 
-pub async fn remove_container(Path(id): Path<String>) -> impl IntoResponse {
-    let podman_url = match service_registry::bootstrap::get_service_endpoint("coreos").await {
-        Some(url) => url,
-        None => std::env::var("COREOS_URL").unwrap_or("http://coreos:8085".to_string()),
+/// Store an input file for later mounts
+#[utoipa::path(
+    post,
+    path = "/blobs",
+    tag = "files",
+    security(("bearer" = [])),
+    request_body(content_type = "application/octet-stream", description = "File contents"),
+    responses(
+        (status = CREATED, description = "Stored", body = BlobInfo),
+        (status = PAYLOAD_TOO_LARGE, description = "The file exceeds `BLOB_MAX_BYTES`"),
+        (status = INSUFFICIENT_STORAGE, description = "The blob store is full", body = String),
+    )
+)]
+pub async fn upload_blob(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    match state.blobs.put(body, &caller_or_anonymous(&caller)).await {
+        Ok(info) => (StatusCode::CREATED, Json(json!(info))).into_response(),
+        Err(e @ blobs::BlobError::StoreFull) => (StatusCode::INSUFFICIENT_STORAGE, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/blobs/{id}",
+    tag = "files",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "The blob exists", body = BlobInfo),
+        (status = NOT_FOUND, description = "No such blob, or it belongs to another caller", body = String),
+    )
+)]
+pub async fn get_blob(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.blobs.get(&id, &caller_or_anonymous(&caller)).await {
+        Some(info) => (StatusCode::OK, Json(json!(info))).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Blob '{}' not found", id)).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/blobs/{id}",
+    tag = "files",
+    security(("bearer" = [])),
+    responses(
+        (status = NO_CONTENT, description = "Deleted"),
+        (status = NOT_FOUND, description = "No such blob, or it belongs to another caller", body = String),
+    )
+)]
+pub async fn delete_blob(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if state.blobs.delete(&id, &caller_or_anonymous(&caller)).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (StatusCode::NOT_FOUND, format!("Blob '{}' not found", id)).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PutSecretRequest {
+    pub value: String,
+    /// Names of the non-admin callers that may reference the secret
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+/// Secrets and the audit log are for admin callers only
+fn require_admin(caller: Option<Extension<Caller>>) -> Result<Caller, (StatusCode, String)> {
+    let caller = caller.map(|Extension(c)| c).unwrap_or_else(Caller::anonymous);
+    if caller.admin {
+        Ok(caller)
+    } else {
+        Err((StatusCode::FORBIDDEN, "This endpoint requires an admin token".to_string()))
+    }
+}
+
+/// Query executions, newest first; see [`AuditQuery`] for the filters
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    security(("bearer" = [])),
+    params(AuditQuery),
+    responses(
+        (status = OK, description = "A page of audit records", body = AuditPage),
+        (status = FORBIDDEN, description = "Admin token required", body = String),
+    )
+)]
+pub async fn get_audit(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = require_admin(caller) {
+        return e.into_response();
+    }
+
+    match state.audit.query(query).await {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read audit log: {}", e)).into_response(),
+    }
+}
+
+/// Names of the stored secrets; values are never returned
+#[utoipa::path(
+    get,
+    path = "/secrets",
+    tag = "secrets",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Secret names", body = Vec<String>),
+        (status = FORBIDDEN, description = "Admin token required", body = String),
+    )
+)]
+pub async fn list_secrets(State(state): State<AppState>, caller: Option<Extension<Caller>>) -> impl IntoResponse {
+    if let Err(e) = require_admin(caller) {
+        return e.into_response();
+    }
+
+    let store = state.secrets.clone();
+    match tokio::task::spawn_blocking(move || store.names()).await {
+        Ok(names) => Json(names).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/secrets/{name}",
+    tag = "secrets",
+    security(("bearer" = [])),
+    responses(
+        (status = NO_CONTENT, description = "Stored"),
+        (status = BAD_REQUEST, description = "Invalid name or value too large", body = String),
+        (status = FORBIDDEN, description = "Admin token required", body = String),
+    )
+)]
+pub async fn put_secret(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(name): Path<String>,
+    Json(payload): Json<PutSecretRequest>,
+) -> impl IntoResponse {
+    let caller = match require_admin(caller) {
+        Ok(caller) => caller,
+        Err(e) => return e.into_response(),
     };
-    let podman = match Podman::new(podman_url) {
-        Ok(p) => p,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to connect to Podman service: {}", e),
-            )
-                .into_response();
+
+    let store = state.secrets.clone();
+    let stored = {
+        let name = name.clone();
+        tokio::task::spawn_blocking(move || store.put(&name, &payload.value, &payload.allow))
+            .await
+            .unwrap_or_else(|e| Err(secrets::SecretError::Io(e.to_string())))
+    };
+    match stored {
+        Ok(()) => {
+            tracing::info!("Caller {} stored secret {}", caller.name, name);
+            StatusCode::NO_CONTENT.into_response()
         }
+        Err(e @ (secrets::SecretError::InvalidName(_) | secrets::SecretError::TooLarge)) => {
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/secrets/{name}",
+    tag = "secrets",
+    security(("bearer" = [])),
+    responses(
+        (status = NO_CONTENT, description = "Deleted"),
+        (status = NOT_FOUND, description = "No such secret", body = String),
+        (status = FORBIDDEN, description = "Admin token required", body = String),
+    )
+)]
+pub async fn delete_secret(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let caller = match require_admin(caller) {
+        Ok(caller) => caller,
+        Err(e) => return e.into_response(),
+    };
+
+    let store = state.secrets.clone();
+    let deleted = {
+        let name = name.clone();
+        tokio::task::spawn_blocking(move || store.delete(&name)).await.unwrap_or(false)
+    };
+    if deleted {
+        tracing::info!("Caller {} deleted secret {}", caller.name, name);
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (StatusCode::NOT_FOUND, format!("Secret '{}' not found", name)).into_response()
+    }
+}
+
+/// Download a file collected from an execution
+#[utoipa::path(
+    get,
+    path = "/artifacts/{id}",
+    tag = "files",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "The file contents", content_type = "application/octet-stream"),
+        (status = NOT_FOUND, description = "No such artifact, or it belongs to another caller", body = String),
+    )
+)]
+pub async fn get_artifact(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.artifacts.read(&id, &caller_or_anonymous(&caller)).await {
+        Some((info, contents)) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", info.file_name().replace('"', "")),
+                ),
+            ],
+            contents,
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Artifact '{}' not found", id)).into_response(),
+    }
+}
+
+/// Stop and remove an execution container on whichever host has it
+#[utoipa::path(
+    delete,
+    path = "/containers/{id}",
+    tag = "containers",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Removed", body = RemoveContainerResponse),
+        (status = NOT_FOUND, description = "No host has the container, or it is not one of the caller's executions", body = String),
+    )
+)]
+pub async fn remove_container(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let caller = caller_or_anonymous(&caller);
+
+    // Containers may live on any host; use the first one that knows the id
+    let mut podman = None;
+    for host in state.scheduler.hosts().await {
+        match state.connections.get(&host.endpoint).await {
+            Ok(p) => {
+                if let Ok(inspect) = p.containers().get(&id).inspect().await {
+                    let labels = inspect.config.and_then(|config| config.labels).unwrap_or_default();
+                    if may_manage(&labels, &caller) {
+                        podman = Some(p);
+                    } else {
+                        tracing::warn!("Caller {} may not remove container {} on {}", caller.name, id, host.id);
+                    }
+                    break;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to connect to Podman host {}: {}", host.id, e),
+        }
+    }
+    let Some(podman) = podman else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Container '{}' not found on any Podman host", id),
+        )
+            .into_response();
     };
 
     let container = podman.containers().get(&id);
@@ -338,14 +1079,15 @@ pub async fn remove_container(Path(id): Path<String>) -> impl IntoResponse {
             println!("Container '{}' removed successfully", id);
             (
                 StatusCode::OK,
-                Json(json!({
-                    "id": id,
-                    "message": "Container removed successfully"
-                })),
+                Json(RemoveContainerResponse {
+                    id,
+                    message: "Container removed successfully".to_string(),
+                }),
             )
                 .into_response()
         }
         Err(e) => {
+            xxx_metrics::podman_error("remove");
             println!("Failed to remove container '{}': {}", id, e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -408,5 +1150,228 @@ mod tests {
         let request: CreateContainerRequest = serde_json::from_str(json).unwrap();
         assert_eq!(request.image, "python:3.11");
         assert_eq!(request.command, None);
+        assert_eq!(request.network, NetworkPolicy::None);
+    }
+
+    #[test]
+    fn test_create_container_request_with_network() {
+        let json = r#"{"image":"python:3.11","network":{"mode":"full"}}"#;
+        let request: CreateContainerRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.network, NetworkPolicy::Full);
+    }
+
+    #[tokio::test]
+    async fn test_get_artifact_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let info = state
+            .artifacts
+            .put("/work/plot.png", b"png-bytes".to_vec(), &Caller::anonymous())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/artifacts/{id}", get(get_artifact))
+            .with_state(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/artifacts/{}", info.id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"plot.png\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"png-bytes");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/artifacts/unknown")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_execution_is_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let app = |caller: Caller| {
+            Router::new()
+                .route("/api/containers/create", axum::routing::post(create_container))
+                .route("/api/audit", get(get_audit))
+                .layer(Extension(caller))
+                .with_state(state.clone())
+        };
+        let ci = Caller { name: "ci".to_string(), admin: false };
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/containers/create")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"image":"alpine","command":["ls"],"artifacts":["relative/*.txt"]}"#))
+            .unwrap();
+        let response = app(ci.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let audit_request = || Request::builder().uri("/api/audit?caller=ci").body(Body::empty()).unwrap();
+        let response = app(ci).oneshot(audit_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let admin = Caller { name: "ops".to_string(), admin: true };
+        let response = app(admin).oneshot(audit_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["total"], 1);
+        let record = &page["records"][0];
+        assert_eq!(record["caller"], "ci");
+        assert_eq!(record["image"], "alpine");
+        assert_eq!(record["outcome"], "blocked");
+        assert_eq!(record["code_hash"], audit::code_hash("ls"));
+    }
+
+    #[tokio::test]
+    async fn test_secret_endpoints_require_admin() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let app = |caller: Caller| {
+            Router::new()
+                .route("/api/secrets", get(list_secrets))
+                .route("/api/secrets/{name}", axum::routing::put(put_secret).delete(delete_secret))
+                .layer(Extension(caller))
+                .with_state(state.clone())
+        };
+        let admin = Caller { name: "ops".to_string(), admin: true };
+        let put = Request::builder()
+            .method("PUT")
+            .uri("/api/secrets/api-token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"value":"s3cr3t"}"#))
+            .unwrap();
+
+        let response = app(Caller::anonymous()).oneshot(put).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let put = Request::builder()
+            .method("PUT")
+            .uri("/api/secrets/api-token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"value":"s3cr3t"}"#))
+            .unwrap();
+        let response = app(admin.clone()).oneshot(put).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(state.secrets.get("api-token").unwrap().unwrap().value, "s3cr3t");
+
+        let response = app(admin)
+            .oneshot(Request::builder().uri("/api/secrets").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"["api-token"]"#);
+    }
+
+    #[tokio::test]
+    async fn test_blob_endpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let app = Router::new()
+            .route("/api/blobs", axum::routing::post(upload_blob))
+            .route("/api/blobs/{id}", get(get_blob).delete(delete_blob))
+            .with_state(state);
+        let request = |method: &str, uri: &str, body: &'static [u8]| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request("POST", "/api/blobs", b"a,b\n")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["size"], 4);
+        let uri = format!("/api/blobs/{}", info["id"].as_str().unwrap());
+
+        let response = app.clone().oneshot(request("GET", &uri, b"")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request("POST", "/api/blobs", b"too large")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+        let response = app.clone().oneshot(request("DELETE", &uri, b"")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = app.oneshot(request("GET", &uri, b"")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_openapi_matches_published() {
+        let published = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if let Err(e) = service_registry::openapi::check_published(&openapi(), published) {
+            panic!("{}", e);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_container_route_takes_an_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/api/containers/abc123")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = routes(state, None, 1024).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"Container 'abc123' not found on any Podman host");
+    }
+
+    #[test]
+    fn test_callers_manage_only_their_own_executions() {
+        let alice = Caller { name: "alice".to_string(), admin: false };
+        let bob = Caller { name: "bob".to_string(), admin: false };
+        let admin = Caller { name: "ops".to_string(), admin: true };
+        let execution: HashMap<String, String> = ExecutionLabels::new("container-api-1", "alice", Duration::from_secs(30))
+            .pairs()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+
+        assert!(may_manage(&execution, &alice));
+        assert!(!may_manage(&execution, &bob));
+        assert!(may_manage(&execution, &admin));
+
+        // Containers that are not executions are off limits, also to admins
+        let unmanaged = HashMap::from([(reaper::CALLER_LABEL.to_string(), "alice".to_string())]);
+        assert!(!may_manage(&unmanaged, &alice));
+        assert!(!may_manage(&HashMap::new(), &admin));
+    }
+
+    #[test]
+    fn test_full_network_forbidden_for_anonymous_caller() {
+        let err = resolve_network(None, &NetworkPolicy::Full).unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+
+        let admin = Caller { name: "ops".to_string(), admin: true };
+        let applied = resolve_network(Some(Extension(admin)), &NetworkPolicy::Full).unwrap();
+        assert_eq!(applied.mode, network::NetworkMode::Full);
     }
 }
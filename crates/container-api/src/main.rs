@@ -1,34 +1,42 @@
-use axum::{Router, routing::get};
-use container_api::{create_container, create_container_stream, health, list_containers, remove_container};
-use service_registry::register_service;
+use axum::{Router, middleware, routing::get};
+use container_api::auth::CallerTokens;
+use container_api::reaper::Reaper;
+use container_api::{AppState, health};
+use service_registry::{metrics, openapi, register_service, telemetry, versioning, HealthCheck};
 use tower_http::trace::TraceLayer;
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
 
-    tracing_subscriber::fmt::init();
+    let _telemetry = telemetry::init("container-api");
 
     // Register service with etcd
-    let (service, _lease_id) = register_service!("container-api", "container-api", 3000).await;
+    let (service, _lease_id) =
+        register_service!("container-api", "container-api", 3000, HealthCheck::http("/healthz")).await;
     tracing::info!("Service registered: {} ({})", service.name, service.id);
 
+    let state = AppState::from_env().with_reaper(Reaper::from_env(&service.id));
+    state.spawn_background_tasks();
+
+    let tokens = CallerTokens::from_env().expect("Invalid CONTAINER_API_TOKENS");
+    if tokens.is_none() {
+        tracing::warn!("No caller tokens configured; all callers are anonymous and full network egress is disabled");
+    }
+
+    let blob_max_bytes = std::env::var("BLOB_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(container_api::blobs::DEFAULT_BLOB_MAX_BYTES);
+
     let app = Router::new()
         .route("/healthz", get(health))
-        .route("/api/containers/list", get(list_containers))
-        .route(
-            "/api/containers/create",
-            axum::routing::post(create_container),
-        )
-        .route(
-            "/api/containers/create/stream",
-            axum::routing::post(create_container_stream),
-        )
-        .route(
-            "/api/containers",
-            axum::routing::delete(remove_container),
-        )
-        .layer(TraceLayer::new_for_http());
+        .route("/metrics", get(metrics::handler))
+        .merge(container_api::routes(state, tokens, blob_max_bytes))
+        .merge(openapi::routes(container_api::openapi()))
+        .merge(versioning::routes("container-api", env!("CARGO_PKG_VERSION")))
+        .layer(middleware::from_fn_with_state("container-api", metrics::track))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     println!("Server listening on {}", listener.local_addr().unwrap());
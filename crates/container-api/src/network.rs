@@ -0,0 +1,76 @@
+use podman_api::models::Namespace;
+use podman_api::opts::ContainerCreateOptsBuilder;
+
+use crate::auth::Caller;
+
+pub use xxx_types::container::{AppliedNetwork, NetworkMode, NetworkPolicy};
+
+/// Check that `caller` may use this policy
+pub fn authorize(policy: &NetworkPolicy, caller: &Caller) -> Result<(), String> {
+    match policy {
+        NetworkPolicy::None => Ok(()),
+        NetworkPolicy::Full if caller.admin => Ok(()),
+        NetworkPolicy::Full => Err("Full network egress requires an admin token".to_string()),
+    }
+}
+
+/// The policy to report back and apply to the container
+pub fn resolve(policy: &NetworkPolicy) -> AppliedNetwork {
+    let mode = match policy {
+        NetworkPolicy::None => NetworkMode::None,
+        NetworkPolicy::Full => NetworkMode::Full,
+    };
+    AppliedNetwork { mode }
+}
+
+/// Configure the container's network namespace for this policy
+pub fn apply(network: &AppliedNetwork, builder: ContainerCreateOptsBuilder) -> ContainerCreateOptsBuilder {
+    let nsmode = match network.mode {
+        NetworkMode::None => "private",
+        NetworkMode::Full => "bridge",
+    };
+    builder.net_namespace(Namespace {
+        nsmode: Some(nsmode.to_string()),
+        value: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(admin: bool) -> Caller {
+        Caller {
+            name: "test".to_string(),
+            admin,
+        }
+    }
+
+    #[test]
+    fn test_policy_deserialization() {
+        let policy: NetworkPolicy = serde_json::from_str(r#"{"mode":"none"}"#).unwrap();
+        assert_eq!(policy, NetworkPolicy::None);
+
+        let policy: NetworkPolicy = serde_json::from_str(r#"{"mode":"full"}"#).unwrap();
+        assert_eq!(policy, NetworkPolicy::Full);
+
+        assert!(serde_json::from_str::<NetworkPolicy>(r#"{"mode":"open"}"#).is_err());
+        assert!(serde_json::from_str::<NetworkPolicy>(r#"{"mode":"allowlist","allow":["pypi.org"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_full_egress_requires_admin() {
+        assert!(authorize(&NetworkPolicy::Full, &caller(false)).is_err());
+        assert!(authorize(&NetworkPolicy::Full, &caller(true)).is_ok());
+        assert!(authorize(&NetworkPolicy::None, &caller(false)).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_none_reports_mode_only() {
+        let applied = resolve(&NetworkPolicy::None);
+        assert_eq!(
+            serde_json::to_value(&applied).unwrap(),
+            serde_json::json!({ "mode": "none" })
+        );
+    }
+}
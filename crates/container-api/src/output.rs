@@ -0,0 +1,232 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Caps on the output returned or streamed for one execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimits {
+    /// Hard ceiling on output bytes; the container is terminated once output goes past it
+    pub max_bytes: usize,
+    /// Longer lines are cut, the rest of the line is dropped
+    pub max_line_bytes: usize,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+        }
+    }
+}
+
+impl OutputLimits {
+    /// Read `OUTPUT_MAX_BYTES` (default 1 MiB) and `OUTPUT_MAX_LINE_BYTES` (default 16 KiB)
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            max_bytes: read("OUTPUT_MAX_BYTES", DEFAULT_MAX_BYTES),
+            max_line_bytes: read("OUTPUT_MAX_LINE_BYTES", DEFAULT_MAX_LINE_BYTES),
+        }
+    }
+}
+
+/// Why output was cut, reported in the `truncated` stream event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Truncation {
+    /// A line exceeded `max_line_bytes`
+    Line { max_line_bytes: usize },
+    /// The execution went past `max_bytes` and was terminated
+    Output { max_bytes: usize },
+}
+
+/// Applies [`OutputLimits`] to output arriving in chunks
+#[derive(Debug)]
+pub struct OutputLimiter {
+    limits: OutputLimits,
+    /// Bytes received so far, including those dropped from long lines
+    received: usize,
+    /// Bytes seen on the current line, including dropped ones
+    line_len: usize,
+    lines_truncated: usize,
+    exhausted: bool,
+}
+
+/// Largest char boundary of `text` at or below `index`
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    (0..=index).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0)
+}
+
+impl OutputLimiter {
+    pub fn new(limits: OutputLimits) -> Self {
+        Self {
+            limits,
+            received: 0,
+            line_len: 0,
+            lines_truncated: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Apply the limits to the next chunk; returns the text to keep
+    pub fn push(&mut self, chunk: &str) -> String {
+        let mut kept = String::new();
+
+        for segment in chunk.split_inclusive('\n') {
+            if self.exhausted {
+                break;
+            }
+            let (line, newline) = match segment.strip_suffix('\n') {
+                Some(line) => (line, true),
+                None => (segment, false),
+            };
+
+            let room = self.limits.max_line_bytes.saturating_sub(self.line_len);
+            let end = floor_char_boundary(line, room);
+            if end < line.len() && self.line_len <= self.limits.max_line_bytes {
+                self.lines_truncated += 1;
+            }
+            // Past the limit, so later chunks of this line are not counted again
+            self.line_len = if end < line.len() {
+                self.limits.max_line_bytes + 1
+            } else {
+                self.line_len + line.len()
+            };
+
+            if newline {
+                self.line_len = 0;
+            }
+
+            // Dropped bytes count too, so one endless line still hits the ceiling
+            let remaining = self.limits.max_bytes - self.received;
+            kept.push_str(&line[..floor_char_boundary(line, end.min(remaining))]);
+            if newline && line.len() < remaining {
+                kept.push('\n');
+            }
+            if segment.len() > remaining {
+                self.received = self.limits.max_bytes;
+                self.exhausted = true;
+            } else {
+                self.received += segment.len();
+            }
+        }
+
+        kept
+    }
+
+    /// Whether output went past the hard ceiling
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    pub fn lines_truncated(&self) -> usize {
+        self.lines_truncated
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.exhausted || self.lines_truncated > 0
+    }
+
+    pub fn line_truncation(&self) -> Truncation {
+        Truncation::Line {
+            max_line_bytes: self.limits.max_line_bytes,
+        }
+    }
+
+    pub fn output_truncation(&self) -> Truncation {
+        Truncation::Output {
+            max_bytes: self.limits.max_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_bytes: usize, max_line_bytes: usize) -> OutputLimiter {
+        OutputLimiter::new(OutputLimits {
+            max_bytes,
+            max_line_bytes,
+        })
+    }
+
+    #[test]
+    fn test_output_within_limits_is_unchanged() {
+        let mut limiter = limiter(100, 10);
+        assert_eq!(limiter.push("hello\nwor"), "hello\nwor");
+        assert_eq!(limiter.push("ld\n"), "ld\n");
+        assert!(!limiter.truncated());
+    }
+
+    #[test]
+    fn test_long_lines_are_cut_across_chunks() {
+        let mut limiter = limiter(100, 4);
+        let mut output = limiter.push("abc");
+        output.push_str(&limiter.push("defgh"));
+        output.push_str(&limiter.push("ij\nok\n"));
+        assert_eq!(output, "abcd\nok\n");
+        assert_eq!(limiter.lines_truncated(), 1);
+        assert!(!limiter.exhausted());
+    }
+
+    #[test]
+    fn test_ceiling_exhausts_limiter() {
+        let mut limiter = limiter(8, 100);
+        assert_eq!(limiter.push("12345\n"), "12345\n");
+        assert_eq!(limiter.push("67890\n"), "67");
+        assert!(limiter.exhausted());
+        assert_eq!(limiter.push("more\n"), "");
+        assert_eq!(
+            serde_json::to_value(limiter.output_truncation()).unwrap(),
+            serde_json::json!({ "reason": "output", "max_bytes": 8 })
+        );
+    }
+
+    #[test]
+    fn test_dropped_line_bytes_count_toward_ceiling() {
+        let mut chunks = limiter(10, 4);
+        assert_eq!(chunks.push(&"a".repeat(8)), "aaaa");
+        assert!(!chunks.exhausted());
+        assert_eq!(chunks.push(&"a".repeat(8)), "");
+        assert!(chunks.exhausted());
+
+        let mut single = limiter(10, 4);
+        assert_eq!(single.push(&format!("{}\n", "b".repeat(20))), "bbbb");
+        assert!(single.exhausted());
+        assert_eq!(single.lines_truncated(), 1);
+    }
+
+    #[test]
+    fn test_output_of_exactly_max_bytes_is_not_truncated() {
+        let mut limiter = limiter(6, 100);
+        assert_eq!(limiter.push("12345\n"), "12345\n");
+        assert!(!limiter.exhausted());
+        assert!(!limiter.truncated());
+
+        assert_eq!(limiter.push("6"), "");
+        assert!(limiter.exhausted());
+    }
+
+    #[test]
+    fn test_cuts_respect_char_boundaries() {
+        let mut lines = limiter(100, 3);
+        assert_eq!(lines.push("aé€\n"), "aé\n");
+
+        let mut ceiling = limiter(4, 100);
+        assert_eq!(ceiling.push("€€"), "€");
+        assert!(ceiling.exhausted());
+    }
+}
@@ -0,0 +1,344 @@
+use podman_api::Podman;
+use podman_api::opts::{ContainerListFilter, ContainerListOpts};
+use service_registry::ServiceSelector;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::connections::ConnectionManager;
+use crate::scheduler::Scheduler;
+use crate::volumes::InputVolumes;
+
+pub use xxx_types::labels::{
+    CALLER_LABEL, DEADLINE_LABEL, EXECUTION_LABEL, MANAGED_LABEL, OWNER_LABEL, OWNER_SERVICE, ReapReason, reap_reason,
+};
+
+/// Time past the execution limit for waiting, collecting artifacts and cleaning up
+pub const DEADLINE_GRACE_SECS: u64 = 60;
+
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+
+/// Lease TTL the service registry grants. After the registry loses its entries,
+/// e.g. on a restart, instances register again within one lease period.
+const LEASE_PERIOD: Duration = Duration::from_secs(30);
+
+static EXECUTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Labels identifying an execution container, its owner, caller and deadline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionLabels {
+    pub owner: String,
+    pub caller: String,
+    pub execution_id: String,
+    /// Unix time (seconds)
+    pub deadline: u64,
+}
+
+impl ExecutionLabels {
+    /// A new execution id for `caller`, with a deadline `max_runtime` plus a grace period from now
+    pub fn new(owner: &str, caller: &str, max_runtime: Duration) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self {
+            owner: owner.to_string(),
+            caller: caller.to_string(),
+            execution_id: format!(
+                "{:x}-{}",
+                now.as_nanos(),
+                EXECUTION_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ),
+            deadline: now.as_secs() + max_runtime.as_secs() + DEADLINE_GRACE_SECS,
+        }
+    }
+
+    pub fn pairs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (MANAGED_LABEL, "true".to_string()),
+            (OWNER_LABEL, self.owner.clone()),
+            (CALLER_LABEL, self.caller.clone()),
+            (EXECUTION_LABEL, self.execution_id.clone()),
+            (DEADLINE_LABEL, self.deadline.to_string()),
+        ]
+    }
+}
+
+/// Removes an execution container and its input volumes
+///
+/// Call [`ContainerGuard::cleanup`] once the execution is over. If the guard
+/// is dropped first, e.g. on an early return or when a streaming client
+/// disconnects, the removal runs in the background instead.
+pub struct ContainerGuard {
+    podman: Podman,
+    id: String,
+    volumes: Option<InputVolumes>,
+}
+
+impl ContainerGuard {
+    pub fn new(podman: Podman, id: String, volumes: InputVolumes) -> Self {
+        Self {
+            podman,
+            id,
+            volumes: Some(volumes),
+        }
+    }
+
+    pub async fn cleanup(mut self) {
+        if let Some(volumes) = self.volumes.take() {
+            remove(&self.podman, &self.id, volumes).await;
+        }
+    }
+}
+
+async fn remove(podman: &Podman, id: &str, volumes: InputVolumes) {
+    // Forced, so a running container is killed first
+    if let Err(e) = podman.containers().get(id).remove().await {
+        xxx_metrics::podman_error("remove");
+        tracing::warn!("Failed to remove container {}: {}", id, e);
+    }
+    volumes.cleanup().await;
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        let (Some(volumes), Ok(handle)) = (self.volumes.take(), tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        tracing::info!("Execution container {} abandoned, removing it", self.id);
+        let podman = self.podman.clone();
+        let id = std::mem::take(&mut self.id);
+        handle.spawn(async move { remove(&podman, &id, volumes).await });
+    }
+}
+
+/// Periodically removes managed containers that outlived their deadline or owner
+///
+/// - `CONTAINER_REAPER_INTERVAL_SECS`: seconds between sweeps (default 60, 0 disables)
+#[derive(Debug, Clone)]
+pub struct Reaper {
+    owner: String,
+    interval: Option<Duration>,
+    /// Whether this owner is the only live one, so the registry is not asked
+    sole_owner: bool,
+    /// Since when the registry has listed this owner in every sweep
+    listed_since: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Reaper {
+    pub fn new(owner: impl Into<String>, interval: Option<Duration>) -> Self {
+        Self {
+            owner: owner.into(),
+            interval,
+            sole_owner: false,
+            listed_since: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Treat this owner as the only live one instead of asking the registry,
+    /// for single-process deployments where no other container-api runs
+    pub fn sole_owner(mut self) -> Self {
+        self.sole_owner = true;
+        self
+    }
+
+    pub fn from_env(owner: impl Into<String>) -> Self {
+        let secs = std::env::var("CONTAINER_REAPER_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL_SECS);
+        Self::new(owner, (secs > 0).then(|| Duration::from_secs(secs)))
+    }
+
+    /// Registry instance id written to the owner label of new containers
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub fn spawn(&self, scheduler: Scheduler, connections: ConnectionManager) -> Option<tokio::task::JoinHandle<()>> {
+        let interval = self.interval?;
+        let reaper = self.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                reaper.sweep(&scheduler, &connections).await;
+            }
+        }))
+    }
+
+    /// Instance ids holding a container-api lease, including this one
+    ///
+    /// `None` when the registry cannot be trusted to list every live owner.
+    async fn live_owners(&self) -> Option<HashSet<String>> {
+        if self.sole_owner {
+            return Some(HashSet::from([self.owner.clone()]));
+        }
+        let selector = ServiceSelector::new().include_unhealthy(true);
+        let listed = service_registry::discover_services(OWNER_SERVICE, &selector)
+            .await
+            .map(|services| services.into_iter().map(|service| service.id).collect());
+        if listed.is_none() {
+            tracing::warn!("Service registry unreachable; only reaping expired containers");
+        }
+        self.settled_owners(listed, Instant::now())
+    }
+
+    /// The `listed` owners, once the registry has listed this owner for a full lease period
+    ///
+    /// A listing without this owner, including an empty one, means the registry
+    /// lost its entries, and other owners may not have registered again yet.
+    fn settled_owners(&self, listed: Option<HashSet<String>>, now: Instant) -> Option<HashSet<String>> {
+        let mut listed_since = self.listed_since.lock().unwrap();
+        let Some(owners) = listed.filter(|owners| owners.contains(&self.owner)) else {
+            *listed_since = None;
+            return None;
+        };
+        let since = *listed_since.get_or_insert(now);
+        if now.duration_since(since) < LEASE_PERIOD {
+            tracing::info!("Service registry listing is newer than a lease period; only reaping expired containers");
+            return None;
+        }
+        Some(owners)
+    }
+
+    /// One pass over every Podman host; returns the number of containers removed
+    pub async fn sweep(&self, scheduler: &Scheduler, connections: &ConnectionManager) -> usize {
+        let live_owners = self.live_owners().await;
+
+        let mut reaped = 0;
+        for host in scheduler.hosts().await {
+            let Ok(podman) = connections.get(&host.endpoint).await else {
+                continue;
+            };
+            reaped += self.sweep_host(&podman, &host.id, live_owners.as_ref()).await;
+        }
+        reaped
+    }
+
+    async fn sweep_host(&self, podman: &Podman, host: &str, live_owners: Option<&HashSet<String>>) -> usize {
+        let opts = ContainerListOpts::builder()
+            .all(true)
+            .filter([ContainerListFilter::LabelKeyVal(MANAGED_LABEL.to_string(), "true".to_string())])
+            .build();
+        let containers = match podman.containers().list(&opts).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                xxx_metrics::podman_error("list");
+                tracing::warn!("Failed to list managed containers on {}: {}", host, e);
+                return 0;
+            }
+        };
+
+        let now = now_secs();
+        let mut reaped = 0;
+        for container in containers {
+            let (Some(id), Some(labels)) = (container.id, container.labels) else {
+                continue;
+            };
+            let Some(reason) = reap_reason(&labels, now, live_owners) else {
+                continue;
+            };
+            match podman.containers().get(&id).remove().await {
+                Ok(()) => {
+                    tracing::warn!(
+                        "Reaped container {} on {} ({}, execution {})",
+                        id,
+                        host,
+                        reason.as_str(),
+                        labels.get(EXECUTION_LABEL).map_or("unknown", String::as_str)
+                    );
+                    xxx_metrics::container_reaped(reason.as_str());
+                    reaped += 1;
+                }
+                Err(e) => {
+                    xxx_metrics::podman_error("remove");
+                    tracing::warn!("Failed to reap container {} on {}: {}", id, host, e);
+                }
+            }
+        }
+        reaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_execution_labels() {
+        let labels = ExecutionLabels::new("container-api-1", "alice", Duration::from_secs(30));
+        let other = ExecutionLabels::new("container-api-1", "alice", Duration::from_secs(30));
+        assert_ne!(labels.execution_id, other.execution_id);
+        assert!(labels.deadline >= now_secs() + 30 + DEADLINE_GRACE_SECS - 1);
+
+        let pairs: HashMap<_, _> = labels.pairs().into_iter().collect();
+        assert_eq!(pairs[MANAGED_LABEL], "true");
+        assert_eq!(pairs[OWNER_LABEL], "container-api-1");
+        assert_eq!(pairs[CALLER_LABEL], "alice");
+        assert_eq!(pairs[DEADLINE_LABEL], labels.deadline.to_string());
+    }
+
+    #[test]
+    fn test_owners_are_trusted_once_the_listing_settles() {
+        let reaper = Reaper::new("container-api-1", None);
+        let start = Instant::now();
+        let listed = |ids: &[&str]| Some(ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>());
+
+        // A registry that lost its entries does not list this owner
+        assert_eq!(reaper.settled_owners(listed(&[]), start), None);
+        assert_eq!(reaper.settled_owners(listed(&["container-api-2"]), start), None);
+
+        // Other owners get one lease period to register again
+        assert_eq!(reaper.settled_owners(listed(&["container-api-1"]), start), None);
+        let settled = start + LEASE_PERIOD;
+        assert_eq!(
+            reaper.settled_owners(listed(&["container-api-1", "container-api-2"]), settled),
+            listed(&["container-api-1", "container-api-2"])
+        );
+
+        // Losing this owner, or the registry, starts the wait over
+        assert_eq!(reaper.settled_owners(None, settled), None);
+        assert_eq!(reaper.settled_owners(listed(&["container-api-1"]), settled), None);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_guard_removes_container() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let recorded = requests.clone();
+        let app = axum::Router::new().fallback(move |request: axum::extract::Request| {
+            let recorded = recorded.clone();
+            async move {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", request.method(), request.uri()));
+                axum::http::StatusCode::NO_CONTENT
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let podman = Podman::new(format!("http://{}", addr)).unwrap();
+        drop(ContainerGuard::new(podman, "abc123".to_string(), InputVolumes::none()));
+
+        for _ in 0..50 {
+            if !requests.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("DELETE "));
+        assert!(requests[0].contains("/containers/abc123?force=true"));
+    }
+}
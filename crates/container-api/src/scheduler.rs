@@ -0,0 +1,483 @@
+use service_registry::{ServiceInfo, ServiceSelector};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Registry name under which Podman hosts are registered
+const PODMAN_SERVICE: &str = "coreos";
+
+/// Concurrent executions per host when its registration has no `capacity` metadata
+const DEFAULT_HOST_CAPACITY: usize = 8;
+
+/// How long discovered hosts are reused before asking the registry again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a host that failed an execution is avoided
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Host id used for the `COREOS_URL` fallback when the registry has no hosts
+const FALLBACK_HOST_ID: &str = "coreos-fallback";
+
+/// Host id of the only host of a scheduler built with [`Scheduler::with_local_host`]
+const LOCAL_HOST_ID: &str = "local";
+
+/// How new executions are spread over the Podman hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulingStrategy {
+    /// Place on the host with the lowest running/capacity ratio
+    #[default]
+    LeastLoaded,
+    /// Fill the busiest host that still has a free slot
+    BinPack,
+}
+
+impl FromStr for SchedulingStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "least-loaded" => Ok(Self::LeastLoaded),
+            "bin-pack" | "binpack" => Ok(Self::BinPack),
+            other => Err(format!("unknown scheduling strategy '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerError {
+    /// No Podman host is registered (or all were excluded)
+    NoHosts,
+    /// Every host is running at its capacity
+    AtCapacity,
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::NoHosts => write!(f, "No Podman hosts available"),
+            SchedulerError::AtCapacity => write!(f, "All Podman hosts are at capacity"),
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+/// A Podman host and the number of executions the scheduler has placed on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostLoad {
+    pub id: String,
+    pub endpoint: String,
+    pub running: usize,
+    pub capacity: usize,
+}
+
+#[derive(Debug)]
+struct HostState {
+    endpoint: String,
+    capacity: usize,
+    running: usize,
+    failed_until: Option<Instant>,
+}
+
+impl HostState {
+    fn is_cooling_down(&self, now: Instant) -> bool {
+        self.failed_until.is_some_and(|until| until > now)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SchedulerInner {
+    hosts: BTreeMap<String, HostState>,
+    refreshed_at: Option<Instant>,
+}
+
+/// Places container executions on the registered Podman hosts
+///
+/// Hosts are discovered from the registry under `coreos`; their capacity comes
+/// from the `capacity` metadata of the registration. Each placement holds a slot
+/// until its [`Placement`] is dropped.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    strategy: SchedulingStrategy,
+    default_capacity: usize,
+    fallback_endpoint: Option<String>,
+    /// Whether hosts are discovered from the registry
+    discover: bool,
+    inner: Arc<Mutex<SchedulerInner>>,
+}
+
+impl Scheduler {
+    pub fn new(strategy: SchedulingStrategy, default_capacity: usize) -> Self {
+        Self {
+            strategy,
+            default_capacity,
+            fallback_endpoint: None,
+            discover: true,
+            inner: Arc::new(Mutex::new(SchedulerInner::default())),
+        }
+    }
+
+    /// Build a scheduler from the environment
+    ///
+    /// - `SCHEDULER_STRATEGY`: `least-loaded` (default) or `bin-pack`
+    /// - `SCHEDULER_HOST_CAPACITY`: capacity of hosts without `capacity` metadata
+    /// - `COREOS_URL`: host used when the registry has none (default http://coreos:8085)
+    pub fn from_env() -> Self {
+        let strategy = match std::env::var("SCHEDULER_STRATEGY") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                tracing::warn!("{}; using least-loaded", e);
+                SchedulingStrategy::default()
+            }),
+            Err(_) => SchedulingStrategy::default(),
+        };
+        let default_capacity = std::env::var("SCHEDULER_HOST_CAPACITY")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_HOST_CAPACITY);
+        let fallback = std::env::var("COREOS_URL").unwrap_or("http://coreos:8085".to_string());
+
+        Self::new(strategy, default_capacity).with_fallback_endpoint(Some(fallback))
+    }
+
+    /// Podman endpoint to use when the registry has no hosts
+    pub fn with_fallback_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.fallback_endpoint = endpoint;
+        self
+    }
+
+    /// Use only the Podman host at `endpoint`, without asking the registry
+    pub fn with_local_host(mut self, endpoint: impl Into<String>) -> Self {
+        self.fallback_endpoint = Some(endpoint.into());
+        self.discover = false;
+        self
+    }
+
+    pub fn strategy(&self) -> SchedulingStrategy {
+        self.strategy
+    }
+
+    /// Replace the known hosts, keeping the running counts of hosts that remain
+    pub fn set_hosts(&self, hosts: Vec<HostLoad>) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut previous = std::mem::take(&mut inner.hosts);
+
+        for host in hosts {
+            let (running, failed_until) = previous
+                .remove(&host.id)
+                .map(|old| (old.running, old.failed_until))
+                .unwrap_or((host.running, None));
+            inner.hosts.insert(
+                host.id,
+                HostState {
+                    endpoint: host.endpoint,
+                    capacity: host.capacity,
+                    running,
+                    failed_until,
+                },
+            );
+        }
+        inner.refreshed_at = Some(Instant::now());
+    }
+
+    /// Re-discover the Podman hosts from the registry
+    pub async fn refresh(&self) {
+        if !self.discover {
+            let hosts = self.fallback_hosts();
+            self.set_hosts(hosts);
+            return;
+        }
+
+        let discovered =
+            service_registry::discover_services(PODMAN_SERVICE, &ServiceSelector::default()).await;
+
+        let hosts = match discovered {
+            Some(services) if !services.is_empty() => services
+                .iter()
+                .map(|service| self.host_from_service(service))
+                .collect(),
+            Some(_) => self.fallback_hosts(),
+            None => {
+                // Keep what we had if the registry is briefly unreachable
+                let has_hosts = !self.inner.lock().unwrap().hosts.is_empty();
+                if has_hosts {
+                    self.inner.lock().unwrap().refreshed_at = Some(Instant::now());
+                    return;
+                }
+                self.fallback_hosts()
+            }
+        };
+
+        self.set_hosts(hosts);
+    }
+
+    fn host_from_service(&self, service: &ServiceInfo) -> HostLoad {
+        let capacity = service
+            .metadata
+            .get("capacity")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(self.default_capacity);
+
+        HostLoad {
+            id: service.id.clone(),
+            endpoint: format!("http://{}:{}", service.address, service.port),
+            running: 0,
+            capacity,
+        }
+    }
+
+    fn fallback_hosts(&self) -> Vec<HostLoad> {
+        self.fallback_endpoint
+            .iter()
+            .map(|endpoint| HostLoad {
+                id: if self.discover { FALLBACK_HOST_ID } else { LOCAL_HOST_ID }.to_string(),
+                endpoint: endpoint.clone(),
+                running: 0,
+                capacity: self.default_capacity,
+            })
+            .collect()
+    }
+
+    async fn refresh_if_stale(&self) {
+        let stale = self
+            .inner
+            .lock()
+            .unwrap()
+            .refreshed_at
+            .is_none_or(|at| at.elapsed() >= REFRESH_INTERVAL);
+        if stale {
+            self.refresh().await;
+        }
+    }
+
+    /// Current load of every known host
+    pub async fn hosts(&self) -> Vec<HostLoad> {
+        self.refresh_if_stale().await;
+        self.loads()
+    }
+
+    fn loads(&self) -> Vec<HostLoad> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .hosts
+            .iter()
+            .map(|(id, host)| HostLoad {
+                id: id.clone(),
+                endpoint: host.endpoint.clone(),
+                running: host.running,
+                capacity: host.capacity,
+            })
+            .collect()
+    }
+
+    /// Reserve a slot on a host, skipping the hosts in `exclude`
+    pub async fn acquire(&self, exclude: &[String]) -> Result<Placement, SchedulerError> {
+        self.refresh_if_stale().await;
+        self.place(exclude)
+    }
+
+    fn place(&self, exclude: &[String]) -> Result<Placement, SchedulerError> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let eligible: Vec<(&String, &HostState)> = inner
+            .hosts
+            .iter()
+            .filter(|(id, _)| !exclude.contains(id))
+            .collect();
+        if eligible.is_empty() {
+            return Err(SchedulerError::NoHosts);
+        }
+
+        let free: Vec<(&String, &HostState)> = eligible
+            .into_iter()
+            .filter(|(_, host)| host.running < host.capacity)
+            .collect();
+        // Hosts that recently failed are only used when nothing else has room
+        let healthy: Vec<(&String, &HostState)> = free
+            .iter()
+            .copied()
+            .filter(|(_, host)| !host.is_cooling_down(now))
+            .collect();
+        let candidates = if healthy.is_empty() { free } else { healthy };
+
+        let chosen = match self.strategy {
+            SchedulingStrategy::LeastLoaded => candidates.into_iter().min_by(|(a_id, a), (b_id, b)| {
+                // Compare running/capacity ratios without floating point
+                (a.running * b.capacity)
+                    .cmp(&(b.running * a.capacity))
+                    .then(a.running.cmp(&b.running))
+                    .then(a_id.cmp(b_id))
+            }),
+            SchedulingStrategy::BinPack => candidates.into_iter().min_by(|(a_id, a), (b_id, b)| {
+                (a.capacity - a.running)
+                    .cmp(&(b.capacity - b.running))
+                    .then(a_id.cmp(b_id))
+            }),
+        };
+
+        let Some((id, _)) = chosen else {
+            return Err(SchedulerError::AtCapacity);
+        };
+        let id = id.clone();
+
+        let host = inner.hosts.get_mut(&id).expect("chosen host exists");
+        host.running += 1;
+        tracing::debug!("Placed execution on {} ({}/{})", id, host.running, host.capacity);
+
+        Ok(Placement {
+            host_id: id,
+            endpoint: host.endpoint.clone(),
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// Avoid a host for a while after it failed an execution
+    pub fn mark_failed(&self, host_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(host) = inner.hosts.get_mut(host_id) {
+            tracing::warn!("Podman host {} failed; avoiding it for {:?}", host_id, FAILURE_COOLDOWN);
+            host.failed_until = Some(Instant::now() + FAILURE_COOLDOWN);
+        }
+    }
+}
+
+/// A reserved slot on a Podman host, released when dropped
+#[derive(Debug)]
+pub struct Placement {
+    pub host_id: String,
+    pub endpoint: String,
+    inner: Arc<Mutex<SchedulerInner>>,
+}
+
+impl Drop for Placement {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(host) = inner.hosts.get_mut(&self.host_id) {
+            host.running = host.running.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(id: &str, capacity: usize) -> HostLoad {
+        HostLoad {
+            id: id.to_string(),
+            endpoint: format!("http://{}:8085", id),
+            running: 0,
+            capacity,
+        }
+    }
+
+    fn scheduler(strategy: SchedulingStrategy, hosts: Vec<HostLoad>) -> Scheduler {
+        let scheduler = Scheduler::new(strategy, DEFAULT_HOST_CAPACITY);
+        scheduler.set_hosts(hosts);
+        scheduler
+    }
+
+    fn running(scheduler: &Scheduler, id: &str) -> usize {
+        scheduler.loads().iter().find(|h| h.id == id).unwrap().running
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        assert_eq!("least-loaded".parse(), Ok(SchedulingStrategy::LeastLoaded));
+        assert_eq!("BIN_PACK".parse(), Ok(SchedulingStrategy::BinPack));
+        assert!("random".parse::<SchedulingStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_least_loaded_spreads_by_ratio() {
+        let scheduler = scheduler(
+            SchedulingStrategy::LeastLoaded,
+            vec![host("a", 2), host("b", 4)],
+        );
+
+        let first = scheduler.place(&[]).unwrap();
+        assert_eq!(first.host_id, "a");
+        // a is now 1/2, b is 0/4
+        let second = scheduler.place(&[]).unwrap();
+        assert_eq!(second.host_id, "b");
+        // a is 1/2, b is 1/4
+        let third = scheduler.place(&[]).unwrap();
+        assert_eq!(third.host_id, "b");
+    }
+
+    #[test]
+    fn test_bin_pack_fills_one_host_first() {
+        let scheduler = scheduler(SchedulingStrategy::BinPack, vec![host("a", 2), host("b", 2)]);
+
+        let first = scheduler.place(&[]).unwrap();
+        let second = scheduler.place(&[]).unwrap();
+        assert_eq!(first.host_id, second.host_id);
+        let third = scheduler.place(&[]).unwrap();
+        assert_ne!(third.host_id, first.host_id);
+    }
+
+    #[test]
+    fn test_capacity_and_release() {
+        let scheduler = scheduler(SchedulingStrategy::LeastLoaded, vec![host("a", 1)]);
+
+        let placement = scheduler.place(&[]).unwrap();
+        assert_eq!(running(&scheduler, "a"), 1);
+        assert_eq!(scheduler.place(&[]).unwrap_err(), SchedulerError::AtCapacity);
+
+        drop(placement);
+        assert_eq!(running(&scheduler, "a"), 0);
+        assert!(scheduler.place(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_failover_skips_excluded_and_failed_hosts() {
+        let scheduler = scheduler(SchedulingStrategy::LeastLoaded, vec![host("a", 4), host("b", 4)]);
+
+        let placement = scheduler.place(&["a".to_string()]).unwrap();
+        assert_eq!(placement.host_id, "b");
+        assert_eq!(
+            scheduler.place(&["a".to_string(), "b".to_string()]).unwrap_err(),
+            SchedulerError::NoHosts
+        );
+        drop(placement);
+
+        scheduler.mark_failed("a");
+        assert_eq!(scheduler.place(&[]).unwrap().host_id, "b");
+
+        // A failed host is still used when it is the only one left
+        assert_eq!(scheduler.place(&["b".to_string()]).unwrap().host_id, "a");
+    }
+
+    #[test]
+    fn test_set_hosts_keeps_running_counts() {
+        let scheduler = scheduler(SchedulingStrategy::LeastLoaded, vec![host("a", 4)]);
+        let placement = scheduler.place(&[]).unwrap();
+
+        scheduler.set_hosts(vec![host("a", 4), host("b", 4)]);
+        assert_eq!(running(&scheduler, "a"), 1);
+        assert_eq!(running(&scheduler, "b"), 0);
+
+        // Releasing a slot on a host that left the registry is a no-op
+        scheduler.set_hosts(vec![host("b", 4)]);
+        drop(placement);
+        assert_eq!(scheduler.loads().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_local_host_skips_the_registry() {
+        let scheduler = Scheduler::new(SchedulingStrategy::LeastLoaded, 2)
+            .with_local_host("unix:///run/podman/podman.sock");
+
+        let hosts = scheduler.hosts().await;
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].id, LOCAL_HOST_ID);
+        assert_eq!(hosts[0].endpoint, "unix:///run/podman/podman.sock");
+
+        let placement = scheduler.acquire(&[]).await.unwrap();
+        scheduler.refresh().await;
+        assert_eq!(running(&scheduler, LOCAL_HOST_ID), 1);
+        drop(placement);
+    }
+}
@@ -0,0 +1,537 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::auth::Caller;
+
+/// Largest secret value accepted
+pub const MAX_SECRET_BYTES: usize = 64 * 1024;
+
+/// Environment variables accepted per request, plain and secret combined
+const MAX_ENV_VARS: usize = 64;
+
+const MAX_ENV_VALUE_BYTES: usize = 32 * 1024;
+
+const NONCE_LEN: usize = 12;
+
+/// Written over secret values in execution output
+pub const MASK: &str = "********";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SecretError {
+    InvalidName(String),
+    TooLarge,
+    Io(String),
+    /// The stored value could not be decrypted, usually because the key changed
+    Decrypt(String),
+    /// The caller may not reference the secret
+    Forbidden(String),
+}
+
+impl std::fmt::Display for SecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretError::InvalidName(name) => write!(f, "Invalid secret name '{}'", name),
+            SecretError::TooLarge => write!(f, "Secret values are limited to {} bytes", MAX_SECRET_BYTES),
+            SecretError::Io(e) => write!(f, "Secret store error: {}", e),
+            SecretError::Decrypt(name) => write!(f, "Failed to decrypt secret '{}'", name),
+            SecretError::Forbidden(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+fn valid_secret_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        && !name.starts_with('.')
+}
+
+fn valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A decrypted secret and the callers it is shared with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Secret {
+    pub value: String,
+    /// Names of the non-admin callers that may reference the secret
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl Secret {
+    /// Admins may reference any secret, other authenticated callers only those
+    /// shared with them
+    pub fn allows(&self, caller: &Caller) -> bool {
+        caller.admin || (!caller.is_anonymous() && self.allow.contains(&caller.name))
+    }
+}
+
+/// Named secrets encrypted at rest with ChaCha20-Poly1305
+///
+/// Each secret is one `<name>.secret` file holding a random nonce followed by
+/// the ciphertext of the value and its allowed callers. The name is bound as
+/// associated data, so renamed files fail to decrypt instead of yielding
+/// another secret's value.
+#[derive(Clone)]
+pub struct SecretStore {
+    root: PathBuf,
+    cipher: Arc<ChaCha20Poly1305>,
+}
+
+impl std::fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretStore").field("root", &self.root).finish_non_exhaustive()
+    }
+}
+
+impl SecretStore {
+    pub fn open(root: impl Into<PathBuf>, key: [u8; 32]) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            cipher: Arc::new(ChaCha20Poly1305::new(Key::from_slice(&key))),
+        })
+    }
+
+    /// Open the store configured by the environment
+    ///
+    /// - `SECRET_DIR`: store directory (default `<tmp>/xxx-secrets`)
+    /// - `SECRET_KEY`: 32-byte key as 64 hex characters
+    /// - `SECRET_KEY_FILE`: file holding the key instead, which must not be
+    ///   inside `SECRET_DIR`
+    ///
+    /// Fails when neither key variable is set, so the key never ends up next to
+    /// the values it protects.
+    pub fn from_env() -> std::io::Result<Self> {
+        let root = std::env::var("SECRET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("xxx-secrets"));
+        std::fs::create_dir_all(&root)?;
+
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+        let key = match (std::env::var("SECRET_KEY"), std::env::var("SECRET_KEY_FILE")) {
+            (Ok(hex_key), _) => parse_key(hex_key.trim()).ok_or_else(|| invalid("SECRET_KEY must be 64 hex characters".to_string()))?,
+            (Err(_), Ok(path)) => read_key_file(&root, &PathBuf::from(path))?,
+            (Err(_), Err(_)) => return Err(invalid("SECRET_KEY or SECRET_KEY_FILE must be set".to_string())),
+        };
+
+        Self::open(root, key)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, SecretError> {
+        if valid_secret_name(name) {
+            Ok(self.root.join(format!("{}.secret", name)))
+        } else {
+            Err(SecretError::InvalidName(name.to_string()))
+        }
+    }
+
+    /// Store a secret that admins and the callers named in `allow` may reference
+    pub fn put(&self, name: &str, value: &str, allow: &[String]) -> Result<(), SecretError> {
+        let path = self.path(name)?;
+        if value.len() > MAX_SECRET_BYTES {
+            return Err(SecretError::TooLarge);
+        }
+
+        let secret = Secret {
+            value: value.to_string(),
+            allow: allow.to_vec(),
+        };
+        let plaintext = serde_json::to_vec(&secret).map_err(|e| SecretError::Io(e.to_string()))?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: &plaintext, aad: name.as_bytes() })
+            .map_err(|_| SecretError::Io("encryption failed".to_string()))?;
+
+        let mut contents = nonce.to_vec();
+        contents.extend_from_slice(&ciphertext);
+        let tmp = self.root.join(format!(".{}.tmp", name));
+        write_private(&tmp, &contents)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(|e| SecretError::Io(e.to_string()))
+    }
+
+    /// Decrypt a secret; Ok(None) when it does not exist
+    pub fn get(&self, name: &str) -> Result<Option<Secret>, SecretError> {
+        let contents = match std::fs::read(self.path(name)?) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(SecretError::Io(e.to_string())),
+        };
+        if contents.len() < NONCE_LEN {
+            return Err(SecretError::Decrypt(name.to_string()));
+        }
+
+        let (nonce, ciphertext) = contents.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: name.as_bytes() })
+            .map_err(|_| SecretError::Decrypt(name.to_string()))?;
+        serde_json::from_slice(&plaintext)
+            .map(Some)
+            .map_err(|_| SecretError::Decrypt(name.to_string()))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).is_ok_and(|path| path.is_file())
+    }
+
+    /// Returns whether the secret existed
+    pub fn delete(&self, name: &str) -> bool {
+        self.path(name).is_ok_and(|path| std::fs::remove_file(path).is_ok())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| {
+                        let name = entry.ok()?.file_name().into_string().ok()?;
+                        name.strip_suffix(".secret")
+                            .filter(|name| valid_secret_name(name))
+                            .map(str::to_string)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+}
+
+fn parse_key(hex_key: &str) -> Option<[u8; 32]> {
+    hex::decode(hex_key).ok()?.try_into().ok()
+}
+
+/// Read a hex key from `path`, refusing files stored with the secrets in `root`
+fn read_key_file(root: &std::path::Path, path: &std::path::Path) -> std::io::Result<[u8; 32]> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    let file = path.canonicalize()?;
+    if file.starts_with(root.canonicalize()?) {
+        return Err(invalid(format!("SECRET_KEY_FILE {} must not be inside SECRET_DIR", path.display())));
+    }
+    parse_key(std::fs::read_to_string(&file)?.trim())
+        .ok_or_else(|| invalid(format!("{} must hold 64 hex characters", path.display())))
+}
+
+fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// Check plain variables and that every referenced secret exists
+pub fn validate_env(
+    env: &BTreeMap<String, String>,
+    secrets: &BTreeMap<String, String>,
+    store: &SecretStore,
+) -> Result<(), String> {
+    if env.len() + secrets.len() > MAX_ENV_VARS {
+        return Err(format!("At most {} environment variables are allowed", MAX_ENV_VARS));
+    }
+
+    for (name, value) in env {
+        if !valid_env_name(name) {
+            return Err(format!("Invalid environment variable name '{}'", name));
+        }
+        if value.len() > MAX_ENV_VALUE_BYTES {
+            return Err(format!("Value of '{}' exceeds {} bytes", name, MAX_ENV_VALUE_BYTES));
+        }
+    }
+
+    for (name, secret) in secrets {
+        if !valid_env_name(name) {
+            return Err(format!("Invalid environment variable name '{}'", name));
+        }
+        if env.contains_key(name) {
+            return Err(format!("'{}' is set both as a variable and a secret", name));
+        }
+        if !store.exists(secret) {
+            return Err(format!("Secret '{}' not found", secret));
+        }
+    }
+
+    Ok(())
+}
+
+/// Environment of one execution, with secret values decrypted
+#[derive(Default)]
+pub struct ExecutionEnv {
+    vars: Vec<(String, String)>,
+    masker: Masker,
+}
+
+impl ExecutionEnv {
+    /// Decrypt the referenced secrets and combine them with the plain variables
+    ///
+    /// Fails with [`SecretError::Forbidden`] unless every secret is shared
+    /// with `caller`; anonymous callers may reference none.
+    pub fn resolve(
+        env: &BTreeMap<String, String>,
+        secrets: &BTreeMap<String, String>,
+        store: &SecretStore,
+        caller: &Caller,
+    ) -> Result<Self, SecretError> {
+        let mut vars: Vec<(String, String)> = env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let mut values = Vec::new();
+
+        if !secrets.is_empty() && caller.is_anonymous() {
+            return Err(SecretError::Forbidden("Secrets require an authenticated caller".to_string()));
+        }
+
+        for (name, secret) in secrets {
+            let Secret { value, .. } = store
+                .get(secret)?
+                .filter(|stored| stored.allows(caller))
+                .ok_or_else(|| {
+                    SecretError::Forbidden(format!("Secret '{}' is not shared with caller '{}'", secret, caller.name))
+                })?;
+            values.push(value.clone());
+            vars.push((name.clone(), value));
+        }
+
+        Ok(Self {
+            vars,
+            masker: Masker::new(values),
+        })
+    }
+
+    pub fn vars(&self) -> &[(String, String)] {
+        &self.vars
+    }
+
+    pub fn masker(&self) -> &Masker {
+        &self.masker
+    }
+}
+
+/// Replaces secret values in text with [`MASK`]
+#[derive(Debug, Clone, Default)]
+pub struct Masker {
+    /// Longest first, so a secret containing another is masked whole
+    secrets: Vec<String>,
+}
+
+impl Masker {
+    pub fn new(secrets: Vec<String>) -> Self {
+        let mut secrets: Vec<String> = secrets.into_iter().filter(|s| !s.is_empty()).collect();
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        self.secrets
+            .iter()
+            .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), MASK))
+    }
+
+    /// A masker for chunked output, where a secret may straddle two chunks
+    pub fn stream(&self) -> StreamMasker {
+        StreamMasker {
+            masker: self.clone(),
+            pending: String::new(),
+        }
+    }
+}
+
+/// Masks a stream of chunks, holding back any tail that could begin a secret
+#[derive(Debug)]
+pub struct StreamMasker {
+    masker: Masker,
+    pending: String,
+}
+
+impl StreamMasker {
+    /// Mask the next chunk; returns the text that is safe to emit
+    pub fn push(&mut self, chunk: &str) -> String {
+        if self.masker.secrets.is_empty() {
+            return chunk.to_string();
+        }
+
+        self.pending.push_str(chunk);
+        let masked = self.masker.mask(&self.pending);
+        let hold = self.held_suffix_len(&masked);
+        let (emit, keep) = masked.split_at(masked.len() - hold);
+        let emit = emit.to_string();
+        self.pending = keep.to_string();
+        emit
+    }
+
+    /// Emit whatever is still held back once the stream ends
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Length of the longest suffix of `text` that is a proper prefix of a secret
+    fn held_suffix_len(&self, text: &str) -> usize {
+        let longest = self.masker.secrets.first().map_or(0, |s| s.len());
+        (1..longest.min(text.len() + 1))
+            .rev()
+            .filter(|&len| text.is_char_boundary(text.len() - len))
+            .find(|&len| {
+                let suffix = &text[text.len() - len..];
+                self.masker.secrets.iter().any(|s| s.len() > len && s.starts_with(suffix))
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, SecretStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SecretStore::open(dir.path(), [7; 32]).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn test_secrets_are_encrypted_at_rest() {
+        let (dir, store) = store();
+        store.put("db-password", "hunter2-hunter2", &[]).unwrap();
+
+        let raw = std::fs::read(dir.path().join("db-password.secret")).unwrap();
+        assert!(!raw.windows(7).any(|w| w == b"hunter2"));
+        assert_eq!(store.get("db-password").unwrap().unwrap().value, "hunter2-hunter2");
+        assert_eq!(store.names(), vec!["db-password"]);
+
+        // A different key cannot read the value
+        let other = SecretStore::open(dir.path(), [8; 32]).unwrap();
+        assert!(matches!(other.get("db-password"), Err(SecretError::Decrypt(_))));
+
+        assert!(store.delete("db-password"));
+        assert_eq!(store.get("db-password").unwrap(), None);
+    }
+
+    #[test]
+    fn test_key_file_must_be_outside_the_store() {
+        let root = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let key = hex::encode([3u8; 32]);
+        std::fs::write(root.path().join("secret.key"), &key).unwrap();
+        std::fs::write(other.path().join("secret.key"), format!("{}\n", key)).unwrap();
+        std::fs::write(other.path().join("short.key"), "abcd").unwrap();
+
+        assert_eq!(read_key_file(root.path(), &other.path().join("secret.key")).unwrap(), [3; 32]);
+        assert!(read_key_file(root.path(), &root.path().join("secret.key")).is_err());
+        assert!(read_key_file(root.path(), &other.path().join("short.key")).is_err());
+        assert!(read_key_file(root.path(), &other.path().join("missing.key")).is_err());
+    }
+
+    #[test]
+    fn test_renamed_secret_fails_to_decrypt() {
+        let (dir, store) = store();
+        store.put("a", "value", &[]).unwrap();
+        std::fs::rename(dir.path().join("a.secret"), dir.path().join("b.secret")).unwrap();
+        assert!(matches!(store.get("b"), Err(SecretError::Decrypt(_))));
+    }
+
+    #[test]
+    fn test_secret_names_are_validated() {
+        let (_dir, store) = store();
+        assert!(matches!(store.put("../x", "v", &[]), Err(SecretError::InvalidName(_))));
+        assert!(matches!(store.put(".hidden", "v", &[]), Err(SecretError::InvalidName(_))));
+        assert!(!store.exists("../x"));
+    }
+
+    #[test]
+    fn test_validate_env() {
+        let (_dir, store) = store();
+        store.put("token", "abc", &[]).unwrap();
+        let map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        assert!(validate_env(&map(&[("MODE", "test")]), &map(&[("API_TOKEN", "token")]), &store).is_ok());
+        assert!(validate_env(&map(&[("1BAD", "x")]), &map(&[]), &store).is_err());
+        assert!(validate_env(&map(&[]), &map(&[("API_TOKEN", "missing")]), &store).is_err());
+        assert!(validate_env(&map(&[("A", "x")]), &map(&[("A", "token")]), &store).is_err());
+    }
+
+    fn caller(name: &str, admin: bool) -> Caller {
+        Caller {
+            name: name.to_string(),
+            admin,
+        }
+    }
+
+    #[test]
+    fn test_resolve_env() {
+        let (_dir, store) = store();
+        store.put("token", "s3cr3t-value", &["ci".to_string()]).unwrap();
+        let env = BTreeMap::from([("MODE".to_string(), "test".to_string())]);
+        let secrets = BTreeMap::from([("API_TOKEN".to_string(), "token".to_string())]);
+
+        let resolved = ExecutionEnv::resolve(&env, &secrets, &store, &caller("ci", false)).unwrap();
+        assert_eq!(
+            resolved.vars(),
+            &[
+                ("MODE".to_string(), "test".to_string()),
+                ("API_TOKEN".to_string(), "s3cr3t-value".to_string())
+            ]
+        );
+        assert_eq!(resolved.masker().mask("token=s3cr3t-value"), format!("token={}", MASK));
+    }
+
+    #[test]
+    fn test_resolve_env_checks_allowed_callers() {
+        let (_dir, store) = store();
+        store.put("token", "s3cr3t-value", &["ci".to_string(), "anonymous".to_string()]).unwrap();
+        let env = BTreeMap::new();
+        let secrets = BTreeMap::from([("API_TOKEN".to_string(), "token".to_string())]);
+        let resolve = |caller: &Caller| ExecutionEnv::resolve(&env, &secrets, &store, caller);
+
+        assert!(resolve(&caller("ops", true)).is_ok());
+        assert!(matches!(resolve(&caller("other", false)), Err(SecretError::Forbidden(_))));
+        assert!(matches!(resolve(&Caller::anonymous()), Err(SecretError::Forbidden(_))));
+        // Plain variables need no identity
+        assert!(ExecutionEnv::resolve(&env, &BTreeMap::new(), &store, &Caller::anonymous()).is_ok());
+    }
+
+    #[test]
+    fn test_mask_prefers_longest_secret() {
+        let masker = Masker::new(vec!["abc".to_string(), "abcdef".to_string(), String::new()]);
+        assert_eq!(masker.mask("abcdef abc"), format!("{} {}", MASK, MASK));
+        assert_eq!(masker.mask("nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn test_stream_masker_handles_split_secrets() {
+        let masker = Masker::new(vec!["hunter2".to_string()]);
+        let mut stream = masker.stream();
+
+        let mut output = String::new();
+        for chunk in ["pass: hun", "te", "r2\nnext hu", "man\n"] {
+            output.push_str(&stream.push(chunk));
+        }
+        output.push_str(&stream.finish());
+        assert_eq!(output, format!("pass: {}\nnext human\n", MASK));
+
+        // Nothing held back once a chunk cannot start a secret
+        let mut stream = masker.stream();
+        assert_eq!(stream.push("plain text\n"), "plain text\n");
+    }
+}
@@ -0,0 +1,298 @@
+use podman_api::Podman;
+use podman_api::models::NamedVolume;
+use podman_api::opts::{ContainerCreateOpts, VolumeCreateOpts};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::Caller;
+use crate::blobs::BlobStore;
+use crate::reaper::ExecutionLabels;
+
+pub use xxx_types::container::InputMount;
+
+/// Mounts accepted per request
+pub const MAX_INPUT_MOUNTS: usize = 8;
+
+/// Where the loader container sees the volumes while they are filled
+const LOADER_MOUNT: &str = "/xxx-input";
+
+static VOLUME_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Check mount paths and that every referenced blob exists and is visible to `caller`
+pub async fn validate_mounts(mounts: &[InputMount], blobs: &BlobStore, caller: &Caller) -> Result<(), String> {
+    if mounts.len() > MAX_INPUT_MOUNTS {
+        return Err(format!("At most {} mounts are allowed", MAX_INPUT_MOUNTS));
+    }
+
+    let mut paths = HashSet::new();
+    for mount in mounts {
+        if !mount.path.starts_with('/') || mount.path.trim_end_matches('/').is_empty() {
+            return Err(format!(
+                "Mount path '{}' must be an absolute directory other than /",
+                mount.path
+            ));
+        }
+        if mount.path.split('/').any(|segment| segment == "..") {
+            return Err(format!("Mount path '{}' must not contain '..'", mount.path));
+        }
+        if !paths.insert(mount.path.trim_end_matches('/')) {
+            return Err(format!(
+                "Mount path '{}' is used more than once",
+                mount.path
+            ));
+        }
+        if mount.files.is_empty() {
+            return Err(format!("Mount '{}' has no files", mount.path));
+        }
+
+        for (name, blob) in &mount.files {
+            if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+                return Err(format!(
+                    "Invalid file name '{}' in mount '{}'",
+                    name, mount.path
+                ));
+            }
+            if blobs.get(blob, caller).await.is_none() {
+                return Err(format!("Blob '{}' not found", blob));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn volume_name() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!(
+        "xxx-input-{:x}-{}",
+        nanos,
+        VOLUME_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Podman volumes holding an execution's input files
+///
+/// The volumes are removed by [`InputVolumes::cleanup`] once the execution
+/// container is gone, or in the background if the guard is dropped first.
+#[derive(Debug, Default)]
+pub struct InputVolumes {
+    podman: Option<Podman>,
+    /// Volume name and the path it is mounted at
+    volumes: Vec<(String, String)>,
+}
+
+impl InputVolumes {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Create one volume per mount and fill it with the mount's blobs
+    ///
+    /// The files are copied in through a short-lived loader container created
+    /// (but never started) from `image`, which is already present on the host.
+    /// Both carry the execution's labels, so they are reaped with its container.
+    pub async fn create(
+        podman: &Podman,
+        image: &str,
+        mounts: &[InputMount],
+        blobs: &BlobStore,
+        execution: &ExecutionLabels,
+    ) -> Result<Self, podman_api::Error> {
+        let mut volumes = Self {
+            podman: Some(podman.clone()),
+            volumes: Vec::new(),
+        };
+        if mounts.is_empty() {
+            return Ok(volumes);
+        }
+
+        for mount in mounts {
+            let name = volume_name();
+            podman
+                .volumes()
+                .create(
+                    &VolumeCreateOpts::builder()
+                        .name(&name)
+                        .labels(execution.pairs())
+                        .build(),
+                )
+                .await?;
+            volumes.volumes.push((name, mount.path.clone()));
+        }
+
+        let loader_volumes = volumes
+            .volumes
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| NamedVolume {
+                name: Some(name.clone()),
+                dest: Some(format!("{}/{}", LOADER_MOUNT, i)),
+                is_anonymous: None,
+                options: None,
+            });
+        let loader = podman
+            .containers()
+            .create(
+                &ContainerCreateOpts::builder()
+                    .image(image)
+                    .command(["true"])
+                    .volumes(loader_volumes)
+                    .labels(execution.pairs())
+                    .build(),
+            )
+            .await?;
+        let loader = podman.containers().get(&loader.id);
+
+        let mut result = Ok(());
+        'copy: for (i, mount) in mounts.iter().enumerate() {
+            for (file, blob) in &mount.files {
+                let Some(contents) = blobs.read(blob).await else {
+                    result = Err(podman_api::Error::StringError(format!(
+                        "Blob '{}' not found",
+                        blob
+                    )));
+                    break 'copy;
+                };
+                let dest = format!("{}/{}/{}", LOADER_MOUNT, i, file);
+                if let Err(e) = loader.copy_file_into(&dest, &contents).await {
+                    result = Err(e);
+                    break 'copy;
+                }
+            }
+        }
+
+        if let Err(e) = loader.remove().await {
+            tracing::warn!("Failed to remove input loader container: {}", e);
+        }
+        result.map(|_| volumes)
+    }
+
+    /// Mount specs for the execution container, all read-only
+    pub fn named_volumes(&self) -> Vec<NamedVolume> {
+        self.volumes
+            .iter()
+            .map(|(name, path)| NamedVolume {
+                name: Some(name.clone()),
+                dest: Some(path.clone()),
+                is_anonymous: None,
+                options: Some(vec!["ro".to_string()]),
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.volumes.is_empty()
+    }
+
+    /// Remove the volumes; call after the execution container has been removed
+    pub async fn cleanup(mut self) {
+        if let Some(podman) = self.podman.take() {
+            remove_volumes(&podman, std::mem::take(&mut self.volumes)).await;
+        }
+    }
+}
+
+async fn remove_volumes(podman: &Podman, volumes: Vec<(String, String)>) {
+    for (name, _) in volumes {
+        if let Err(e) = podman.volumes().get(&name).remove().await {
+            tracing::warn!("Failed to remove input volume {}: {}", name, e);
+        }
+    }
+}
+
+impl Drop for InputVolumes {
+    fn drop(&mut self) {
+        if self.volumes.is_empty() {
+            return;
+        }
+        let (Some(podman), Ok(handle)) =
+            (self.podman.take(), tokio::runtime::Handle::try_current())
+        else {
+            return;
+        };
+        let volumes = std::mem::take(&mut self.volumes);
+        // Forced removal also removes a container still using the volumes
+        handle.spawn(async move { remove_volumes(&podman, volumes).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(path: &str, files: &[(&str, &str)]) -> InputMount {
+        InputMount {
+            path: path.to_string(),
+            files: files
+                .iter()
+                .map(|(name, blob)| (name.to_string(), blob.to_string()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_mounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::open(dir.path(), 1024).unwrap();
+        let alice = Caller {
+            name: "alice".to_string(),
+            admin: false,
+        };
+        let bob = Caller {
+            name: "bob".to_string(),
+            admin: false,
+        };
+        let blob = blobs.put(b"a,b\n".to_vec().into(), &alice).await.unwrap().id;
+
+        assert!(validate_mounts(&[mount("/data", &[("input.csv", &blob)])], &blobs, &alice).await.is_ok());
+        assert!(validate_mounts(&[mount("data", &[("input.csv", &blob)])], &blobs, &alice).await.is_err());
+        assert!(validate_mounts(&[mount("/", &[("input.csv", &blob)])], &blobs, &alice).await.is_err());
+        assert!(validate_mounts(&[mount("/data/../etc", &[("x", &blob)])], &blobs, &alice).await.is_err());
+        assert!(validate_mounts(&[mount("/data", &[("../x", &blob)])], &blobs, &alice).await.is_err());
+        assert!(validate_mounts(&[mount("/data", &[])], &blobs, &alice).await.is_err());
+        assert!(validate_mounts(&[mount("/data", &[("x", "missing")])], &blobs, &alice).await.is_err());
+        assert!(
+            validate_mounts(
+                &[
+                    mount("/data", &[("a", &blob)]),
+                    mount("/data/", &[("b", &blob)])
+                ],
+                &blobs,
+                &alice
+            )
+            .await
+            .is_err()
+        );
+
+        // Another caller's blob is reported as missing
+        let err = validate_mounts(&[mount("/data", &[("input.csv", &blob)])], &blobs, &bob).await.unwrap_err();
+        assert_eq!(err, format!("Blob '{}' not found", blob));
+    }
+
+    #[test]
+    fn test_mount_deserialization() {
+        let json = r#"{"path":"/data","files":{"input.csv":"abc"}}"#;
+        let parsed: InputMount = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed, mount("/data", &[("input.csv", "abc")]));
+    }
+
+    #[test]
+    fn test_named_volumes_are_read_only() {
+        let volumes = InputVolumes {
+            podman: None,
+            volumes: vec![("xxx-input-1".to_string(), "/data".to_string())],
+        };
+        let named = volumes.named_volumes();
+        assert_eq!(named[0].dest.as_deref(), Some("/data"));
+        assert_eq!(named[0].options, Some(vec!["ro".to_string()]));
+    }
+
+    #[test]
+    fn test_volume_names_are_unique() {
+        assert_ne!(volume_name(), volume_name());
+    }
+}
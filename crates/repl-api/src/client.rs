@@ -0,0 +1,125 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Address of the client a request came from, set by [`identify_client`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Reverse proxies whose `X-Forwarded-For` header names the client, such as the `ui` server
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Vec<(IpAddr, u8)>);
+
+impl TrustedProxies {
+    /// Addresses and CIDR ranges, e.g. `10.0.0.5,172.18.0.0/16`
+    pub fn parse(list: &str) -> Result<Self, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || format!("Invalid trusted proxy '{}'", entry);
+                let (addr, prefix) = entry.split_once('/').unwrap_or((entry, ""));
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                let max = if addr.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    "" => max,
+                    prefix => prefix.parse().ok().filter(|p| *p <= max).ok_or_else(invalid)?,
+                };
+                Ok((addr, prefix))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// `REPL_TRUSTED_PROXIES`; without it no proxy is trusted
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(&std::env::var("REPL_TRUSTED_PROXIES").unwrap_or_default())
+    }
+
+    fn trusts(&self, peer: IpAddr) -> bool {
+        self.0.iter().any(|(addr, prefix)| match (addr, peer) {
+            (IpAddr::V4(addr), IpAddr::V4(peer)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                u32::from(*addr) & mask == u32::from(peer) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(peer)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                u128::from(*addr) & mask == u128::from(peer) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// The client behind `peer`: the last `X-Forwarded-For` entry when `peer`
+    /// is a trusted proxy, which is the one that proxy added, otherwise `peer`
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusts(peer) {
+            return peer;
+        }
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .next_back()
+            .and_then(|entry| entry.trim().parse().ok())
+            .unwrap_or(peer)
+    }
+}
+
+/// Middleware recording the request's [`ClientIp`] for auditing and rate limiting
+pub async fn identify_client(State(proxies): State<Arc<TrustedProxies>>, mut request: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let client = proxies.client_ip(peer.ip(), request.headers());
+        request.extensions_mut().insert(ClientIp(client));
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_forwarded_for_is_only_trusted_from_proxies() {
+        let proxies = TrustedProxies::parse("10.0.0.5, 172.18.0.0/16").unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let headers = forwarded(&["198.51.100.1, 203.0.113.7"]);
+
+        assert_eq!(proxies.client_ip("10.0.0.5".parse().unwrap(), &headers), client);
+        assert_eq!(proxies.client_ip("172.18.3.4".parse().unwrap(), &headers), client);
+
+        let direct: IpAddr = "10.0.0.6".parse().unwrap();
+        assert_eq!(proxies.client_ip(direct, &headers), direct);
+        assert_eq!(TrustedProxies::default().client_ip(direct, &headers), direct);
+    }
+
+    #[test]
+    fn test_missing_or_invalid_forwarded_for_keeps_the_peer() {
+        let proxies = TrustedProxies::parse("::1").unwrap();
+        let peer: IpAddr = "::1".parse().unwrap();
+
+        assert_eq!(proxies.client_ip(peer, &HeaderMap::new()), peer);
+        assert_eq!(proxies.client_ip(peer, &forwarded(&["not-an-ip"])), peer);
+        assert_eq!(proxies.client_ip(peer, &forwarded(&["1.1.1.1", "2001:db8::1"])), "2001:db8::1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_entries() {
+        assert!(TrustedProxies::parse("").unwrap().0.is_empty());
+        assert!(TrustedProxies::parse("0.0.0.0/0").is_ok());
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("ui").is_err());
+    }
+}
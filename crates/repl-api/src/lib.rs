@@ -1,3 +1,5 @@
+pub mod client;
+pub mod rate_limit;
 mod security;
 pub use security::{validate_code, CodeValidationResult, SecurityViolation};
 
@@ -7,22 +9,35 @@ use axum::response::IntoResponse;
 use axum::response::sse::{Event, Sse};
 use axum::Json;
 use futures_util::Stream;
-use serde::{Deserialize, Serialize};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::{Extension, Router};
+use xxx_audit::{self as audit, AuditEntry, AuditLog, AuditOutcome, AuditPage, AuditQuery, AuditRecord, AuditVerdict};
 use service_registry::get_service_endpoint;
-use std::collections::HashMap;
+use service_registry::openapi::Defaults;
+use service_registry::versioning;
+use service_registry::telemetry;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Language {
-    Python,
-    Node,
-    Rust,
-    Go,
-    Ruby,
+use std::sync::{Arc, OnceLock};
+use utoipa::OpenApi;
+use client::ClientIp;
+use xxx_types::container::{CreateContainerRequest, CreateContainerResponse, NetworkPolicy};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+pub use xxx_types::repl::{ExecuteReplRequest, ExecuteReplResponse, Language, LanguagesResponse};
+
+/// How code in each [`Language`] is run in a container
+pub trait LanguageRuntime {
+    fn container_image(&self) -> &str;
+    fn install_dependencies_command(&self, dependencies: &[String]) -> Option<String>;
+    fn execute_command(&self, code: &str) -> Vec<String>;
+    fn build_command_with_dependencies(&self, code: &str, dependencies: &[String]) -> Vec<String>;
 }
 
-impl Language {
-    pub fn container_image(&self) -> &str {
+impl LanguageRuntime for Language {
+    fn container_image(&self) -> &str {
         match self {
             Language::Python => "python:3.11-slim",
             Language::Node => "node:20-slim",
@@ -32,7 +47,7 @@ impl Language {
         }
     }
 
-    pub fn install_dependencies_command(&self, dependencies: &[String]) -> Option<String> {
+    fn install_dependencies_command(&self, dependencies: &[String]) -> Option<String> {
         if dependencies.is_empty() {
             return None;
         }
@@ -56,7 +71,7 @@ impl Language {
         Some(cmd)
     }
 
-    pub fn execute_command(&self, code: &str) -> Vec<String> {
+    fn execute_command(&self, code: &str) -> Vec<String> {
         match self {
             Language::Python => vec!["python".to_string(), "-c".to_string(), code.to_string()],
             Language::Node => vec!["node".to_string(), "-e".to_string(), code.to_string()],
@@ -82,7 +97,7 @@ impl Language {
         }
     }
 
-    pub fn build_command_with_dependencies(
+    fn build_command_with_dependencies(
         &self,
         code: &str,
         dependencies: &[String],
@@ -114,19 +129,72 @@ pub struct ReplSession {
     language: Language,
     containers_api_url: String,
     session_variables: HashMap<String, String>,
+    network: NetworkPolicy,
+    env: BTreeMap<String, String>,
+    secrets: BTreeMap<String, String>,
+    credential: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-struct CreateContainerRequest {
-    image: String,
-    command: Vec<String>,
+/// Attach the caller's own container-api token, or `CONTAINER_API_TOKEN`
+/// when the caller sent none
+fn with_container_api_token(
+    request: reqwest::RequestBuilder,
+    credential: Option<&str>,
+) -> reqwest::RequestBuilder {
+    match credential.map(str::to_string).or_else(|| std::env::var("CONTAINER_API_TOKEN").ok()) {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct CreateContainerResponse {
-    id: String,
-    message: String,
-    output: Option<String>,
+/// The bearer token of a request, if any
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Refuse requests that only the caller's own container-api identity may make
+///
+/// Without a token of its own the caller would borrow repl-api's
+/// `CONTAINER_API_TOKEN`, so network policies and secrets are refused.
+fn check_credential(payload: &ExecuteReplRequest, credential: Option<&str>) -> std::result::Result<(), String> {
+    if credential.is_some() {
+        return Ok(());
+    }
+    if !payload.network.is_none() {
+        return Err("A network policy requires your own container-api token in Authorization".to_string());
+    }
+    if !payload.secrets.is_empty() {
+        return Err("Secrets require your own container-api token in Authorization".to_string());
+    }
+    Ok(())
+}
+
+/// container-api stopped the execution for exceeding its time limit
+#[derive(Debug, thiserror::Error)]
+#[error("Container execution timed out: {0}")]
+pub struct ExecutionTimedOut(pub String);
+
+/// How a failed execution is audited: `timeout` for [`ExecutionTimedOut`], else `error`
+fn failure_outcome(error: &anyhow::Error) -> AuditOutcome {
+    if error.is::<ExecutionTimedOut>() {
+        AuditOutcome::Timeout
+    } else {
+        AuditOutcome::Error
+    }
+}
+
+/// Output of a finished execution, with what container-api reported about it
+#[derive(Debug, Clone)]
+pub struct ExecutionResult {
+    pub output: String,
+    pub container_id: String,
+    pub exit_code: Option<i64>,
+    /// Whether the output limits cut `output`
+    pub truncated: bool,
 }
 
 impl ReplSession {
@@ -142,9 +210,33 @@ impl ReplSession {
                     .unwrap_or_else(|_| "http://localhost:3000".to_string())
             }),
             session_variables: HashMap::new(),
+            network: NetworkPolicy::None,
+            env: BTreeMap::new(),
+            secrets: BTreeMap::new(),
+            credential: None,
         }
     }
 
+    /// Network policy forwarded to container-api
+    pub fn with_network(mut self, network: NetworkPolicy) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Bearer token container-api identifies the execution by, instead of
+    /// `CONTAINER_API_TOKEN`
+    pub fn with_credential(mut self, credential: Option<String>) -> Self {
+        self.credential = credential;
+        self
+    }
+
+    /// Environment variables, and secret references resolved by container-api
+    pub fn with_env(mut self, env: BTreeMap<String, String>, secrets: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self.secrets = secrets;
+        self
+    }
+
     pub async fn execute(&mut self, code: &str) -> Result<String> {
         self.execute_with_dependencies(code, &[]).await
     }
@@ -154,27 +246,49 @@ impl ReplSession {
         code: &str,
         dependencies: &[String],
     ) -> Result<String> {
+        self.execute_detailed(code, dependencies)
+            .await
+            .map(|result| result.output)
+    }
+
+    #[tracing::instrument(name = "execute", skip_all, fields(language = ?self.language))]
+    pub async fn execute_detailed(
+        &mut self,
+        code: &str,
+        dependencies: &[String],
+    ) -> Result<ExecutionResult> {
         let client = reqwest::Client::new();
 
         let request = CreateContainerRequest {
             image: self.language.container_image().to_string(),
-            command: self
-                .language
-                .build_command_with_dependencies(code, dependencies),
+            command: Some(
+                self.language
+                    .build_command_with_dependencies(code, dependencies),
+            ),
+            network: self.network.clone(),
+            env: self.env.clone(),
+            secrets: self.secrets.clone(),
+            ..Default::default()
         };
 
-        let response = client
-            .post(format!("{}/api/containers/create", self.containers_api_url))
+        let response = with_container_api_token(
+            telemetry::inject(client.post(format!("{}/api/v1/containers/create", self.containers_api_url))),
+            self.credential.as_deref(),
+        )
             .json(&request)
             .send()
             .await
             .context("Failed to send request to containers API")?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            if status == reqwest::StatusCode::REQUEST_TIMEOUT {
+                return Err(ExecutionTimedOut(error_text).into());
+            }
             anyhow::bail!("Container execution failed: {}", error_text);
         }
 
@@ -183,12 +297,18 @@ impl ReplSession {
             .await
             .context("Failed to parse container response")?;
 
-        Ok(container_response.output.unwrap_or_else(|| {
+        let output = container_response.output.unwrap_or_else(|| {
             format!(
                 "Executed in container {}: {}",
                 container_response.id, container_response.message
             )
-        }))
+        });
+        Ok(ExecutionResult {
+            output,
+            container_id: container_response.id,
+            exit_code: container_response.exit_code,
+            truncated: container_response.truncated,
+        })
     }
 
     pub fn set_variable(&mut self, key: String, value: String) {
@@ -204,25 +324,222 @@ impl ReplSession {
     }
 }
 
-// ========== Axum Handlers ==========
-#[derive(Deserialize)]
-pub struct ExecuteReplRequest {
-    pub language: Language,
-    pub code: String,
-    #[serde(default)]
-    pub dependencies: Vec<String>,
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Runs code snippets in language containers through container-api"),
+    modifiers(&Defaults),
+    tags((name = "repl", description = "Code execution and its audit log"))
+)]
+struct ApiDoc;
+
+/// The `/api/v1/repl` execution routes and their deprecated `/api/repl` aliases
+pub fn routes() -> Router {
+    let (router, _) = api().split_for_parts();
+    versioning::versioned(router)
+}
+
+/// `GET /api/v1/audit` and its `/api/audit` alias, kept apart since the
+/// all-in-one server mounts it elsewhere
+pub fn audit_routes() -> Router {
+    let (router, _) = audit_api().split_for_parts();
+    versioning::versioned(router)
+}
+
+/// `GET /api/v1/repl/tls/ca.pem` and its `/api/repl/tls/ca.pem` alias,
+/// serving the root certificate of repl-api's persisted CA, if it has one
+pub fn tls_routes(ca_pem: Option<String>) -> Router {
+    let (router, _) = tls_api().split_for_parts();
+    versioning::versioned(router.with_state(ca_pem.map(Arc::from)))
 }
 
-#[derive(Serialize)]
-pub struct ExecuteReplResponse {
-    pub result: String,
-    pub success: bool,
+/// OpenAPI document of [`routes`], [`audit_routes`] and [`tls_routes`]
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut spec = api().merge(audit_api()).into_openapi();
+    spec.merge(tls_api().into_openapi());
+    versioning::document(spec)
+}
+
+fn api() -> OpenApiRouter {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(execute_repl))
+        .routes(routes!(execute_repl_stream))
+        .routes(routes!(list_languages))
+}
+
+fn audit_api() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(get_audit))
+}
+
+fn tls_api() -> OpenApiRouter<Option<Arc<str>>> {
+    OpenApiRouter::new().routes(routes!(ca_cert))
+}
+
+/// Root certificate of the persisted CA, for clients to verify repl-api with
+#[utoipa::path(
+    get,
+    path = "/repl/tls/ca.pem",
+    tag = "repl",
+    responses(
+        (status = OK, description = "PEM root certificate", body = String, content_type = "application/x-pem-file"),
+        (status = NOT_FOUND, description = "repl-api is not using a persisted CA (`REPL_TLS_CA_DIR` is unset)", body = String),
+    )
+)]
+async fn ca_cert(State(ca_pem): State<Option<Arc<str>>>) -> axum::response::Response {
+    match ca_pem {
+        Some(pem) => ([(header::CONTENT_TYPE, "application/x-pem-file")], pem.to_string()).into_response(),
+        None => (StatusCode::NOT_FOUND, "repl-api is not using a persisted CA").into_response(),
+    }
+}
+
+// ========== container-api ==========
+
+static CONTAINER_API_URL: OnceLock<String> = OnceLock::new();
+
+/// Send executions to the container-api at `url` instead of looking it up in
+/// the service registry, e.g. when both run in the same process
+pub fn use_container_api(url: impl Into<String>) {
+    let _ = CONTAINER_API_URL.set(url.into());
+}
+
+async fn container_api_endpoint() -> Option<String> {
+    match CONTAINER_API_URL.get() {
+        Some(url) => Some(url.clone()),
+        None => get_service_endpoint("container-api").await,
+    }
+}
+
+// ========== Audit ==========
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// Enable auditing of repl executions to the log configured by the environment
+/// (see [`AuditLog::from_env`]); without it nothing is recorded
+pub fn init_audit_log() -> std::io::Result<()> {
+    let log = AuditLog::from_env("repl-api")?;
+    let _ = AUDIT_LOG.set(log);
+    Ok(())
+}
+
+/// Start the audit record for an execution; it is written when the entry is
+/// dropped, and only records metrics when the audit log is not enabled
+///
+/// repl-api does not authenticate callers, so the client address identifies them.
+fn audit_entry(
+    client: &Option<Extension<ClientIp>>,
+    payload: &ExecuteReplRequest,
+    validation: &CodeValidationResult,
+) -> AuditEntry {
+    let caller = client
+        .as_ref()
+        .map_or_else(|| "anonymous".to_string(), |Extension(ClientIp(ip))| ip.to_string());
+
+    let mut record = AuditRecord::new(
+        "repl-api",
+        caller,
+        payload.language.container_image(),
+        audit::code_hash(&payload.code),
+    );
+    record.language = Some(format!("{:?}", payload.language));
+    record.verdicts = validation
+        .violations
+        .iter()
+        .map(|v| AuditVerdict {
+            description: v.description.clone(),
+            severity: format!("{:?}", v.severity).to_lowercase(),
+            blocked: v.should_block,
+        })
+        .collect();
+    AuditEntry::new(AUDIT_LOG.get(), record)
+}
+
+fn validate(payload: &ExecuteReplRequest, language: &str) -> CodeValidationResult {
+    tracing::info_span!("validate", language).in_scope(|| validate_code(&payload.code, language, &payload.dependencies))
+}
+
+/// Count each blocking rule in `validation_blocks_total`
+fn record_blocks(validation: &CodeValidationResult) {
+    for violation in validation.violations.iter().filter(|v| v.should_block) {
+        xxx_metrics::validation_blocked(violation.rule);
+    }
+}
+
+/// Query repl executions, newest first
+///
+/// Requires `Authorization: Bearer $AUDIT_TOKEN`; disabled when `AUDIT_TOKEN` is unset.
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "repl",
+    operation_id = "get_repl_audit",
+    params(AuditQuery),
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "A page of audit records", body = AuditPage),
+        (status = UNAUTHORIZED, description = "Missing or invalid token", body = String),
+        (status = FORBIDDEN, description = "`AUDIT_TOKEN` is not set", body = String),
+        (status = SERVICE_UNAVAILABLE, description = "The audit log is not enabled", body = String),
+    )
+)]
+pub async fn get_audit(headers: HeaderMap, Query(query): Query<AuditQuery>) -> impl IntoResponse {
+    let Ok(expected) = std::env::var("AUDIT_TOKEN") else {
+        return (StatusCode::FORBIDDEN, "Audit endpoint is disabled; set AUDIT_TOKEN").into_response();
+    };
+    if bearer_token(&headers).as_deref() != Some(expected.as_str()) {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+    }
+
+    let Some(log) = AUDIT_LOG.get() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Audit log is not enabled").into_response();
+    };
+    match log.query(query).await {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read audit log: {}", e),
+        )
+            .into_response(),
+    }
 }
 
-pub async fn execute_repl(Json(payload): Json<ExecuteReplRequest>) -> impl IntoResponse {
+// ========== Axum Handlers ==========
+/// Validate and run code, returning its output once it finishes
+///
+/// An `Authorization: Bearer` token is forwarded to container-api as the
+/// caller's identity; a network policy requires one.
+#[utoipa::path(
+    post,
+    path = "/repl/execute",
+    tag = "repl",
+    security((), ("bearer" = [])),
+    responses(
+        (status = OK, description = "The code ran", body = ExecuteReplResponse),
+        (status = FORBIDDEN, description = "Code validation blocked the code, or it needs the caller's own token", body = ExecuteReplResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "The execution failed", body = ExecuteReplResponse),
+    )
+)]
+pub async fn execute_repl(
+    client: Option<Extension<ClientIp>>,
+    headers: HeaderMap,
+    Json(payload): Json<ExecuteReplRequest>,
+) -> impl IntoResponse {
     // Validate code for security violations
     let language_str = format!("{:?}", payload.language);
-    let validation = validate_code(&payload.code, &language_str, &payload.dependencies);
+    let validation = validate(&payload, &language_str);
+    let mut audit = audit_entry(&client, &payload, &validation);
+    let credential = bearer_token(&headers);
+
+    if let Err(msg) = check_credential(&payload, credential.as_deref()) {
+        audit.fail(AuditOutcome::Blocked, &msg);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ExecuteReplResponse {
+                result: msg,
+                success: false,
+                truncated: false,
+            }),
+        )
+            .into_response();
+    }
 
     if !validation.is_safe {
         let violations_msg = validation
@@ -232,17 +549,20 @@ pub async fn execute_repl(Json(payload): Json<ExecuteReplRequest>) -> impl IntoR
             .map(|v| v.description.clone())
             .collect::<Vec<_>>()
             .join("; ");
+        record_blocks(&validation);
 
         tracing::warn!(
             "Code execution blocked due to security violations: {}",
             violations_msg
         );
+        audit.fail(AuditOutcome::Blocked, &violations_msg);
 
         return (
             StatusCode::FORBIDDEN,
             Json(ExecuteReplResponse {
                 result: format!("Code execution blocked: {}", violations_msg),
                 success: false,
+                truncated: false,
             }),
         )
             .into_response();
@@ -254,45 +574,76 @@ pub async fn execute_repl(Json(payload): Json<ExecuteReplRequest>) -> impl IntoR
     }
 
     // Try to get container-api endpoint from service registry
-    let endpoint = get_service_endpoint("container-api").await;
+    let endpoint = container_api_endpoint().await;
 
-    let mut session = ReplSession::new_with_endpoint(payload.language, endpoint);
+    let mut session =
+        ReplSession::new_with_endpoint(payload.language, endpoint)
+            .with_network(payload.network)
+            .with_env(payload.env, payload.secrets)
+            .with_credential(credential);
 
     match session
-        .execute_with_dependencies(&payload.code, &payload.dependencies)
+        .execute_detailed(&payload.code, &payload.dependencies)
         .await
     {
-        Ok(result) => (
-            StatusCode::OK,
-            Json(ExecuteReplResponse {
-                result,
-                success: true,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ExecuteReplResponse {
-                result: e.to_string(),
-                success: false,
-            }),
-        )
-            .into_response(),
+        Ok(result) => {
+            audit.record.container_id = Some(result.container_id);
+            audit.complete(result.exit_code);
+            (
+                StatusCode::OK,
+                Json(ExecuteReplResponse {
+                    result: result.output,
+                    success: true,
+                    truncated: result.truncated,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            audit.fail(failure_outcome(&e), e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ExecuteReplResponse {
+                    result: e.to_string(),
+                    success: false,
+                    truncated: false,
+                }),
+            )
+                .into_response()
+        }
     }
 }
 
-#[derive(Serialize)]
-pub struct LanguagesResponse {
-    pub languages: Vec<String>,
-}
-
+/// Validate and run code, streaming its output as server-sent events
+///
+/// Events are relayed from container-api's `/api/v1/containers/create/stream`;
+/// failures, including blocked code, are sent as `ERROR: ...` data. Callers
+/// authenticate as for `/repl/execute`.
+#[utoipa::path(
+    post,
+    path = "/repl/execute/stream",
+    tag = "repl",
+    security((), ("bearer" = [])),
+    responses((status = OK, description = "Event stream of the execution", content_type = "text/event-stream", body = String))
+)]
 pub async fn execute_repl_stream(
+    client: Option<Extension<ClientIp>>,
+    headers: HeaderMap,
     Json(payload): Json<ExecuteReplRequest>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let credential = bearer_token(&headers);
     let stream = async_stream::stream! {
         // Validate code for security violations
         let language_str = format!("{:?}", payload.language);
-        let validation = validate_code(&payload.code, &language_str, &payload.dependencies);
+        let validation = validate(&payload, &language_str);
+        // Written when the stream ends, including when the client disconnects
+        let mut audit = audit_entry(&client, &payload, &validation);
+
+        if let Err(msg) = check_credential(&payload, credential.as_deref()) {
+            audit.fail(AuditOutcome::Blocked, &msg);
+            yield Ok(Event::default().data(format!("ERROR: {}", msg)));
+            return;
+        }
 
         if !validation.is_safe {
             let violations_msg = validation
@@ -302,11 +653,13 @@ pub async fn execute_repl_stream(
                 .map(|v| v.description.clone())
                 .collect::<Vec<_>>()
                 .join("; ");
+            record_blocks(&validation);
 
             tracing::warn!(
                 "Code execution blocked due to security violations: {}",
                 violations_msg
             );
+            audit.fail(AuditOutcome::Blocked, &violations_msg);
 
             yield Ok(Event::default().data(format!("ERROR: Code execution blocked: {}", violations_msg)));
             return;
@@ -318,7 +671,7 @@ pub async fn execute_repl_stream(
         }
 
         // Try to get container-api endpoint from service registry
-        let endpoint = get_service_endpoint("container-api").await;
+        let endpoint = container_api_endpoint().await;
         let containers_api_url = endpoint.unwrap_or_else(|| {
             std::env::var("CONTAINERS_API_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".to_string())
@@ -326,20 +679,29 @@ pub async fn execute_repl_stream(
 
         let request = CreateContainerRequest {
             image: payload.language.container_image().to_string(),
-            command: payload
-                .language
-                .build_command_with_dependencies(&payload.code, &payload.dependencies),
+            command: Some(
+                payload
+                    .language
+                    .build_command_with_dependencies(&payload.code, &payload.dependencies),
+            ),
+            network: payload.network.clone(),
+            env: payload.env.clone(),
+            secrets: payload.secrets.clone(),
+            ..Default::default()
         };
 
         let client = reqwest::Client::new();
-        let response = match client
-            .post(format!("{}/api/containers/create/stream", containers_api_url))
+        let response = match with_container_api_token(
+            telemetry::inject(client.post(format!("{}/api/v1/containers/create/stream", containers_api_url))),
+            credential.as_deref(),
+        )
             .json(&request)
             .send()
             .await
         {
             Ok(r) => r,
             Err(e) => {
+                audit.fail(AuditOutcome::Error, format!("Failed to connect to container API: {}", e));
                 yield Ok(Event::default().data(format!("ERROR: Failed to connect to container API: {}", e)));
                 return;
             }
@@ -350,6 +712,7 @@ pub async fn execute_repl_stream(
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            audit.fail(AuditOutcome::Error, &error_text);
             yield Ok(Event::default().data(format!("ERROR: Container execution failed: {}", error_text)));
             return;
         }
@@ -357,6 +720,9 @@ pub async fn execute_repl_stream(
         // Stream the SSE events from the container API
         let mut event_source = response.bytes_stream();
         use futures_util::StreamExt;
+        // Named events (e.g. `network`) are forwarded under their name, not as output
+        let mut event_type: Option<String> = None;
+        let mut exit_code: Option<i64> = None;
 
         while let Some(chunk_result) = event_source.next().await {
             match chunk_result {
@@ -366,16 +732,27 @@ pub async fn execute_repl_stream(
                     for line in text.lines() {
                         if line.starts_with("data:") {
                             let data = line.strip_prefix("data:").unwrap_or("").trim();
+                            if event_type.as_deref() == Some("exit") {
+                                exit_code = serde_json::from_str::<serde_json::Value>(data)
+                                    .ok()
+                                    .and_then(|v| v["exit_code"].as_i64());
+                            }
                             if !data.is_empty() {
-                                yield Ok(Event::default().data(data.to_string()));
+                                match &event_type {
+                                    Some(name) => yield Ok(Event::default().event(name).data(data)),
+                                    None => yield Ok(Event::default().data(data)),
+                                }
                             }
                         } else if line.starts_with("event:") {
-                            // Handle event type if needed
-                            let event_type = line.strip_prefix("event:").unwrap_or("").trim();
-                            if event_type == "done" {
+                            let name = line.strip_prefix("event:").unwrap_or("").trim();
+                            if name == "done" {
+                                audit.complete(exit_code);
                                 yield Ok(Event::default().event("done").data(""));
                                 break;
                             }
+                            event_type = Some(name.to_string());
+                        } else if line.is_empty() {
+                            event_type = None;
                         }
                     }
                 }
@@ -390,15 +767,15 @@ pub async fn execute_repl_stream(
     Sse::new(stream)
 }
 
+#[utoipa::path(
+    get,
+    path = "/repl/languages",
+    tag = "repl",
+    responses((status = OK, description = "Supported languages", body = LanguagesResponse))
+)]
 pub async fn list_languages() -> impl IntoResponse {
     Json(LanguagesResponse {
-        languages: vec![
-            "Python".to_string(),
-            "Node".to_string(),
-            "Rust".to_string(),
-            "Go".to_string(),
-            "Ruby".to_string(),
-        ],
+        languages: Language::ALL.iter().map(Language::to_string).collect(),
     })
 }
 
@@ -579,4 +956,108 @@ mod tests {
         assert!(cmd[2].contains("go install github.com/spf13/cobra@latest"));
         assert!(cmd[2].contains("go run"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_container_request_forwards_env_and_secrets() {
+        let request = CreateContainerRequest {
+            image: "python:3.11-slim".to_string(),
+            command: Some(vec!["python".to_string()]),
+            env: BTreeMap::from([("MODE".to_string(), "test".to_string())]),
+            secrets: BTreeMap::from([("API_TOKEN".to_string(), "api-token".to_string())]),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["env"]["MODE"], "test");
+        assert_eq!(json["secrets"]["API_TOKEN"], "api-token");
+
+        let request = CreateContainerRequest { env: BTreeMap::new(), secrets: BTreeMap::new(), ..request };
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("env").is_none());
+        assert!(json.get("secrets").is_none());
+    }
+
+    #[test]
+    fn test_network_and_secrets_require_caller_credential() {
+        let payload: ExecuteReplRequest =
+            serde_json::from_str(r#"{"language":"Python","code":"print(1)","network":{"mode":"full"}}"#).unwrap();
+        assert!(check_credential(&payload, None).is_err());
+        assert!(check_credential(&payload, Some("caller-token")).is_ok());
+
+        let payload = ExecuteReplRequest { network: NetworkPolicy::None, ..payload };
+        assert!(check_credential(&payload, None).is_ok());
+
+        let payload = ExecuteReplRequest {
+            secrets: BTreeMap::from([("API_TOKEN".to_string(), "api-token".to_string())]),
+            ..payload
+        };
+        assert!(check_credential(&payload, None).is_err());
+        assert!(check_credential(&payload, Some("caller-token")).is_ok());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(axum::http::header::AUTHORIZATION, "Bearer abc ".parse().unwrap());
+        assert_eq!(bearer_token(&headers).as_deref(), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn test_container_api_timeouts_are_audited_as_timeouts() {
+        let app = Router::new().route(
+            "/api/v1/containers/create",
+            axum::routing::post(|| async { (StatusCode::REQUEST_TIMEOUT, "exceeded maximum time limit") }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut session = ReplSession::new_with_endpoint(Language::Python, Some(url));
+        let error = session.execute_detailed("while True: pass", &[]).await.unwrap_err();
+        assert!(error.is::<ExecutionTimedOut>());
+        assert_eq!(failure_outcome(&error), AuditOutcome::Timeout);
+        assert_eq!(failure_outcome(&anyhow::anyhow!("refused")), AuditOutcome::Error);
+    }
+
+    #[test]
+    fn test_create_container_response_exit_code() {
+        let response: CreateContainerResponse =
+            serde_json::from_str(r#"{"id":"abc","message":"ok","output":"hi","exit_code":3}"#).unwrap();
+        assert_eq!(response.exit_code, Some(3));
+
+        let response: CreateContainerResponse =
+            serde_json::from_str(r#"{"id":"abc","message":"ok"}"#).unwrap();
+        assert_eq!(response.exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn test_ca_cert_is_versioned() {
+        let app = tls_routes(Some("-----BEGIN CERTIFICATE-----\n".to_string())).merge(
+            Router::new().nest("/none", tls_routes(None)),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let response = reqwest::get(format!("{}/api/v1/repl/tls/ca.pem", url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::OK.as_u16());
+        assert_eq!(response.headers()["content-type"], "application/x-pem-file");
+        assert!(response.headers().get("deprecation").is_none());
+        assert_eq!(response.text().await.unwrap(), "-----BEGIN CERTIFICATE-----\n");
+
+        let response = reqwest::get(format!("{}/api/repl/tls/ca.pem", url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::OK.as_u16());
+        assert!(response.headers().get("deprecation").is_some());
+
+        let response = reqwest::get(format!("{}/none/api/v1/repl/tls/ca.pem", url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::NOT_FOUND.as_u16());
+    }
+
+    #[test]
+    fn test_openapi_matches_published() {
+        let published = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if let Err(e) = service_registry::openapi::check_published(&openapi(), published) {
+            panic!("{}", e);
+        }
+    }
+}
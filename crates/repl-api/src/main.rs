@@ -1,42 +1,58 @@
 mod tls;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use service_registry::register_service;
+use axum::{middleware, routing::get, Router};
+use service_registry::{metrics, openapi, register_service, telemetry, versioning, HealthCheck};
+use repl_api::client::{identify_client, TrustedProxies};
 use std::net::SocketAddr;
-use axum_server::tls_rustls::RustlsConfig;
-use crate::tls::make_cert;
+use std::sync::Arc;
+use tower_http::trace::TraceLayer;
+use crate::tls::{ServerTls, TlsSource};
 
 #[tokio::main]
 async fn main() {
     // Initialize tracing
-    tracing_subscriber::fmt::init();
+    let _telemetry = telemetry::init("repl-api");
 
     println!("repl-api server starting...");
 
 
-    let app = Router::new()
-        .route("/api/repl/execute", post(repl_api::execute_repl))
-        .route("/api/repl/execute/stream", post(repl_api::execute_repl_stream))
-        .route("/api/repl/languages", get(repl_api::list_languages));
-
-    // Generate a self-signed cert (via your tls module)
-    let (cert_pem, key_pem) = make_cert();
+    repl_api::init_audit_log().expect("Failed to open audit log");
+    let trusted_proxies = TrustedProxies::from_env().expect("Invalid REPL_TRUSTED_PROXIES");
 
-    // Use the in-memory PEM data directly
-    let tls_config = RustlsConfig::from_pem(cert_pem.into_bytes(), key_pem.into_bytes())
+    let tls = ServerTls::load(&TlsSource::from_env().expect("Invalid repl-api TLS configuration"))
         .await
-        .expect("failed to build RustlsConfig from cert data");
+        .expect("Failed to load repl-api TLS certificate");
+    let app = Router::new()
+        .route("/metrics", get(metrics::handler))
+        .merge(repl_api::routes().layer(middleware::from_fn_with_state(
+            Arc::new(trusted_proxies),
+            identify_client,
+        )))
+        .merge(repl_api::audit_routes())
+        .merge(repl_api::tls_routes(tls.ca_pem.clone()))
+        .merge(openapi::routes(repl_api::openapi()))
+        .merge(versioning::routes("repl-api", env!("CARGO_PKG_VERSION")))
+        .layer(middleware::from_fn_with_state("repl-api", metrics::track))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span));
 
-    // Bind HTTPS on port 3001
     let addr = SocketAddr::from(([0, 0, 0, 0], 3001));
-    println!("repl-api listening securely on https://{}", addr);
-    let (service, _lease_id) = register_service!("repl-api", "repl-api", 3001).await;
+    let health_check = match tls.config {
+        Some(_) => HealthCheck::https("/api/v1/repl/languages", true),
+        None => HealthCheck::http("/api/v1/repl/languages"),
+    };
+    let (service, _lease_id) = register_service!("repl-api", "repl-api", 3001, health_check).await;
     tracing::info!("Service registered: {} ({})", service.name, service.id);
-    axum_server::bind_rustls(addr, tls_config)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls.config {
+        Some(tls_config) => {
+            println!("repl-api listening securely on https://{}", addr);
+            axum_server::bind_rustls(addr, tls_config).serve(app).await.unwrap();
+        }
+        None => {
+            println!("repl-api listening on http://{}", addr);
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
 }
@@ -1,10 +1,10 @@
 use axum::{
-    body::Body,
     extract::{ConnectInfo, Request},
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use crate::client::ClientIp;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -131,6 +131,9 @@ impl RateLimiter {
 }
 
 /// Middleware function for rate limiting
+///
+/// Requests are counted per [`ClientIp`] when [`identify_client`](crate::client::identify_client)
+/// ran, so clients behind a trusted proxy get their own buckets.
 pub async fn rate_limit_middleware(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     request: Request,
@@ -145,7 +148,11 @@ pub async fn rate_limit_middleware(
         }
     };
 
-    let ip = addr.ip().to_string();
+    let ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map_or(addr.ip(), |ClientIp(ip)| *ip)
+        .to_string();
 
     match limiter.check_rate_limit(&ip).await {
         Ok(()) => {
@@ -155,6 +162,7 @@ pub async fn rate_limit_middleware(
         Err(retry_after) => {
             // Rate limit exceeded
             tracing::warn!("Rate limit exceeded for IP: {}", ip);
+            service_registry::metrics::rate_limit_rejected("repl-api");
 
             let retry_seconds = retry_after.as_secs();
             let response = (
@@ -178,9 +186,9 @@ pub trait RateLimitExt {
     fn with_rate_limit(self, limiter: RateLimiter) -> Self;
 }
 
-impl RateLimitExt for Router {
+impl<S: Clone + Send + Sync + 'static> RateLimitExt for Router<S> {
     fn with_rate_limit(self, limiter: RateLimiter) -> Self {
-        self.layer(axum::middleware::from_fn(move |req, next| {
+        self.layer(axum::middleware::from_fn(move |req: Request, next: Next| {
             let limiter = limiter.clone();
             async move {
                 // Add limiter to extensions so middleware can access it
@@ -190,8 +198,8 @@ impl RateLimitExt for Router {
                     ConnectInfo(
                         req.extensions()
                             .get::<ConnectInfo<SocketAddr>>()
-                            .copied()
-                            .unwrap_or(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0)))),
+                            .map(|info| info.0)
+                            .unwrap_or(SocketAddr::from(([127, 0, 0, 1], 0))),
                     ),
                     req,
                     next,
@@ -215,7 +223,8 @@ mod tests {
         assert!(bucket.try_consume(5.0));
         assert!(!bucket.try_consume(1.0)); // Now empty
 
-        assert_eq!(bucket.tokens, 0.0);
+        // Only the refill of the few microseconds the test took is left
+        assert!(bucket.tokens < 0.01);
     }
 
     #[test]
@@ -14,47 +14,55 @@ static DANGEROUS_PATTERNS: Lazy<Vec<DangerousPattern>> = Lazy::new(|| {
         // Fork bombs
         DangerousPattern {
             pattern: Regex::new(r":\(\)\{.*:\|:&\};:").unwrap(),
+            rule: "fork_bomb",
             description: "Fork bomb pattern detected",
             severity: Severity::Critical,
         },
         DangerousPattern {
             pattern: Regex::new(r"while\s+true.*fork|fork.*while\s+true").unwrap(),
+            rule: "fork_bomb_loop",
             description: "Potential fork bomb loop detected",
             severity: Severity::Critical,
         },
         // Network scanning/attacks
         DangerousPattern {
             pattern: Regex::new(r"nmap|masscan|zmap").unwrap(),
+            rule: "network_scanner",
             description: "Network scanning tool detected",
             severity: Severity::Critical,
         },
         // Crypto mining
         DangerousPattern {
             pattern: Regex::new(r"xmrig|ethminer|cgminer|bfgminer|cryptonight").unwrap(),
+            rule: "crypto_miner",
             description: "Cryptocurrency mining software detected",
             severity: Severity::Critical,
         },
         // Reverse shells
         DangerousPattern {
             pattern: Regex::new(r"/bin/(bash|sh).*-i|nc.*-e\s+/bin/(bash|sh)|bash\s+-i\s+>&\s+/dev/tcp").unwrap(),
+            rule: "reverse_shell",
             description: "Reverse shell pattern detected",
             severity: Severity::Critical,
         },
         // File system destruction
         DangerousPattern {
             pattern: Regex::new(r"rm\s+-rf\s+/|dd\s+if=/dev/(zero|random)\s+of=/dev/").unwrap(),
+            rule: "destructive_fs",
             description: "Potentially destructive file system operation",
             severity: Severity::High,
         },
         // SQL injection attempts (in code strings)
         DangerousPattern {
             pattern: Regex::new(r"(union.*select|drop\s+table|delete\s+from.*where\s+1=1)").unwrap(),
+            rule: "sql_injection",
             description: "SQL injection pattern detected",
             severity: Severity::Medium,
         },
         // Excessive loops (simple detection)
         DangerousPattern {
             pattern: Regex::new(r"while\s*\(\s*1\s*\)|while\s+True|for\s*\(\s*;\s*;\s*\)").unwrap(),
+            rule: "infinite_loop",
             description: "Infinite loop pattern detected",
             severity: Severity::Medium,
         },
@@ -120,12 +128,15 @@ pub enum Severity {
 #[derive(Debug, Clone)]
 pub struct DangerousPattern {
     pattern: Regex,
+    rule: &'static str,
     description: &'static str,
     severity: Severity,
 }
 
 #[derive(Debug)]
 pub struct SecurityViolation {
+    /// Stable identifier of the rule that matched, used as a metrics label
+    pub rule: &'static str,
     pub description: String,
     pub severity: Severity,
     pub should_block: bool,
@@ -144,6 +155,7 @@ pub fn validate_code(code: &str, language: &str, dependencies: &[String]) -> Cod
     // Check code size
     if code.len() > MAX_CODE_SIZE {
         violations.push(SecurityViolation {
+            rule: "code_size",
             description: format!("Code size {} exceeds maximum allowed size of {} bytes",
                 code.len(), MAX_CODE_SIZE),
             severity: Severity::High,
@@ -154,6 +166,7 @@ pub fn validate_code(code: &str, language: &str, dependencies: &[String]) -> Cod
     // Check dependency count
     if dependencies.len() > MAX_DEPENDENCIES {
         violations.push(SecurityViolation {
+            rule: "dependency_count",
             description: format!("Number of dependencies {} exceeds maximum allowed of {}",
                 dependencies.len(), MAX_DEPENDENCIES),
             severity: Severity::Medium,
@@ -166,6 +179,7 @@ pub fn validate_code(code: &str, language: &str, dependencies: &[String]) -> Cod
         if pattern_def.pattern.is_match(code) {
             let should_block = matches!(pattern_def.severity, Severity::Critical | Severity::High);
             violations.push(SecurityViolation {
+                rule: pattern_def.rule,
                 description: pattern_def.description.to_string(),
                 severity: pattern_def.severity.clone(),
                 should_block,
@@ -178,6 +192,7 @@ pub fn validate_code(code: &str, language: &str, dependencies: &[String]) -> Cod
         for import in dangerous_imports {
             if code.contains(import) {
                 violations.push(SecurityViolation {
+                    rule: "dangerous_import",
                     description: format!("Potentially dangerous import/pattern detected: {}", import),
                     severity: Severity::Medium,
                     should_block: false, // Warning only for imports
@@ -190,6 +205,7 @@ pub fn validate_code(code: &str, language: &str, dependencies: &[String]) -> Cod
     for dep in dependencies {
         if is_suspicious_dependency(dep) {
             violations.push(SecurityViolation {
+                rule: "suspicious_dependency",
                 description: format!("Suspicious dependency detected: {}", dep),
                 severity: Severity::High,
                 should_block: true,
@@ -275,6 +291,7 @@ mod tests {
             .collect::<Vec<_>>();
         let result = validate_code("print('hi')", "Python", &deps);
         assert!(!result.is_safe);
+        assert!(result.violations.iter().any(|v| v.rule == "dependency_count"));
     }
 
     #[test]
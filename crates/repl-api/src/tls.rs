@@ -1,12 +1,382 @@
-use rcgen::generate_simple_self_signed;
-use serde::Serialize;
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose,
+};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-pub fn make_cert() -> (String, String) {
-    let subject_alt_names = vec!["localhost".to_string()];
-    let cert_key_pair = generate_simple_self_signed(subject_alt_names)
-        .expect("failed to generate self signed");
+const DEFAULT_HOSTNAMES: &str = "localhost,repl-api";
+const DEFAULT_RELOAD_SECS: u64 = 30;
+
+/// Common name of the persisted CA; its issuer is rebuilt from these params
+const CA_COMMON_NAME: &str = "xxx repl-api CA";
+const CA_CERT_FILE: &str = "ca.pem";
+const CA_KEY_FILE: &str = "ca-key.pem";
+
+/// Where repl-api's server certificate comes from
+///
+/// - `REPL_TLS=off`: plain HTTP
+/// - `REPL_TLS_CERT` / `REPL_TLS_KEY`: PEM certificate chain and key, reloaded
+///   when either file changes (checked every `REPL_TLS_RELOAD_SECS`, default 30)
+/// - `REPL_TLS_CA_DIR`: a self-signed CA kept in this directory (created on
+///   first start) that signs a fresh certificate on every start
+/// - otherwise a throwaway self-signed certificate
+///
+/// Generated certificates cover `REPL_TLS_HOSTNAMES` (default `localhost,repl-api`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsSource {
+    Disabled,
+    Files {
+        cert: PathBuf,
+        key: PathBuf,
+        reload_interval: Duration,
+    },
+    PersistedCa {
+        dir: PathBuf,
+        hostnames: Vec<String>,
+    },
+    SelfSigned {
+        hostnames: Vec<String>,
+    },
+}
+
+impl TlsSource {
+    pub fn from_env() -> anyhow::Result<Self> {
+        if std::env::var("REPL_TLS").is_ok_and(|value| value.eq_ignore_ascii_case("off")) {
+            return Ok(TlsSource::Disabled);
+        }
+
+        let hostnames: Vec<String> = std::env::var("REPL_TLS_HOSTNAMES")
+            .unwrap_or_else(|_| DEFAULT_HOSTNAMES.to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        match (std::env::var("REPL_TLS_CERT"), std::env::var("REPL_TLS_KEY")) {
+            (Ok(cert), Ok(key)) => {
+                let secs = std::env::var("REPL_TLS_RELOAD_SECS")
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&secs| secs > 0)
+                    .unwrap_or(DEFAULT_RELOAD_SECS);
+                Ok(TlsSource::Files {
+                    cert: cert.into(),
+                    key: key.into(),
+                    reload_interval: Duration::from_secs(secs),
+                })
+            }
+            (Err(_), Err(_)) => Ok(match std::env::var("REPL_TLS_CA_DIR") {
+                Ok(dir) => TlsSource::PersistedCa {
+                    dir: dir.into(),
+                    hostnames,
+                },
+                Err(_) => TlsSource::SelfSigned { hostnames },
+            }),
+            _ => anyhow::bail!("REPL_TLS_CERT and REPL_TLS_KEY must be set together"),
+        }
+    }
+}
+
+/// The server's TLS settings, once loaded
+pub struct ServerTls {
+    /// None when serving plain HTTP
+    pub config: Option<RustlsConfig>,
+    /// Root certificate of the persisted CA, for `GET /api/v1/repl/tls/ca.pem`
+    pub ca_pem: Option<String>,
+}
+
+impl ServerTls {
+    /// Load or generate the certificate; file-based certificates start being watched for changes
+    pub async fn load(source: &TlsSource) -> anyhow::Result<Self> {
+        match source {
+            TlsSource::Disabled => Ok(Self {
+                config: None,
+                ca_pem: None,
+            }),
+            TlsSource::Files {
+                cert,
+                key,
+                reload_interval,
+            } => {
+                let mut watcher = CertWatcher::new(cert.clone(), key.clone());
+                let config = RustlsConfig::from_config(watcher.load()?);
+                watcher.spawn(config.clone(), *reload_interval);
+                Ok(Self {
+                    config: Some(config),
+                    ca_pem: None,
+                })
+            }
+            TlsSource::PersistedCa { dir, hostnames } => {
+                let ca = CertificateAuthority::load_or_create(dir)?;
+                let (cert_pem, key_pem) = ca.issue(hostnames)?;
+                Ok(Self {
+                    config: Some(RustlsConfig::from_config(server_config(
+                        cert_pem.as_bytes(),
+                        key_pem.as_bytes(),
+                    )?)),
+                    ca_pem: Some(ca.cert_pem),
+                })
+            }
+            TlsSource::SelfSigned { hostnames } => {
+                let (cert_pem, key_pem) = make_cert(hostnames)?;
+                Ok(Self {
+                    config: Some(RustlsConfig::from_config(server_config(
+                        cert_pem.as_bytes(),
+                        key_pem.as_bytes(),
+                    )?)),
+                    ca_pem: None,
+                })
+            }
+        }
+    }
+}
+
+/// A throwaway self-signed certificate; clients cannot verify it
+pub fn make_cert(hostnames: &[String]) -> anyhow::Result<(String, String)> {
+    let cert_key_pair = rcgen::generate_simple_self_signed(hostnames.to_vec())
+        .context("failed to generate self signed certificate")?;
 
     let cert_pem = cert_key_pair.cert.pem();
     let key_pem = cert_key_pair.signing_key.serialize_pem();
-    (cert_pem, key_pem)
-}
\ No newline at end of file
+    Ok((cert_pem, key_pem))
+}
+
+/// Server config for a PEM certificate chain and private key
+fn server_config(cert_pem: &[u8], key_pem: &[u8]) -> anyhow::Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_slice_iter(cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse certificates")?;
+    anyhow::ensure!(!certs.is_empty(), "No certificate found");
+    let key = PrivateKeyDer::from_pem_slice(key_pem).context("Failed to parse private key")?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// A self-signed CA whose certificate and key live in a directory
+pub struct CertificateAuthority {
+    pub cert_pem: String,
+    key: KeyPair,
+}
+
+fn ca_params() -> anyhow::Result<CertificateParams> {
+    let mut params = CertificateParams::new(Vec::new())?;
+    params.distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    Ok(params)
+}
+
+impl CertificateAuthority {
+    /// Read the CA from `dir`, creating it on first use
+    pub fn load_or_create(dir: &Path) -> anyhow::Result<Self> {
+        let cert_path = dir.join(CA_CERT_FILE);
+        let key_path = dir.join(CA_KEY_FILE);
+
+        if cert_path.exists() && key_path.exists() {
+            let cert_pem = std::fs::read_to_string(&cert_path)
+                .with_context(|| format!("Failed to read {}", cert_path.display()))?;
+            let key_pem = std::fs::read_to_string(&key_path)
+                .with_context(|| format!("Failed to read {}", key_path.display()))?;
+            let key = KeyPair::from_pem(&key_pem).with_context(|| format!("Invalid CA key {}", key_path.display()))?;
+            return Ok(Self { cert_pem, key });
+        }
+
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let key = KeyPair::generate()?;
+        let cert_pem = ca_params()?.self_signed(&key)?.pem();
+        write_private(&key_path, key.serialize_pem().as_bytes())?;
+        std::fs::write(&cert_path, &cert_pem).with_context(|| format!("Failed to write {}", cert_path.display()))?;
+        tracing::info!("Created repl-api CA in {}", dir.display());
+        Ok(Self { cert_pem, key })
+    }
+
+    /// A server certificate for `hostnames`, signed by this CA
+    pub fn issue(&self, hostnames: &[String]) -> anyhow::Result<(String, String)> {
+        let issuer = Issuer::new(ca_params()?, &self.key);
+        let mut params = CertificateParams::new(hostnames.to_vec())?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, hostnames.first().map_or("repl-api", String::as_str));
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        let key = KeyPair::generate()?;
+        let cert = params.signed_by(&key, &issuer)?;
+        Ok((cert.pem(), key.serialize_pem()))
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    std::io::Write::write_all(&mut file, contents)?;
+    Ok(())
+}
+
+/// Reloads a certificate and key from disk when their modification times change
+struct CertWatcher {
+    cert: PathBuf,
+    key: PathBuf,
+    modified: Option<(SystemTime, SystemTime)>,
+}
+
+impl CertWatcher {
+    fn new(cert: PathBuf, key: PathBuf) -> Self {
+        Self {
+            cert,
+            key,
+            modified: None,
+        }
+    }
+
+    fn modified(&self) -> std::io::Result<(SystemTime, SystemTime)> {
+        Ok((
+            std::fs::metadata(&self.cert)?.modified()?,
+            std::fs::metadata(&self.key)?.modified()?,
+        ))
+    }
+
+    fn load(&mut self) -> anyhow::Result<Arc<ServerConfig>> {
+        let modified = self.modified().ok();
+        let cert = std::fs::read(&self.cert).with_context(|| format!("Failed to read {}", self.cert.display()))?;
+        let key = std::fs::read(&self.key).with_context(|| format!("Failed to read {}", self.key.display()))?;
+        let config = server_config(&cert, &key)
+            .with_context(|| format!("Invalid certificate {} or key {}", self.cert.display(), self.key.display()))?;
+        self.modified = modified;
+        Ok(config)
+    }
+
+    /// Swap in the files' certificate if they changed; a broken pair keeps the old one
+    fn reload_if_changed(&mut self, config: &RustlsConfig) -> bool {
+        if self.modified().ok() == self.modified {
+            return false;
+        }
+        match self.load() {
+            Ok(server_config) => {
+                config.reload_from_config(server_config);
+                tracing::info!("Reloaded TLS certificate from {}", self.cert.display());
+                true
+            }
+            Err(e) => {
+                // Retry on the next tick, e.g. after the key has been written too
+                tracing::warn!("Keeping the current TLS certificate: {:#}", e);
+                false
+            }
+        }
+    }
+
+    fn spawn(mut self, config: RustlsConfig, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.reload_if_changed(&config);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hostnames() -> Vec<String> {
+        vec!["localhost".to_string()]
+    }
+
+    /// Serve `config` on localhost and fetch a page with a client that trusts only `ca_pem`
+    async fn fetch_with_ca(config: RustlsConfig, ca_pem: &str) -> reqwest::Result<String> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "secure" }));
+        tokio::spawn(axum_server::from_tcp_rustls(listener, config).serve(app.into_make_service()));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(ca_pem.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+        client.get(format!("https://localhost:{}/", port)).send().await?.text().await
+    }
+
+    #[tokio::test]
+    async fn test_persisted_ca_is_reused_and_verifiable() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = CertificateAuthority::load_or_create(dir.path()).unwrap();
+        let reloaded = CertificateAuthority::load_or_create(dir.path()).unwrap();
+        assert_eq!(ca.cert_pem, reloaded.cert_pem);
+
+        // A certificate issued after a restart still chains to the exported root
+        let tls = ServerTls::load(&TlsSource::PersistedCa {
+            dir: dir.path().to_path_buf(),
+            hostnames: hostnames(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(tls.ca_pem.as_deref(), Some(ca.cert_pem.as_str()));
+        assert_eq!(fetch_with_ca(tls.config.unwrap(), &ca.cert_pem).await.unwrap(), "secure");
+    }
+
+    #[tokio::test]
+    async fn test_self_signed_certificate_is_not_trusted_by_other_ca() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = CertificateAuthority::load_or_create(dir.path()).unwrap();
+        let tls = ServerTls::load(&TlsSource::SelfSigned { hostnames: hostnames() }).await.unwrap();
+        assert!(tls.ca_pem.is_none());
+        assert!(fetch_with_ca(tls.config.unwrap(), &ca.cert_pem).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_certificate_is_reloaded_when_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        let old_ca = CertificateAuthority::load_or_create(&dir.path().join("old")).unwrap();
+        let new_ca = CertificateAuthority::load_or_create(&dir.path().join("new")).unwrap();
+
+        let (cert, key) = old_ca.issue(&hostnames()).unwrap();
+        std::fs::write(&cert_path, cert).unwrap();
+        std::fs::write(&key_path, key).unwrap();
+        let mut watcher = CertWatcher::new(cert_path.clone(), key_path.clone());
+        let config = RustlsConfig::from_config(watcher.load().unwrap());
+        assert!(!watcher.reload_if_changed(&config));
+
+        // A half-written pair is rejected and the current certificate kept
+        let (cert, key) = new_ca.issue(&hostnames()).unwrap();
+        std::fs::write(&cert_path, &cert).unwrap();
+        std::fs::write(&key_path, "not a key").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&cert_path).unwrap().set_modified(later).unwrap();
+        assert!(!watcher.reload_if_changed(&config));
+
+        std::fs::write(&key_path, key).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&key_path)
+            .unwrap()
+            .set_modified(later + Duration::from_secs(5))
+            .unwrap();
+        assert!(watcher.reload_if_changed(&config));
+        assert_eq!(fetch_with_ca(config, &new_ca.cert_pem).await.unwrap(), "secure");
+    }
+}
@@ -1,12 +1,13 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, LitStr, Token};
+use syn::{parse_macro_input, Expr, LitStr, Token};
 use syn::parse::{Parse, ParseStream};
 
 struct ServiceRegistrationArgs {
     name: LitStr,
     address: LitStr,
     port: syn::LitInt,
+    health_check: Option<Expr>,
 }
 
 impl Parse for ServiceRegistrationArgs {
@@ -17,23 +18,35 @@ impl Parse for ServiceRegistrationArgs {
         input.parse::<Token![,]>()?;
         let port: syn::LitInt = input.parse()?;
 
+        let health_check = if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
         Ok(ServiceRegistrationArgs {
             name,
             address,
             port,
+            health_check,
         })
     }
 }
 
 /// Macro to bootstrap service registration with etcd
 ///
+/// An optional fourth argument declares a `HealthCheck` that the registry
+/// will probe to keep the instance's status up to date.
+///
 /// # Example
 /// ```ignore
-/// use service_registry::register_service;
+/// use service_registry::{register_service, HealthCheck};
 ///
 /// #[tokio::main]
 /// async fn main() {
 ///     let (service, lease_id) = register_service!("my-service", "localhost", 8080).await;
+///     let (service, lease_id) =
+///         register_service!("my-service", "localhost", 8080, HealthCheck::http("/healthz")).await;
 /// }
 /// ```
 #[proc_macro]
@@ -44,8 +57,13 @@ pub fn register_service(input: TokenStream) -> TokenStream {
     let address = args.address;
     let port = args.port;
 
-    let expanded = quote! {
-        service_registry::bootstrap_service(#name, #address, #port)
+    let expanded = match args.health_check {
+        Some(health_check) => quote! {
+            service_registry::bootstrap_service_with_health_check(#name, #address, #port, #health_check)
+        },
+        None => quote! {
+            service_registry::bootstrap_service(#name, #address, #port)
+        },
     };
 
     TokenStream::from(expanded)
@@ -1,35 +1,96 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    Json,
+    middleware,
+    routing::get,
+    Extension, Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
 
-use crate::{ServiceRegistry, ServiceInfo};
+use crate::auth::{self, check_scope, TokenScope, TokenStore};
+use crate::openapi::{self, Defaults};
+use crate::{metrics, telemetry, versioning};
+use crate::snapshot::{ImportSummary, RegistrySnapshot, SnapshotDiff};
+use crate::{RegistryError, ServiceRegistry, ServiceInfo, ServiceSelector, ServiceStatus};
+use tower_http::trace::TraceLayer;
 
 type AppState = Arc<Mutex<ServiceRegistry>>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Service registration and discovery, backed by etcd"),
+    modifiers(&Defaults),
+    tags(
+        (name = "registry", description = "Registering and discovering instances"),
+        (name = "registry-admin", description = "Operator endpoints; require an admin token, and are disabled when no tokens are configured"),
+    )
+)]
+struct ApiDoc;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub service: ServiceInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RegisterResponse {
     pub lease_id: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// Replace keys that already exist instead of skipping them
+    #[serde(default)]
+    pub overwrite: bool,
+    /// Import entries under one new lease that lapses after the registry's
+    /// TTL unless kept alive; otherwise they are static and never expire
+    #[serde(default)]
+    pub leased: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DiffRequest {
+    pub before: RegistrySnapshot,
+    /// Compared against the live registry when omitted
+    #[serde(default)]
+    pub after: Option<RegistrySnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct KeepAliveRequest {
     pub lease_id: i64,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetStatusRequest {
+    pub status: ServiceStatus,
+}
+
+/// Register an instance under a new lease
+#[utoipa::path(
+    post,
+    path = "/registry/register",
+    tag = "registry",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Registered", body = RegisterResponse),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "The token may not register this service"),
+    )
+)]
 pub async fn register(
     State(registry): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<Json<RegisterResponse>, StatusCode> {
+    check_scope(scope.as_deref(), &req.service.name)?;
+
     let mut registry = registry.lock().await;
 
     match registry.register(&req.service).await {
@@ -44,10 +105,24 @@ pub async fn register(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/registry/deregister",
+    tag = "registry",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Deregistered"),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "The token may not deregister this service"),
+    )
+)]
 pub async fn deregister(
     State(registry): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
     Json(service): Json<ServiceInfo>,
 ) -> Result<StatusCode, StatusCode> {
+    check_scope(scope.as_deref(), &service.name)?;
+
     let mut registry = registry.lock().await;
 
     match registry.deregister(&service).await {
@@ -62,6 +137,12 @@ pub async fn deregister(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/registry/services",
+    tag = "registry",
+    responses((status = OK, description = "Every registered instance", body = Vec<ServiceInfo>))
+)]
 pub async fn list_services(
     State(registry): State<AppState>,
 ) -> Result<Json<Vec<ServiceInfo>>, StatusCode> {
@@ -76,32 +157,384 @@ pub async fn list_services(
     }
 }
 
+/// Instances of a service that match a selector
+///
+/// Query parameters are parsed as a [`ServiceSelector`], e.g.
+/// `?version=^0.2&meta.zone=us-east1&include_unhealthy=true`
+#[utoipa::path(
+    get,
+    path = "/registry/services/{name}",
+    tag = "registry",
+    params(
+        ("version" = Option<String>, Query, description = "Semver requirement, e.g. `^0.2`"),
+        ("include_unhealthy" = Option<bool>, Query, description = "Also return instances that are not healthy"),
+        ("meta.{key}" = Option<String>, Query, description = "Required metadata value, e.g. `meta.zone=us-east1`"),
+    ),
+    responses(
+        (status = OK, description = "Matching instances", body = Vec<ServiceInfo>),
+        (status = BAD_REQUEST, description = "Invalid selector", body = String),
+    )
+)]
 pub async fn get_services_by_name(
     State(registry): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Vec<ServiceInfo>>, StatusCode> {
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<Json<Vec<ServiceInfo>>, (StatusCode, String)> {
+    let selector = ServiceSelector::from_query_pairs(params)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
     let mut registry = registry.lock().await;
 
-    match registry.get_services(&name).await {
+    match registry.find_services(&name, &selector).await {
         Ok(services) => Ok(Json(services)),
         Err(e) => {
             tracing::error!("Failed to get services by name {}: {}", name, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/registry/keepalive",
+    tag = "registry",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Lease renewed"),
+        (status = NOT_FOUND, description = "The lease has expired"),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "The token may not renew a service under this lease"),
+    )
+)]
 pub async fn keep_alive(
     State(registry): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
     Json(req): Json<KeepAliveRequest>,
 ) -> Result<StatusCode, StatusCode> {
     let mut registry = registry.lock().await;
 
+    // Only the owner of every service under the lease may keep it alive
+    if scope.is_some() {
+        let services = registry.lease_services(req.lease_id).await.map_err(|e| {
+            tracing::error!("Failed to look up services of lease {}: {}", req.lease_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        for service in &services {
+            check_scope(scope.as_deref(), service)?;
+        }
+    }
+
     match registry.keep_alive(req.lease_id).await {
         Ok(_) => Ok(StatusCode::OK),
+        Err(RegistryError::LeaseExpired(lease_id)) => {
+            tracing::warn!("Keepalive for expired lease {}", lease_id);
+            Err(StatusCode::NOT_FOUND)
+        }
         Err(e) => {
             tracing::error!("Failed to keep alive lease {}: {}", req.lease_id, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Override an instance's status, e.g. to take it out of discovery while it is unhealthy
+#[utoipa::path(
+    put,
+    path = "/registry/admin/services/{name}/{id}/status",
+    tag = "registry-admin",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "The updated instance", body = ServiceInfo),
+        (status = NOT_FOUND, description = "No such instance"),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "Admin token required"),
+        (status = SERVICE_UNAVAILABLE, description = "No registry tokens are configured"),
+    )
+)]
+pub async fn set_service_status(
+    State(registry): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<SetStatusRequest>,
+) -> Result<Json<ServiceInfo>, StatusCode> {
+    let mut registry = registry.lock().await;
+
+    match registry.set_status(&name, &id, req.status).await {
+        Ok(service) => {
+            tracing::info!("Set status of {}/{} to {:?}", name, id, req.status);
+            Ok(Json(service))
+        }
+        Err(RegistryError::ServiceNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to set status of {}/{}: {}", name, id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Remove an instance regardless of its lease, for entries whose owner is gone
+#[utoipa::path(
+    delete,
+    path = "/registry/admin/services/{name}/{id}",
+    tag = "registry-admin",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Deregistered"),
+        (status = NOT_FOUND, description = "No such instance"),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "Admin token required"),
+        (status = SERVICE_UNAVAILABLE, description = "No registry tokens are configured"),
+    )
+)]
+pub async fn force_deregister(
+    State(registry): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let mut registry = registry.lock().await;
+
+    let service = match registry.get_service(&name, &id).await {
+        Ok(service) => service,
+        Err(RegistryError::ServiceNotFound(_)) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to look up {}/{}: {}", name, id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match registry.deregister(&service).await {
+        Ok(_) => {
+            tracing::warn!("Force-deregistered service: {}/{}", name, id);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            tracing::error!("Failed to force-deregister {}/{}: {}", name, id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/registry/admin/snapshot",
+    tag = "registry-admin",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Everything under `/services/`", body = RegistrySnapshot),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "Admin token required"),
+        (status = SERVICE_UNAVAILABLE, description = "No registry tokens are configured"),
+    )
+)]
+pub async fn export_snapshot(
+    State(registry): State<AppState>,
+) -> Result<Json<RegistrySnapshot>, StatusCode> {
+    let mut registry = registry.lock().await;
+
+    match registry.export_snapshot().await {
+        Ok(snapshot) => Ok(Json(snapshot)),
+        Err(e) => {
+            tracing::error!("Failed to export registry snapshot: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/registry/admin/import",
+    tag = "registry-admin",
+    params(ImportQuery),
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Imported and skipped keys", body = ImportSummary),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "Admin token required"),
+        (status = SERVICE_UNAVAILABLE, description = "No registry tokens are configured"),
+    )
+)]
+pub async fn import_snapshot(
+    State(registry): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    Json(snapshot): Json<RegistrySnapshot>,
+) -> Result<Json<ImportSummary>, StatusCode> {
+    let mut registry = registry.lock().await;
+
+    match registry.import_snapshot(&snapshot, query.overwrite, query.leased).await {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => {
+            tracing::error!("Failed to import registry snapshot: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/registry/admin/diff",
+    tag = "registry-admin",
+    security(("bearer" = [])),
+    responses(
+        (status = OK, description = "Differences from `before` to `after`", body = SnapshotDiff),
+        (status = UNAUTHORIZED, description = "Missing or invalid registration token"),
+        (status = FORBIDDEN, description = "Admin token required"),
+        (status = SERVICE_UNAVAILABLE, description = "No registry tokens are configured"),
+    )
+)]
+pub async fn diff_snapshots(
+    State(registry): State<AppState>,
+    Json(req): Json<DiffRequest>,
+) -> Result<Json<SnapshotDiff>, StatusCode> {
+    let after = match req.after {
+        Some(after) => after,
+        None => {
+            let mut registry = registry.lock().await;
+            registry.export_snapshot().await.map_err(|e| {
+                tracing::error!("Failed to export registry snapshot for diff: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+        }
+    };
+
+    Ok(Json(req.before.diff(&after)))
+}
+
+/// Build the registry HTTP API
+///
+/// Mutating endpoints require a registration token when `tokens` is
+/// configured. The admin endpoints require an admin token, and answer 503
+/// when `tokens` is None.
+pub fn router(registry: AppState, tokens: Option<TokenStore>) -> Router {
+    routes(registry, tokens)
+        .merge(openapi::routes(openapi()))
+        .merge(versioning::routes("service-registry", env!("CARGO_PKG_VERSION")))
+        .route("/health", get(|| async { "OK" }))
+        .route("/metrics", get(metrics::handler))
+        .layer(middleware::from_fn_with_state("service-registry", metrics::track))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span))
+}
+
+/// The `/api/v1/registry` routes and their deprecated `/api/registry` aliases
+/// alone, for mounting next to other services
+pub fn routes(registry: AppState, tokens: Option<TokenStore>) -> Router {
+    let (router, _) = api(tokens).split_for_parts();
+    versioning::versioned(router).with_state(registry)
+}
+
+/// OpenAPI document of the `/api/v1/registry` routes
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    versioning::document(api(None).into_openapi())
+}
+
+fn api(tokens: Option<TokenStore>) -> OpenApiRouter<AppState> {
+    let mut mutating = OpenApiRouter::new()
+        .routes(routes!(register))
+        .routes(routes!(deregister))
+        .routes(routes!(keep_alive));
+    let mut admin = OpenApiRouter::new()
+        .routes(routes!(export_snapshot))
+        .routes(routes!(import_snapshot))
+        .routes(routes!(diff_snapshots))
+        .routes(routes!(force_deregister))
+        .routes(routes!(set_service_status));
+    match tokens {
+        Some(tokens) => {
+            mutating = mutating.route_layer(middleware::from_fn_with_state(tokens.clone(), auth::require_token));
+            admin = admin.route_layer(middleware::from_fn_with_state(tokens, auth::require_admin_token));
+        }
+        None => admin = admin.route_layer(middleware::from_fn(auth::admin_disabled)),
+    }
+
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(list_services))
+        .routes(routes!(get_services_by_name))
+        .merge(mutating)
+        .merge(admin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::RegistrationToken;
+    use axum::body::Body;
+    use axum::http::{header, Request};
+    use tower::ServiceExt;
+
+    async fn status(app: &Router, method: &str, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = if method == "GET" { Body::empty() } else { Body::from(r#"{"taken_at":0,"entries":[]}"#) };
+        app.clone().oneshot(request.body(body).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_fail_closed_without_tokens() {
+        let registry = Arc::new(Mutex::new(ServiceRegistry::in_memory(None)));
+        let app = routes(registry, None);
+
+        assert_eq!(status(&app, "GET", "/api/v1/registry/services", None).await, StatusCode::OK);
+        for (method, uri) in [
+            ("GET", "/api/v1/registry/admin/snapshot"),
+            ("POST", "/api/v1/registry/admin/import"),
+            ("DELETE", "/api/v1/registry/admin/services/repl-api/a"),
+        ] {
+            assert_eq!(status(&app, method, uri, None).await, StatusCode::SERVICE_UNAVAILABLE, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_is_scoped_to_the_lease_owner() {
+        let registry = Arc::new(Mutex::new(ServiceRegistry::in_memory(Some(30))));
+        let lease_id = registry
+            .lock()
+            .await
+            .register(&ServiceInfo::new("coreos", "a", "127.0.0.1", 8085))
+            .await
+            .unwrap();
+        let tokens = TokenStore::new(vec![
+            RegistrationToken { token: "repl".to_string(), services: vec!["repl-api".to_string()] },
+            RegistrationToken { token: "coreos".to_string(), services: vec!["coreos".to_string()] },
+        ]);
+        let app = routes(registry, Some(tokens));
+        let keepalive = |token: &'static str| {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/api/v1/registry/keepalive")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::from(format!(r#"{{"lease_id":{}}}"#, lease_id)))
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(keepalive("repl").await, StatusCode::FORBIDDEN);
+        assert_eq!(keepalive("coreos").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_require_admin_token() {
+        let registry = Arc::new(Mutex::new(ServiceRegistry::in_memory(None)));
+        let tokens = TokenStore::new(vec![
+            RegistrationToken { token: "svc".to_string(), services: vec!["repl-api".to_string()] },
+            RegistrationToken { token: "ops".to_string(), services: vec!["*".to_string()] },
+        ]);
+        let app = routes(registry, Some(tokens));
+        let uri = "/api/v1/registry/admin/snapshot";
+
+        assert_eq!(status(&app, "GET", uri, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "GET", uri, Some("svc")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(&app, "GET", uri, Some("ops")).await, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_openapi_matches_published() {
+        let published = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if let Err(e) = openapi::check_published(&openapi(), published) {
+            panic!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Scope wildcard that allows a token to register any service name
+const ANY_SERVICE: &str = "*";
+
+/// A registration token and the service names it may register
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationToken {
+    pub token: String,
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenFile {
+    tokens: Vec<RegistrationToken>,
+}
+
+/// The service names the caller's token was issued for
+///
+/// Inserted into request extensions by [`require_token`].
+#[derive(Debug, Clone)]
+pub struct TokenScope {
+    services: Vec<String>,
+}
+
+impl TokenScope {
+    pub fn allows(&self, service_name: &str) -> bool {
+        self.services
+            .iter()
+            .any(|s| s == ANY_SERVICE || s == service_name)
+    }
+
+    /// Tokens scoped to `*` may also use the admin endpoints
+    pub fn is_admin(&self) -> bool {
+        self.services.iter().any(|s| s == ANY_SERVICE)
+    }
+}
+
+/// Registration tokens accepted by the mutating registry endpoints
+#[derive(Debug, Clone, Default)]
+pub struct TokenStore {
+    tokens: Arc<HashMap<String, TokenScope>>,
+}
+
+impl TokenStore {
+    pub fn new(tokens: Vec<RegistrationToken>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|t| (t.token, TokenScope { services: t.services }))
+            .collect();
+
+        Self {
+            tokens: Arc::new(tokens),
+        }
+    }
+
+    /// Load tokens from the environment
+    ///
+    /// - `REGISTRY_TOKENS_FILE`: path to a JSON file `{"tokens": [{"token": "...", "services": ["repl-api"]}]}`
+    /// - `REGISTRY_TOKENS`: inline `token=svc-a|svc-b;other=*`
+    ///
+    /// Returns None when neither is set, which leaves the registry open.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let mut tokens = Vec::new();
+
+        if let Ok(path) = std::env::var("REGISTRY_TOKENS_FILE") {
+            let contents = std::fs::read_to_string(&path)?;
+            let file: TokenFile = serde_json::from_str(&contents)?;
+            tokens.extend(file.tokens);
+        }
+
+        if let Ok(inline) = std::env::var("REGISTRY_TOKENS") {
+            tokens.extend(parse_inline_tokens(&inline)?);
+        }
+
+        if tokens.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Self::new(tokens)))
+        }
+    }
+
+    pub fn scope(&self, token: &str) -> Option<&TokenScope> {
+        self.tokens.get(token)
+    }
+}
+
+fn parse_inline_tokens(inline: &str) -> anyhow::Result<Vec<RegistrationToken>> {
+    inline
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (token, services) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("expected token=services, got '{}'", entry))?;
+
+            Ok(RegistrationToken {
+                token: token.trim().to_string(),
+                services: services
+                    .split('|')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+fn bearer_scope(store: &TokenStore, request: &Request) -> Option<TokenScope> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| store.scope(token.trim()))
+        .cloned()
+}
+
+/// Middleware that rejects requests without a valid `Authorization: Bearer` token
+pub async fn require_token(State(store): State<TokenStore>, mut request: Request, next: Next) -> Response {
+    let Some(scope) = bearer_scope(&store, &request) else {
+        tracing::warn!("Rejected registry request to {} without a valid token", request.uri());
+        return (StatusCode::UNAUTHORIZED, "missing or invalid registration token").into_response();
+    };
+
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
+
+/// Middleware that only lets through tokens scoped to `*`
+pub async fn require_admin_token(State(store): State<TokenStore>, request: Request, next: Next) -> Response {
+    match bearer_scope(&store, &request) {
+        Some(scope) if scope.is_admin() => next.run(request).await,
+        Some(_) => {
+            tracing::warn!("Rejected non-admin token for {}", request.uri());
+            (StatusCode::FORBIDDEN, "admin token required").into_response()
+        }
+        None => {
+            tracing::warn!("Rejected admin request to {} without a valid token", request.uri());
+            (StatusCode::UNAUTHORIZED, "missing or invalid registration token").into_response()
+        }
+    }
+}
+
+/// Middleware for the admin routes when no tokens are configured
+///
+/// Registration stays open without tokens, but the admin endpoints fail
+/// closed rather than let anyone rewrite the registry.
+pub async fn admin_disabled(request: Request, _next: Next) -> Response {
+    tracing::warn!("Rejected admin request to {}; no registry tokens are configured", request.uri());
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "admin endpoints are disabled; configure an admin (*) registry token",
+    )
+        .into_response()
+}
+
+/// Check the caller's token scope, if auth is enabled, against a service name
+pub fn check_scope(scope: Option<&TokenScope>, service_name: &str) -> Result<(), StatusCode> {
+    match scope {
+        Some(scope) if !scope.allows(service_name) => {
+            tracing::warn!("Token is not scoped for service {}", service_name);
+            Err(StatusCode::FORBIDDEN)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::post, Extension, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_parse_inline_tokens() {
+        let tokens = parse_inline_tokens("abc=repl-api|container-api; admin=*").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token, "abc");
+        assert_eq!(tokens[0].services, vec!["repl-api", "container-api"]);
+        assert_eq!(tokens[1].services, vec!["*"]);
+
+        assert!(parse_inline_tokens("no-separator").is_err());
+    }
+
+    #[test]
+    fn test_scope_allows() {
+        let store = TokenStore::new(parse_inline_tokens("abc=repl-api;admin=*").unwrap());
+
+        let scoped = store.scope("abc").unwrap();
+        assert!(scoped.allows("repl-api"));
+        assert!(!scoped.allows("coreos"));
+
+        let admin = store.scope("admin").unwrap();
+        assert!(admin.allows("coreos"));
+        assert!(admin.is_admin());
+        assert!(!scoped.is_admin());
+
+        assert!(store.scope("unknown").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_require_token_middleware() {
+        let store = TokenStore::new(parse_inline_tokens("abc=repl-api").unwrap());
+        let app = Router::new()
+            .route(
+                "/register",
+                post(|Extension(scope): Extension<TokenScope>| async move {
+                    check_scope(Some(&scope), "coreos")
+                }),
+            )
+            .layer(middleware::from_fn_with_state(store, require_token));
+
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().method("POST").uri("/register");
+            if let Some(auth) = auth {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.clone().oneshot(request(Some("Bearer nope"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(request(Some("Bearer abc"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
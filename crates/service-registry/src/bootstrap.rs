@@ -1,11 +1,82 @@
-use crate::{ServiceInfo, ServiceStatus};
+use crate::telemetry;
+use crate::{HealthCheck, ServiceInfo, ServiceSelector, ServiceStatus};
+use anyhow::Context;
 use std::env;
+use std::path::PathBuf;
 use tracing::{debug, warn};
 
+/// Connection settings for talking to service-registry
+///
+/// Read from the environment by [`RegistryClientConfig::from_env`]:
+/// - `SERVICE_REGISTRY_URL`: base URL (defaults to http://service-registry:3003)
+/// - `SERVICE_REGISTRY_TOKEN`: registration token sent as `Authorization: Bearer`
+/// - `SERVICE_REGISTRY_CA_CERT`: PEM CA used to verify the registry's certificate
+/// - `SERVICE_REGISTRY_CLIENT_CERT` / `SERVICE_REGISTRY_CLIENT_KEY`: PEM client
+///   certificate and key for mutual TLS
+#[derive(Debug, Clone)]
+pub struct RegistryClientConfig {
+    pub url: String,
+    pub token: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl RegistryClientConfig {
+    pub fn from_env() -> Self {
+        Self {
+            url: env::var("SERVICE_REGISTRY_URL")
+                .unwrap_or_else(|_| "http://service-registry:3003".to_string()),
+            token: env::var("SERVICE_REGISTRY_TOKEN").ok(),
+            ca_cert: env::var("SERVICE_REGISTRY_CA_CERT").ok().map(PathBuf::from),
+            client_cert: env::var("SERVICE_REGISTRY_CLIENT_CERT").ok().map(PathBuf::from),
+            client_key: env::var("SERVICE_REGISTRY_CLIENT_KEY").ok().map(PathBuf::from),
+        }
+    }
+
+    /// Build an HTTP client carrying the configured CA, client identity and token
+    pub fn build_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(ca_cert) = &self.ca_cert {
+            let pem = std::fs::read(ca_cert)
+                .with_context(|| format!("Failed to read registry CA {}", ca_cert.display()))?;
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let mut pem = std::fs::read(cert)
+                    .with_context(|| format!("Failed to read client cert {}", cert.display()))?;
+                pem.extend(
+                    std::fs::read(key)
+                        .with_context(|| format!("Failed to read client key {}", key.display()))?,
+                );
+                builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+            }
+            (None, None) => {}
+            _ => anyhow::bail!(
+                "SERVICE_REGISTRY_CLIENT_CERT and SERVICE_REGISTRY_CLIENT_KEY must be set together"
+            ),
+        }
+
+        if let Some(token) = &self.token {
+            let mut headers = reqwest::header::HeaderMap::new();
+            let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+                .context("Invalid registry token")?;
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+            builder = builder.default_headers(headers);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
 /// Bootstrap a service with automatic registration via service-registry HTTP API
 ///
 /// This function:
-/// - Reads SERVICE_REGISTRY_URL from environment (defaults to http://service-registry:3003)
+/// - Reads the registry URL, token and TLS settings from the environment (see [`RegistryClientConfig`])
 /// - Generates a unique service ID from hostname and PID
 /// - Registers the service via HTTP
 /// - Spawns a background task to keep the lease alive
@@ -16,10 +87,27 @@ pub async fn bootstrap_service(
     address: impl Into<String>,
     port: u16,
 ) -> (ServiceInfo, i64) {
-    // Get service registry URL from environment
-    let registry_url = env::var("SERVICE_REGISTRY_URL")
-        .unwrap_or_else(|_| "http://service-registry:3003".to_string());
+    register_and_keep_alive(local_instance(service_name, address, port)).await
+}
+
+/// Same as [`bootstrap_service`], but declares a health check that the
+/// registry uses to drive the instance's status
+pub async fn bootstrap_service_with_health_check(
+    service_name: impl Into<String>,
+    address: impl Into<String>,
+    port: u16,
+    health_check: HealthCheck,
+) -> (ServiceInfo, i64) {
+    let service = local_instance(service_name, address, port).with_health_check(health_check);
+    register_and_keep_alive(service).await
+}
 
+/// Build the ServiceInfo for this process, using hostname and PID as the ID
+fn local_instance(
+    service_name: impl Into<String>,
+    address: impl Into<String>,
+    port: u16,
+) -> ServiceInfo {
     // Create service ID from hostname and PID
     let hostname = hostname::get()
         .unwrap_or_else(|_| std::ffi::OsString::from("unknown"))
@@ -28,18 +116,23 @@ pub async fn bootstrap_service(
     let pid = std::process::id();
     let service_id = format!("{}-{}", hostname, pid);
 
-    // Create service info
-    let service = ServiceInfo::new(
+    ServiceInfo::new(
         service_name,
         service_id,
         address,
         port
     )
-    .with_status(ServiceStatus::Healthy);
+    .with_status(ServiceStatus::Healthy)
+}
 
-    // Register service via HTTP with retry logic
-    let client = reqwest::Client::new();
+async fn register_and_keep_alive(service: ServiceInfo) -> (ServiceInfo, i64) {
+    let config = RegistryClientConfig::from_env();
+    let registry_url = config.url.clone();
+    let client = config
+        .build_client()
+        .expect("Failed to build service registry client");
 
+    // Register service via HTTP with retry logic
     #[derive(serde::Deserialize)]
     struct RegisterResponse {
         lease_id: i64,
@@ -50,13 +143,18 @@ pub async fn bootstrap_service(
     let lease_id = loop {
         attempts += 1;
 
-        match client
-            .post(format!("{}/api/registry/register", registry_url))
+        match telemetry::inject(client.post(format!("{}/api/v1/registry/register", registry_url)))
             .json(&serde_json::json!({ "service": service }))
             .send()
             .await
         {
             Ok(response) => {
+                let status = response.status();
+                if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+                    // Retrying with the same credentials will not help
+                    panic!("Service registry rejected registration of {}: {}", service.name, status);
+                }
+
                 match response.json::<RegisterResponse>().await {
                     Ok(register_response) => {
                         tracing::info!("Service registered with lease ID: {}", register_response.lease_id);
@@ -85,19 +183,26 @@ pub async fn bootstrap_service(
 
     // Keep-alive task
     let registry_url_clone = registry_url.clone();
+    let service_name = service.name.clone();
     tokio::spawn(async move {
-        let client = reqwest::Client::new();
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-            let result = client
-                .post(format!("{}/api/registry/keepalive", registry_url_clone))
+            let result = telemetry::inject(client.post(format!("{}/api/v1/registry/keepalive", registry_url_clone)))
                 .json(&serde_json::json!({ "lease_id": lease_id }))
                 .send()
                 .await;
 
-            if let Err(e) = result {
-                tracing::error!("Failed to keep lease alive: {}", e);
-                break;
+            match result {
+                Ok(response) if !response.status().is_success() => {
+                    crate::metrics::lease_renew_failed(&service_name);
+                    tracing::warn!("Lease keep-alive rejected: {}", response.status());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    crate::metrics::lease_renew_failed(&service_name);
+                    tracing::error!("Failed to keep lease alive: {}", e);
+                    break;
+                }
             }
         }
     });
@@ -112,31 +217,59 @@ pub async fn bootstrap_service(
 ///
 /// Returns None if the service is not found or if there's an error.
 pub async fn get_service_endpoint(service_name: &str) -> Option<String> {
-    let registry_url = env::var("SERVICE_REGISTRY_URL")
-        .unwrap_or_else(|_| "http://service-registry:3003".to_string());
+    get_service_endpoint_matching(service_name, &ServiceSelector::default()).await
+}
+
+/// Get the endpoint URL for an instance of a service that matches `selector`
+///
+/// Useful for canary rollouts, e.g. only routing to `version=^0.2` instances
+/// in a given zone.
+pub async fn get_service_endpoint_matching(
+    service_name: &str,
+    selector: &ServiceSelector,
+) -> Option<String> {
+    let services = discover_services(service_name, selector).await?;
+
+    if let Some(service) = services.first() {
+        let endpoint = format!("http://{}:{}", service.address, service.port);
+        debug!("Found service {} at {}", service_name, endpoint);
+        Some(endpoint)
+    } else {
+        warn!("No instances found for service: {}", service_name);
+        None
+    }
+}
+
+/// Get every instance of a service that matches `selector`
+///
+/// Returns None if the registry could not be queried, and an empty list if it
+/// has no matching instances.
+#[tracing::instrument(name = "discover", skip(selector))]
+pub async fn discover_services(
+    service_name: &str,
+    selector: &ServiceSelector,
+) -> Option<Vec<ServiceInfo>> {
+    let config = RegistryClientConfig::from_env();
 
-    debug!("Looking up service: {}", service_name);
+    debug!("Looking up service: {} ({:?})", service_name, selector);
 
-    let client = reqwest::Client::new();
+    let client = match config.build_client() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to build service registry client: {}", e);
+            return None;
+        }
+    };
 
-    match client
-        .get(format!("{}/api/registry/services/{}", registry_url, service_name))
+    match telemetry::inject(client.get(format!("{}/api/v1/registry/services/{}", config.url, service_name)))
+        .query(&selector.to_query_pairs())
         .send()
         .await
     {
         Ok(response) => {
             if response.status().is_success() {
                 match response.json::<Vec<ServiceInfo>>().await {
-                    Ok(services) => {
-                        if let Some(service) = services.first() {
-                            let endpoint = format!("http://{}:{}", service.address, service.port);
-                            debug!("Found service {} at {}", service_name, endpoint);
-                            Some(endpoint)
-                        } else {
-                            warn!("No instances found for service: {}", service_name);
-                            None
-                        }
-                    }
+                    Ok(services) => Some(services),
                     Err(e) => {
                         warn!("Failed to parse services response: {}", e);
                         None
@@ -152,4 +285,4 @@ pub async fn get_service_endpoint(service_name: &str) -> Option<String> {
             None
         }
     }
-}
\ No newline at end of file
+}
@@ -14,8 +14,14 @@ pub enum RegistryError {
     #[error("invalid service data: {0}")]
     InvalidServiceData(String),
 
+    #[error("lease expired: {0}")]
+    LeaseExpired(i64),
+
     #[error("connection error: {0}")]
     ConnectionError(String),
+
+    #[error("not supported: {0}")]
+    Unsupported(&'static str),
 }
 
 pub type Result<T> = std::result::Result<T, RegistryError>;
\ No newline at end of file
@@ -0,0 +1,278 @@
+use crate::service::{HealthCheck, HealthCheckKind, ServiceInfo, ServiceStatus};
+use crate::ServiceRegistry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+/// How often the checker wakes up to look for probes that are due
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Consecutive probe results for a single service instance
+#[derive(Debug, Default)]
+struct CheckState {
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    last_checked: Option<Instant>,
+}
+
+impl CheckState {
+    fn is_due(&self, check: &HealthCheck, now: Instant) -> bool {
+        match self.last_checked {
+            Some(last) => now.duration_since(last) >= Duration::from_secs(check.interval_secs),
+            None => true,
+        }
+    }
+
+    /// Record a probe result and return the new status if a threshold was crossed
+    fn record(&mut self, healthy: bool, check: &HealthCheck, current: ServiceStatus) -> Option<ServiceStatus> {
+        if healthy {
+            self.consecutive_successes += 1;
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures += 1;
+            self.consecutive_successes = 0;
+        }
+
+        // A stopping service is draining on purpose; leave it alone
+        if current == ServiceStatus::Stopping {
+            return None;
+        }
+
+        if healthy
+            && current != ServiceStatus::Healthy
+            && self.consecutive_successes >= check.success_threshold
+        {
+            Some(ServiceStatus::Healthy)
+        } else if !healthy
+            && current != ServiceStatus::Unhealthy
+            && self.consecutive_failures >= check.failure_threshold
+        {
+            Some(ServiceStatus::Unhealthy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Probe a service instance once using its declared health check
+pub async fn probe(
+    client: &reqwest::Client,
+    insecure_client: &reqwest::Client,
+    check: &HealthCheck,
+    address: &str,
+    port: u16,
+) -> bool {
+    let timeout = Duration::from_secs(check.timeout_secs);
+
+    match &check.kind {
+        HealthCheckKind::Http { path } => {
+            let url = format!("http://{}:{}{}", address, port, path);
+            probe_http(client, &url, timeout).await
+        }
+        HealthCheckKind::Https { path, insecure } => {
+            let url = format!("https://{}:{}{}", address, port, path);
+            let client = if *insecure { insecure_client } else { client };
+            probe_http(client, &url, timeout).await
+        }
+        HealthCheckKind::Tcp => {
+            let connect = tokio::net::TcpStream::connect((address, port));
+            match tokio::time::timeout(timeout, connect).await {
+                Ok(Ok(_)) => true,
+                Ok(Err(e)) => {
+                    debug!("TCP probe to {}:{} failed: {}", address, port, e);
+                    false
+                }
+                Err(_) => {
+                    debug!("TCP probe to {}:{} timed out", address, port);
+                    false
+                }
+            }
+        }
+    }
+}
+
+async fn probe_http(client: &reqwest::Client, url: &str, timeout: Duration) -> bool {
+    match client.get(url).timeout(timeout).send().await {
+        Ok(resp) => resp.status().is_success(),
+        Err(e) => {
+            debug!("HTTP probe to {} failed: {}", url, e);
+            false
+        }
+    }
+}
+
+/// Periodically probes every registered service that declares a health check
+/// and flips its status once the configured thresholds are crossed
+pub struct HealthChecker {
+    registry: Arc<Mutex<ServiceRegistry>>,
+    client: reqwest::Client,
+    insecure_client: reqwest::Client,
+    states: HashMap<String, CheckState>,
+}
+
+impl HealthChecker {
+    pub fn new(registry: Arc<Mutex<ServiceRegistry>>) -> Self {
+        let insecure_client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .expect("failed building HTTP client");
+
+        Self {
+            registry,
+            client: reqwest::Client::new(),
+            insecure_client,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Run the checker in a background task
+    pub fn spawn(mut self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.run_once().await;
+            }
+        })
+    }
+
+    async fn run_once(&mut self) {
+        let services = {
+            let mut registry = self.registry.lock().await;
+            match registry.get_all_services().await {
+                Ok(services) => services,
+                Err(e) => {
+                    warn!("Health checker failed to list services: {}", e);
+                    return;
+                }
+            }
+        };
+
+        // Forget instances that have been deregistered or expired
+        self.states
+            .retain(|key, _| services.iter().any(|s| &s.service_key() == key));
+
+        let now = Instant::now();
+        let mut probes = JoinSet::new();
+        for service in services {
+            let Some(check) = service.health_check.clone() else {
+                continue;
+            };
+
+            let state = self.states.entry(service.service_key()).or_default();
+            if !state.is_due(&check, now) {
+                continue;
+            }
+            state.last_checked = Some(now);
+
+            let client = self.client.clone();
+            let insecure_client = self.insecure_client.clone();
+            probes.spawn(async move {
+                let healthy =
+                    probe(&client, &insecure_client, &check, &service.address, service.port).await;
+                (service, check, healthy)
+            });
+        }
+
+        while let Some(result) = probes.join_next().await {
+            let Ok((service, check, healthy)) = result else {
+                continue;
+            };
+
+            let state = self.states.entry(service.service_key()).or_default();
+            let Some(new_status) = state.record(healthy, &check, service.status) else {
+                continue;
+            };
+
+            info!(
+                "Service {} ({}) changed status {:?} -> {:?}",
+                service.name, service.id, service.status, new_status
+            );
+
+            let mut registry = self.registry.lock().await;
+            if let Err(e) = registry.set_status(&service.name, &service.id, new_status).await {
+                warn!("Failed to update status of {} ({}): {}", service.name, service.id, e);
+            }
+        }
+    }
+}
+
+/// Whether an instance should be handed out by default discovery queries
+pub fn is_routable(service: &ServiceInfo) -> bool {
+    service.status != ServiceStatus::Unhealthy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_flip_to_unhealthy_after_threshold() {
+        let check = HealthCheck::tcp().with_thresholds(3, 1);
+        let mut state = CheckState::default();
+
+        assert_eq!(state.record(false, &check, ServiceStatus::Healthy), None);
+        assert_eq!(state.record(false, &check, ServiceStatus::Healthy), None);
+        assert_eq!(
+            state.record(false, &check, ServiceStatus::Healthy),
+            Some(ServiceStatus::Unhealthy)
+        );
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let check = HealthCheck::tcp().with_thresholds(2, 1);
+        let mut state = CheckState::default();
+
+        assert_eq!(state.record(false, &check, ServiceStatus::Healthy), None);
+        assert_eq!(state.record(true, &check, ServiceStatus::Healthy), None);
+        assert_eq!(state.record(false, &check, ServiceStatus::Healthy), None);
+    }
+
+    #[test]
+    fn test_recovery_requires_success_threshold() {
+        let check = HealthCheck::tcp().with_thresholds(1, 2);
+        let mut state = CheckState::default();
+
+        assert_eq!(state.record(true, &check, ServiceStatus::Unhealthy), None);
+        assert_eq!(
+            state.record(true, &check, ServiceStatus::Unhealthy),
+            Some(ServiceStatus::Healthy)
+        );
+    }
+
+    #[test]
+    fn test_starting_becomes_healthy() {
+        let check = HealthCheck::http("/healthz");
+        let mut state = CheckState::default();
+
+        assert_eq!(
+            state.record(true, &check, ServiceStatus::Starting),
+            Some(ServiceStatus::Healthy)
+        );
+    }
+
+    #[test]
+    fn test_stopping_is_left_alone() {
+        let check = HealthCheck::tcp().with_thresholds(1, 1);
+        let mut state = CheckState::default();
+
+        assert_eq!(state.record(false, &check, ServiceStatus::Stopping), None);
+        assert_eq!(state.record(true, &check, ServiceStatus::Stopping), None);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = reqwest::Client::new();
+
+        assert!(probe(&client, &client, &HealthCheck::tcp(), "127.0.0.1", port).await);
+
+        drop(listener);
+        assert!(!probe(&client, &client, &HealthCheck::tcp(), "127.0.0.1", port).await);
+    }
+}
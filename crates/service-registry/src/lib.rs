@@ -3,11 +3,27 @@ pub mod error;
 pub mod service;
 pub mod bootstrap;
 pub mod api;
+pub mod auth;
+pub mod health;
+mod memory;
+pub mod metrics;
+pub mod openapi;
+pub mod selector;
+pub mod snapshot;
+pub mod static_services;
+pub mod telemetry;
+pub mod versioning;
 
 pub use registry::ServiceRegistry;
 pub use error::RegistryError;
-pub use service::{ServiceInfo, ServiceStatus};
-pub use bootstrap::{bootstrap_service, get_service_endpoint};
+pub use selector::{SelectorError, ServiceSelector};
+pub use service::{HealthCheck, HealthCheckKind, ServiceInfo, ServiceStatus};
+pub use snapshot::{RegistrySnapshot, SnapshotDiff};
+pub use static_services::{StaticService, StaticServicesConfig};
+pub use bootstrap::{
+    bootstrap_service, bootstrap_service_with_health_check, discover_services, get_service_endpoint,
+    get_service_endpoint_matching, RegistryClientConfig,
+};
 
 // Re-export the macro
 pub use service_registry_macros::register_service;
\ No newline at end of file
@@ -1,15 +1,18 @@
-use axum::{
-    Router,
-    routing::{get, post},
-};
-use service_registry::api;
+mod tls;
+
+use axum::Router;
+use service_registry::{api, telemetry};
+use service_registry::auth::TokenStore;
+use service_registry::health::HealthChecker;
+use service_registry::static_services::StaticServiceKeeper;
+use service_registry::StaticServicesConfig;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tower_http::trace::TraceLayer;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let _telemetry = telemetry::init("service-registry");
 
     // Get etcd endpoints from environment
     let etcd_endpoints = std::env::var("ETCD_ENDPOINTS")
@@ -20,95 +23,50 @@ async fn main() {
 
     tracing::info!("Connecting to etcd at: {:?}", etcd_endpoints);
 
+    let tokens = TokenStore::from_env().expect("Failed to load registration tokens");
+    if tokens.is_none() {
+        tracing::warn!("No registration tokens configured; register/deregister are open to any caller");
+    }
+
+    // Static entries (REGISTRY_STATIC_SERVICES, plus COREOS_URL for compatibility)
+    let static_services = StaticServicesConfig::from_env()
+        .and_then(|config| config.service_infos())
+        .expect("Invalid static services configuration");
+
     // Create registry
-    let mut registry = service_registry::ServiceRegistry::new(etcd_endpoints, Some(30))
+    let registry = service_registry::ServiceRegistry::new(etcd_endpoints, Some(30))
         .await
         .expect("Failed to connect to etcd");
+    let registry = Arc::new(Mutex::new(registry));
 
-    // Auto-register CoreOS if COREOS_URL is set
-    if let Ok(coreos_url) = std::env::var("COREOS_URL") {
-        tracing::info!("Auto-registering CoreOS from COREOS_URL: {}", coreos_url);
-
-        // Parse the URL to extract address and port
-        if let Ok(url) = url::Url::parse(&coreos_url) {
-            let address = url.host_str().unwrap_or("localhost").to_string();
-            let port = url.port().unwrap_or(8080);
-
-            let coreos_service = service_registry::ServiceInfo::new(
-                "coreos",
-                "coreos-primary",
-                address,
-                port
-            )
-            .with_status(service_registry::ServiceStatus::Healthy)
-            .with_metadata("auto_registered", "true");
-
-            match registry.register(&coreos_service).await {
-                Ok(lease_id) => {
-                    tracing::info!("CoreOS registered successfully with lease ID: {}", lease_id);
-
-                    // Wrap registry in Arc<Mutex> before spawning keepalive task
-                    let registry = Arc::new(Mutex::new(registry));
-                    let registry_for_keepalive = registry.clone();
-
-                    tokio::spawn(async move {
-                        loop {
-                            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                            let mut reg = registry_for_keepalive.lock().await;
-                            if let Err(e) = reg.keep_alive(lease_id).await {
-                                tracing::error!("Failed to keep CoreOS lease alive: {}", e);
-                                break;
-                            }
-                        }
-                    });
-
-                    // Build and run the app
-                    let app = Router::new()
-                        .route("/api/registry/register", post(api::register))
-                        .route("/api/registry/deregister", post(api::deregister))
-                        .route("/api/registry/services", get(api::list_services))
-                        .route("/api/registry/services/{name}", get(api::get_services_by_name))
-                        .route("/api/registry/keepalive", post(api::keep_alive))
-                        .route("/health", get(|| async { "OK" }))
-                        .with_state(registry)
-                        .layer(TraceLayer::new_for_http());
-
-                    let listener = tokio::net::TcpListener::bind("0.0.0.0:3003")
-                        .await
-                        .unwrap();
-
-                    tracing::info!("Service registry listening on {}", listener.local_addr().unwrap());
-
-                    axum::serve(listener, app).await.unwrap();
-                    return;
-                }
-                Err(e) => {
-                    tracing::error!("Failed to register CoreOS: {}", e);
-                }
-            }
-        } else {
-            tracing::error!("Invalid COREOS_URL format: {}", coreos_url);
-        }
+    if !static_services.is_empty() {
+        tracing::info!("Keeping {} static service(s) registered", static_services.len());
+        StaticServiceKeeper::new(registry.clone(), static_services).spawn();
     }
+    HealthChecker::new(registry.clone()).spawn();
 
-    let registry = Arc::new(Mutex::new(registry));
+    // Build and run the app
+    serve(api::router(registry, tokens)).await;
+}
 
-    // Build the app
-    let app = Router::new()
-        .route("/api/registry/register", post(api::register))
-        .route("/api/registry/deregister", post(api::deregister))
-        .route("/api/registry/services", get(api::list_services))
-        .route("/api/registry/services/{name}", get(api::get_services_by_name))
-        .route("/api/registry/keepalive", post(api::keep_alive))
-        .route("/health", get(|| async { "OK" }))
-        .with_state(registry)
-        .layer(TraceLayer::new_for_http());
+async fn serve(app: Router) {
+    let addr = SocketAddr::from(([0, 0, 0, 0], 3003));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3003")
+    match tls::config_from_env()
         .await
-        .unwrap();
-
-    tracing::info!("Service registry listening on {}", listener.local_addr().unwrap());
-
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+        .expect("Invalid service registry TLS configuration")
+    {
+        Some(tls_config) => {
+            tracing::info!("Service registry listening securely on https://{}", addr);
+            axum_server::bind_rustls(addr, tls_config)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            tracing::info!("Service registry listening on {}", listener.local_addr().unwrap());
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
+}
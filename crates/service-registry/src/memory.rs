@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// A stored key with its value and the lease it is attached to (0 for none)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StoredKey {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub lease: i64,
+}
+
+#[derive(Debug)]
+struct Lease {
+    ttl: Duration,
+    expires_at: Instant,
+}
+
+/// In-process stand-in for the parts of etcd the registry uses
+///
+/// Keys are kept in order so prefix scans behave like etcd's. Leases expire
+/// lazily: every operation first drops expired leases and their keys.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryStore {
+    keys: BTreeMap<String, (Vec<u8>, i64)>,
+    leases: HashMap<i64, Lease>,
+    next_lease: i64,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop leases that expired before `now`, along with their keys
+    pub fn expire(&mut self, now: Instant) {
+        let before = self.leases.len();
+        self.leases.retain(|_, lease| lease.expires_at > now);
+        if self.leases.len() != before {
+            let leases = &self.leases;
+            self.keys
+                .retain(|_, (_, lease)| *lease == 0 || leases.contains_key(lease));
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<StoredKey> {
+        self.expire(Instant::now());
+        self.keys.get(key).map(|(value, lease)| StoredKey {
+            key: key.to_string(),
+            value: value.clone(),
+            lease: *lease,
+        })
+    }
+
+    pub fn list(&mut self, prefix: &str) -> Vec<StoredKey> {
+        self.expire(Instant::now());
+        self.keys
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, (value, lease))| StoredKey {
+                key: key.clone(),
+                value: value.clone(),
+                lease: *lease,
+            })
+            .collect()
+    }
+
+    /// Store `value` under `key`; `lease` is `Some(0)` for no lease and `None`
+    /// to keep the key's current lease. Returns false if the lease is unknown.
+    pub fn put(&mut self, key: &str, value: Vec<u8>, lease: Option<i64>) -> bool {
+        self.expire(Instant::now());
+        let lease = match lease {
+            Some(0) => 0,
+            Some(id) if self.leases.contains_key(&id) => id,
+            Some(_) => return false,
+            None => self.keys.get(key).map_or(0, |(_, lease)| *lease),
+        };
+        self.keys.insert(key.to_string(), (value, lease));
+        true
+    }
+
+    pub fn delete(&mut self, key: &str) {
+        self.keys.remove(key);
+    }
+
+    pub fn grant(&mut self, ttl_secs: i64) -> i64 {
+        self.next_lease += 1;
+        let ttl = Duration::from_secs(ttl_secs.max(1) as u64);
+        self.leases.insert(
+            self.next_lease,
+            Lease {
+                ttl,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        self.next_lease
+    }
+
+    /// Restart a lease's TTL; returns the TTL, or 0 if the lease has expired
+    pub fn keep_alive(&mut self, lease_id: i64) -> i64 {
+        let now = Instant::now();
+        self.expire(now);
+        match self.leases.get_mut(&lease_id) {
+            Some(lease) => {
+                lease.expires_at = now + lease.ttl;
+                lease.ttl.as_secs() as i64
+            }
+            None => 0,
+        }
+    }
+
+    /// Seconds left on a lease, or -1 if it does not exist (as etcd reports it)
+    pub fn time_to_live(&mut self, lease_id: i64) -> i64 {
+        let now = Instant::now();
+        self.expire(now);
+        self.leases
+            .get(&lease_id)
+            .map_or(-1, |lease| lease.expires_at.saturating_duration_since(now).as_secs() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_listing_is_ordered_and_bounded() {
+        let mut store = MemoryStore::new();
+        store.put("/services/b/1", b"b1".to_vec(), Some(0));
+        store.put("/services/a/2", b"a2".to_vec(), Some(0));
+        store.put("/services/a/1", b"a1".to_vec(), Some(0));
+        store.put("/services/ab/1", b"ab1".to_vec(), Some(0));
+
+        let keys: Vec<String> = store.list("/services/a/").into_iter().map(|k| k.key).collect();
+        assert_eq!(keys, vec!["/services/a/1", "/services/a/2"]);
+        assert_eq!(store.list("/services/").len(), 4);
+    }
+
+    #[test]
+    fn test_expired_lease_takes_its_keys_along() {
+        let mut store = MemoryStore::new();
+        let lease = store.grant(30);
+        assert!(store.put("/services/a/1", b"leased".to_vec(), Some(lease)));
+        store.put("/services/a/2", b"static".to_vec(), Some(0));
+        assert!(store.time_to_live(lease) > 0);
+
+        store.expire(Instant::now() + Duration::from_secs(31));
+
+        assert!(store.get("/services/a/1").is_none());
+        assert!(store.get("/services/a/2").is_some());
+        assert_eq!(store.keep_alive(lease), 0);
+        assert_eq!(store.time_to_live(lease), -1);
+        assert!(!store.put("/services/a/1", b"late".to_vec(), Some(lease)));
+    }
+
+    #[test]
+    fn test_put_without_lease_keeps_the_current_one() {
+        let mut store = MemoryStore::new();
+        let lease = store.grant(30);
+        store.put("/services/a/1", b"v1".to_vec(), Some(lease));
+        store.put("/services/a/1", b"v2".to_vec(), None);
+
+        let stored = store.get("/services/a/1").unwrap();
+        assert_eq!(stored.value, b"v2");
+        assert_eq!(stored.lease, lease);
+        assert_eq!(store.keep_alive(lease), 30);
+    }
+}
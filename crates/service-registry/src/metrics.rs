@@ -0,0 +1,154 @@
+//! Prometheus metrics shared by every service
+//!
+//! All metrics live in the default Prometheus registry, so a process that
+//! hosts several routers (like the all-in-one `xxx` binary) exposes them
+//! through a single `/metrics` endpoint. Execution and container metrics are
+//! recorded through the `xxx-metrics` crate and served here as well.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests handled, by route and status",
+        &["service", "route", "method", "status"]
+    )
+    .expect("register http_requests_total")
+});
+
+static HTTP_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time to produce an HTTP response, by route",
+        &["service", "route", "method"]
+    )
+    .expect("register http_request_duration_seconds")
+});
+
+static RATE_LIMIT_REJECTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rate_limit_rejections_total",
+        "Requests rejected by the rate limiter",
+        &["service"]
+    )
+    .expect("register rate_limit_rejections_total")
+});
+
+static LEASE_RENEW_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "registry_lease_renew_failures_total",
+        "Failed attempts to renew a service registration lease",
+        &["service"]
+    )
+    .expect("register registry_lease_renew_failures_total")
+});
+
+pub fn rate_limit_rejected(service: &str) {
+    RATE_LIMIT_REJECTIONS.with_label_values(&[service]).inc();
+}
+
+pub fn lease_renew_failed(service: &str) {
+    LEASE_RENEW_FAILURES.with_label_values(&[service]).inc();
+}
+
+/// Middleware recording request counts and latencies
+///
+/// Requests are labelled with the matched route template (`/api/blobs/{id}`),
+/// not the raw path, to keep label cardinality bounded. Unmatched requests are
+/// grouped under `unmatched`.
+///
+/// ```ignore
+/// router.layer(middleware::from_fn_with_state("container-api", metrics::track))
+/// ```
+pub async fn track(State(service): State<&'static str>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    HTTP_DURATION
+        .with_label_values(&[service, &route, &method])
+        .observe(started.elapsed().as_secs_f64());
+    HTTP_REQUESTS
+        .with_label_values(&[service, &route, &method, response.status().as_str()])
+        .inc();
+    response
+}
+
+/// Everything in the default registry, in Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::warn!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// `GET /metrics`
+pub async fn handler() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_render_includes_recorded_metrics() {
+        rate_limit_rejected("test-service");
+        lease_renew_failed("test-service");
+
+        let text = render();
+        assert!(text.contains(r#"rate_limit_rejections_total{service="test-service"} 1"#));
+        assert!(text.contains(r#"registry_lease_renew_failures_total{service="test-service"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn test_track_labels_requests_by_route() {
+        let app = Router::new()
+            .route("/items/{id}", get(|| async { "ok" }))
+            .route("/metrics", get(handler))
+            .layer(middleware::from_fn_with_state("track-test", track));
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/items/42").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            prometheus::TEXT_FORMAT
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/items/{id}",service="track-test",status="200"} 1"#
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+//! OpenAPI documents shared by every service
+//!
+//! Each service builds its document from the same `utoipa_axum` router that
+//! serves its API, publishes it at `/openapi.json` with a browsable page at
+//! `/docs`, and keeps a copy in its crate as `openapi.json`. A test in each
+//! service compares the two with [`check_published`], so the copy cannot
+//! drift from the handlers.
+
+use axum::http::header;
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use std::path::Path;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::OpenApi;
+use utoipa::Modify;
+
+/// Name of the bearer token scheme operations refer to in `security(...)`
+pub const BEARER: &str = "bearer";
+
+/// Settings every service document shares
+///
+/// Declares the [`BEARER`] security scheme, and drops the empty license utoipa
+/// fills in from Cargo metadata, since the crates do not declare one.
+pub struct Defaults;
+
+impl Modify for Defaults {
+    fn modify(&self, openapi: &mut OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(BEARER, SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        if openapi.info.license.as_ref().is_some_and(|license| license.name.is_empty()) {
+            openapi.info.license = None;
+        }
+    }
+}
+
+/// `/openapi.json` serving `spec`, and `/docs` rendering it
+///
+/// The docs page loads Redoc from its CDN, so it needs a browser with
+/// internet access; `/openapi.json` does not.
+pub fn routes(spec: OpenApi) -> Router {
+    let page = Html(docs_page(&spec.info.title));
+    let json = spec.to_json().expect("OpenAPI documents serialize to JSON");
+    Router::new()
+        .route(
+            "/openapi.json",
+            get(move || async move { ([(header::CONTENT_TYPE, "application/json")], json) }),
+        )
+        .route("/docs", get(move || async move { page }))
+}
+
+fn docs_page(title: &str) -> String {
+    format!(
+        r#"<!doctype html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>{title} API</title>
+  </head>
+  <body>
+    <redoc spec-url="/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/v2.5.0/bundles/redoc.standalone.js"></script>
+  </body>
+</html>
+"#
+    )
+}
+
+/// Compare `spec` with the copy published at `path`
+///
+/// With `UPDATE_OPENAPI` set the copy is rewritten instead, for API changes
+/// that are intended.
+pub fn check_published(spec: &OpenApi, path: impl AsRef<Path>) -> Result<(), String> {
+    check(spec, path.as_ref(), std::env::var_os("UPDATE_OPENAPI").is_some())
+}
+
+fn check(spec: &OpenApi, path: &Path, update: bool) -> Result<(), String> {
+    let generated = spec.to_pretty_json().map_err(|e| e.to_string())? + "\n";
+
+    if update {
+        return std::fs::write(path, generated).map_err(|e| format!("Failed to write {}: {}", path.display(), e));
+    }
+
+    match std::fs::read_to_string(path) {
+        Ok(published) if published == generated => Ok(()),
+        Ok(_) => Err(format!(
+            "{} no longer matches the handlers; review the API change and regenerate it with UPDATE_OPENAPI=1 cargo test",
+            path.display()
+        )),
+        Err(e) => Err(format!(
+            "Failed to read {} ({}); generate it with UPDATE_OPENAPI=1 cargo test",
+            path.display(),
+            e
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+    use utoipa::OpenApi as _;
+
+    #[derive(utoipa::OpenApi)]
+    #[openapi(info(title = "example"), modifiers(&Defaults))]
+    struct ApiDoc;
+
+    #[tokio::test]
+    async fn test_serves_spec_and_docs_page() {
+        let app = routes(ApiDoc::openapi());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(spec["info"]["title"], "example");
+        assert_eq!(spec["components"]["securitySchemes"][BEARER]["scheme"], "bearer");
+        assert!(spec["info"].get("license").is_none());
+
+        let response = app
+            .oneshot(Request::builder().uri("/docs").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = String::from_utf8(body.to_vec()).unwrap();
+        assert!(page.contains(r#"spec-url="/openapi.json""#));
+        assert!(page.contains("<title>example API</title>"));
+    }
+
+    #[test]
+    fn test_check_published_detects_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openapi.json");
+        let spec = ApiDoc::openapi();
+
+        assert!(check(&spec, &path, false).is_err());
+
+        check(&spec, &path, true).unwrap();
+        assert_eq!(check(&spec, &path, false), Ok(()));
+
+        let mut changed = spec.clone();
+        changed.info.title = "renamed".to_string();
+        assert!(check(&changed, &path, false).unwrap_err().contains("UPDATE_OPENAPI"));
+    }
+}
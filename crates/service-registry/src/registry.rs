@@ -1,11 +1,31 @@
 use crate::error::{RegistryError, Result};
-use crate::service::ServiceInfo;
+use crate::memory::{MemoryStore, StoredKey};
+use crate::selector::ServiceSelector;
+use crate::service::{ServiceInfo, ServiceStatus};
+use crate::snapshot::{ImportSummary, RegistrySnapshot, SnapshotEntry};
+use std::collections::HashMap;
 use etcd_client::{Client, GetOptions, PutOptions};
 use tracing::{debug, info, warn};
 
+/// Where registrations are stored
+enum Backend {
+    Etcd(Box<Client>),
+    /// Process-local store for single-process deployments
+    Memory(MemoryStore),
+}
+
+/// Lease to attach to a key on put
+#[derive(Debug, Clone, Copy)]
+enum PutLease {
+    None,
+    Lease(i64),
+    /// Keep whatever lease the key already has
+    Keep,
+}
+
 /// ServiceRegistry provides service discovery and registration using etcd as the backend
 pub struct ServiceRegistry {
-    client: Client,
+    backend: Backend,
     lease_ttl: i64,
 }
 
@@ -23,11 +43,102 @@ impl ServiceRegistry {
             .map_err(|e| RegistryError::ConnectionError(e.to_string()))?;
 
         Ok(Self {
-            client,
+            backend: Backend::Etcd(Box::new(client)),
             lease_ttl: lease_ttl.unwrap_or(10),
         })
     }
 
+    /// Create a registry that keeps everything in this process
+    ///
+    /// Leases expire as they would in etcd, but nothing survives a restart and
+    /// other processes cannot see the registrations.
+    pub fn in_memory(lease_ttl: Option<i64>) -> Self {
+        Self {
+            backend: Backend::Memory(MemoryStore::new()),
+            lease_ttl: lease_ttl.unwrap_or(10),
+        }
+    }
+
+    async fn get_key(&mut self, key: &str) -> Result<Option<StoredKey>> {
+        match &mut self.backend {
+            Backend::Etcd(client) => {
+                let resp = client.get(key, None).await?;
+                Ok(match resp.kvs().first() {
+                    Some(kv) => Some(StoredKey {
+                        key: kv.key_str()?.to_string(),
+                        value: kv.value().to_vec(),
+                        lease: kv.lease(),
+                    }),
+                    None => None,
+                })
+            }
+            Backend::Memory(store) => Ok(store.get(key)),
+        }
+    }
+
+    async fn list_prefix(&mut self, prefix: &str) -> Result<Vec<StoredKey>> {
+        match &mut self.backend {
+            Backend::Etcd(client) => {
+                let get_options = GetOptions::new().with_prefix();
+                let resp = client.get(prefix, Some(get_options)).await?;
+                let mut keys = Vec::new();
+                for kv in resp.kvs() {
+                    keys.push(StoredKey {
+                        key: kv.key_str()?.to_string(),
+                        value: kv.value().to_vec(),
+                        lease: kv.lease(),
+                    });
+                }
+                Ok(keys)
+            }
+            Backend::Memory(store) => Ok(store.list(prefix)),
+        }
+    }
+
+    async fn put_key(&mut self, key: String, value: String, lease: PutLease) -> Result<()> {
+        match &mut self.backend {
+            Backend::Etcd(client) => {
+                let put_options = match lease {
+                    PutLease::None => None,
+                    PutLease::Lease(lease_id) => Some(PutOptions::new().with_lease(lease_id)),
+                    PutLease::Keep => Some(PutOptions::new().with_ignore_lease()),
+                };
+                client.put(key, value, put_options).await?;
+                Ok(())
+            }
+            Backend::Memory(store) => {
+                let lease_id = match lease {
+                    PutLease::None => Some(0),
+                    PutLease::Lease(lease_id) => Some(lease_id),
+                    PutLease::Keep => None,
+                };
+                if store.put(&key, value.into_bytes(), lease_id) {
+                    Ok(())
+                } else {
+                    Err(RegistryError::LeaseExpired(lease_id.unwrap_or_default()))
+                }
+            }
+        }
+    }
+
+    async fn delete_key(&mut self, key: &str) -> Result<()> {
+        match &mut self.backend {
+            Backend::Etcd(client) => {
+                client.delete(key, None).await?;
+            }
+            Backend::Memory(store) => store.delete(key),
+        }
+        Ok(())
+    }
+
+    /// Seconds left on a lease
+    async fn lease_time_to_live(&mut self, lease_id: i64) -> Result<i64> {
+        match &mut self.backend {
+            Backend::Etcd(client) => Ok(client.lease_time_to_live(lease_id, None).await?.ttl()),
+            Backend::Memory(store) => Ok(store.time_to_live(lease_id)),
+        }
+    }
+
     /// Register a service with the registry
     ///
     /// This creates a lease and associates the service with it for automatic cleanup
@@ -38,8 +149,10 @@ impl ServiceRegistry {
         debug!("Registering service at key: {}", key);
 
         // Create a lease
-        let lease = self.client.lease_grant(self.lease_ttl, None).await?;
-        let lease_id = lease.id();
+        let lease_id = match &mut self.backend {
+            Backend::Etcd(client) => client.lease_grant(self.lease_ttl, None).await?.id(),
+            Backend::Memory(store) => store.grant(self.lease_ttl),
+        };
 
         info!(
             "Created lease {} with TTL {} seconds for service {}",
@@ -47,10 +160,7 @@ impl ServiceRegistry {
         );
 
         // Put the service info with the lease
-        let put_options = PutOptions::new().with_lease(lease_id);
-        self.client
-            .put(key.clone(), value, Some(put_options))
-            .await?;
+        self.put_key(key.clone(), value, PutLease::Lease(lease_id)).await?;
 
         info!("Service {} registered successfully at {}", service.name, key);
 
@@ -61,17 +171,65 @@ impl ServiceRegistry {
     pub async fn keep_alive(&mut self, lease_id: i64) -> Result<()> {
         debug!("Keeping lease {} alive", lease_id);
 
-        let (mut keeper, mut stream) = self.client.lease_keep_alive(lease_id).await?;
+        let client = match &mut self.backend {
+            Backend::Etcd(client) => client,
+            Backend::Memory(store) => {
+                return match store.keep_alive(lease_id) {
+                    ttl if ttl <= 0 => Err(RegistryError::LeaseExpired(lease_id)),
+                    _ => Ok(()),
+                };
+            }
+        };
+
+        let (mut keeper, mut stream) = client.lease_keep_alive(lease_id).await?;
 
         // Send initial keep alive
         keeper.keep_alive().await?;
 
-        // Check response
-        if let Some(resp) = stream.message().await? {
-            debug!("Lease {} kept alive, TTL: {}", resp.id(), resp.ttl());
+        // Check response; etcd answers an unknown or expired lease with TTL 0
+        match stream.message().await? {
+            Some(resp) if resp.ttl() <= 0 => Err(RegistryError::LeaseExpired(lease_id)),
+            Some(resp) => {
+                debug!("Lease {} kept alive, TTL: {}", resp.id(), resp.ttl());
+                Ok(())
+            }
+            None => Ok(()),
         }
+    }
 
-        Ok(())
+    /// Names of the services registered under a lease
+    pub async fn lease_services(&mut self, lease_id: i64) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for kv in self.list_prefix("/services/").await? {
+            if kv.lease != lease_id {
+                continue;
+            }
+            match serde_json::from_slice::<ServiceInfo>(&kv.value) {
+                Ok(service) => names.push(service.name),
+                Err(e) => warn!("Failed to deserialize service at {:?}: {}", kv.key, e),
+            }
+        }
+        Ok(names)
+    }
+
+    /// Update the status of a registered service without touching its lease
+    pub async fn set_status(
+        &mut self,
+        service_name: &str,
+        service_id: &str,
+        status: ServiceStatus,
+    ) -> Result<ServiceInfo> {
+        let mut service = self.get_service(service_name, service_id).await?;
+        service.status = status;
+
+        let key = service.service_key();
+        let value = serde_json::to_string(&service)?;
+
+        debug!("Setting status of {} to {:?}", key, status);
+
+        self.put_key(key, value, PutLease::Keep).await?;
+
+        Ok(service)
     }
 
     /// Deregister a service from the registry
@@ -80,7 +238,7 @@ impl ServiceRegistry {
 
         info!("Deregistering service at key: {}", key);
 
-        self.client.delete(key.clone(), None).await?;
+        self.delete_key(&key).await?;
 
         info!("Service {} deregistered successfully", service.name);
 
@@ -93,10 +251,8 @@ impl ServiceRegistry {
 
         debug!("Getting service at key: {}", key);
 
-        let resp = self.client.get(key.clone(), None).await?;
-
-        if let Some(kv) = resp.kvs().first() {
-            let service: ServiceInfo = serde_json::from_slice(kv.value())?;
+        if let Some(kv) = self.get_key(&key).await? {
+            let service: ServiceInfo = serde_json::from_slice(&kv.value)?;
             Ok(service)
         } else {
             Err(RegistryError::ServiceNotFound(key))
@@ -109,12 +265,9 @@ impl ServiceRegistry {
 
         debug!("Getting all services with prefix: {}", key);
 
-        let get_options = GetOptions::new().with_prefix();
-        let resp = self.client.get(key, Some(get_options)).await?;
-
         let mut services = Vec::new();
-        for kv in resp.kvs() {
-            match serde_json::from_slice(kv.value()) {
+        for kv in self.list_prefix(&key).await? {
+            match serde_json::from_slice(&kv.value) {
                 Ok(service) => services.push(service),
                 Err(e) => {
                     warn!("Failed to deserialize service: {}", e);
@@ -128,18 +281,29 @@ impl ServiceRegistry {
         Ok(services)
     }
 
+    /// Get the instances of a service that match a selector
+    pub async fn find_services(
+        &mut self,
+        service_name: &str,
+        selector: &ServiceSelector,
+    ) -> Result<Vec<ServiceInfo>> {
+        let services = self.get_services(service_name).await?;
+
+        Ok(services
+            .into_iter()
+            .filter(|service| selector.matches(service))
+            .collect())
+    }
+
     /// Get all registered services
     pub async fn get_all_services(&mut self) -> Result<Vec<ServiceInfo>> {
         let key = "/services/";
 
         debug!("Getting all services");
 
-        let get_options = GetOptions::new().with_prefix();
-        let resp = self.client.get(key, Some(get_options)).await?;
-
         let mut services = Vec::new();
-        for kv in resp.kvs() {
-            match serde_json::from_slice(kv.value()) {
+        for kv in self.list_prefix(key).await? {
+            match serde_json::from_slice(&kv.value) {
                 Ok(service) => services.push(service),
                 Err(e) => {
                     warn!("Failed to deserialize service: {}", e);
@@ -153,13 +317,103 @@ impl ServiceRegistry {
         Ok(services)
     }
 
+    /// Export every entry under `/services/` along with its lease and remaining TTL
+    pub async fn export_snapshot(&mut self) -> Result<RegistrySnapshot> {
+        let mut ttls: HashMap<i64, i64> = HashMap::new();
+        let mut entries = Vec::new();
+
+        for kv in self.list_prefix("/services/").await? {
+            let service: ServiceInfo = match serde_json::from_slice(&kv.value) {
+                Ok(service) => service,
+                Err(e) => {
+                    warn!("Skipping undecodable key {:?} in snapshot: {}", kv.key, e);
+                    continue;
+                }
+            };
+
+            let lease_id = Some(kv.lease).filter(|id| *id != 0);
+            let ttl_remaining_secs = match lease_id {
+                Some(id) => match ttls.get(&id) {
+                    Some(ttl) => Some(*ttl),
+                    None => {
+                        let ttl = self.lease_time_to_live(id).await?;
+                        ttls.insert(id, ttl);
+                        Some(ttl)
+                    }
+                },
+                None => None,
+            };
+
+            entries.push(SnapshotEntry {
+                key: kv.key,
+                service,
+                lease_id,
+                ttl_remaining_secs,
+            });
+        }
+
+        info!("Exported snapshot with {} entries", entries.len());
+
+        Ok(RegistrySnapshot::new(entries))
+    }
+
+    /// Import a snapshot as static, non-leased entries, or under one new
+    /// lease when `leased` is set
+    ///
+    /// Existing keys are left alone unless `overwrite` is set.
+    pub async fn import_snapshot(
+        &mut self,
+        snapshot: &RegistrySnapshot,
+        overwrite: bool,
+        leased: bool,
+    ) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        let lease = if leased {
+            let lease_id = match &mut self.backend {
+                Backend::Etcd(client) => client.lease_grant(self.lease_ttl, None).await?.id(),
+                Backend::Memory(store) => store.grant(self.lease_ttl),
+            };
+            summary.lease_id = Some(lease_id);
+            PutLease::Lease(lease_id)
+        } else {
+            PutLease::None
+        };
+
+        for entry in &snapshot.entries {
+            let key = entry.service.service_key();
+
+            if !overwrite && self.get_key(&key).await?.is_some() {
+                debug!("Skipping existing key {} during import", key);
+                summary.skipped.push(key);
+                continue;
+            }
+
+            let value = serde_json::to_string(&entry.service)?;
+            self.put_key(key, value, lease).await?;
+            summary.imported += 1;
+        }
+
+        info!(
+            "Imported {} entries from snapshot ({} skipped)",
+            summary.imported,
+            summary.skipped.len()
+        );
+
+        Ok(summary)
+    }
+
     /// Watch for changes to a specific service
     pub async fn watch_service(&mut self, service_name: &str) -> Result<()> {
         let key = format!("/services/{}/", service_name);
 
         info!("Watching service: {}", service_name);
 
-        let (_watcher, mut stream) = self.client.watch(key, None).await?;
+        let client = match &mut self.backend {
+            Backend::Etcd(client) => client,
+            Backend::Memory(_) => return Err(RegistryError::Unsupported("watching the in-memory registry")),
+        };
+
+        let (_watcher, mut stream) = client.watch(key, None).await?;
 
         while let Some(resp) = stream.message().await? {
             for event in resp.events() {
@@ -169,4 +423,50 @@ impl ServiceRegistry {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_registry_round_trip() {
+        let mut registry = ServiceRegistry::in_memory(Some(30));
+        let service = ServiceInfo::new("repl-api", "a", "127.0.0.1", 3001).with_status(ServiceStatus::Healthy);
+
+        let lease_id = registry.register(&service).await.unwrap();
+        registry.keep_alive(lease_id).await.unwrap();
+        assert_eq!(registry.lease_services(lease_id).await.unwrap(), vec!["repl-api"]);
+        assert!(registry.lease_services(lease_id + 1).await.unwrap().is_empty());
+        assert!(matches!(
+            registry.keep_alive(lease_id + 1).await,
+            Err(RegistryError::LeaseExpired(_))
+        ));
+
+        let updated = registry.set_status("repl-api", "a", ServiceStatus::Unhealthy).await.unwrap();
+        assert_eq!(updated.status, ServiceStatus::Unhealthy);
+        assert_eq!(registry.get_services("repl-api").await.unwrap().len(), 1);
+        assert!(registry.find_services("repl-api", &ServiceSelector::default()).await.unwrap().is_empty());
+
+        let snapshot = registry.export_snapshot().await.unwrap();
+        assert_eq!(snapshot.entries[0].lease_id, Some(lease_id));
+        assert!(snapshot.entries[0].ttl_remaining_secs.is_some_and(|ttl| ttl > 0));
+
+        registry.deregister(&service).await.unwrap();
+        assert!(matches!(
+            registry.get_service("repl-api", "a").await,
+            Err(RegistryError::ServiceNotFound(_))
+        ));
+
+        let summary = registry.import_snapshot(&snapshot, false, true).await.unwrap();
+        assert_eq!(summary.imported, 1);
+        let imported = registry.export_snapshot().await.unwrap();
+        assert_eq!(imported.entries[0].lease_id, summary.lease_id);
+        assert!(summary.lease_id.is_some_and(|id| id != lease_id));
+
+        let summary = registry.import_snapshot(&snapshot, true, false).await.unwrap();
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.lease_id, None);
+        let imported = registry.export_snapshot().await.unwrap();
+        assert_eq!(imported.entries[0].lease_id, None);
+    }
+}
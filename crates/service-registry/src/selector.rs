@@ -0,0 +1,197 @@
+use crate::health;
+use crate::service::ServiceInfo;
+use semver::{Version, VersionReq};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Query-string prefix for metadata selectors, e.g. `meta.zone=us-east1`
+const METADATA_PREFIX: &str = "meta.";
+
+#[derive(Error, Debug)]
+#[error("invalid selector: {0}")]
+pub struct SelectorError(String);
+
+pub type Result<T> = std::result::Result<T, SelectorError>;
+
+/// Narrows a discovery query down to instances matching a version range and
+/// metadata labels
+///
+/// The same selector is accepted by `GET /api/registry/services/{name}` as
+/// query parameters, e.g. `?version=^0.2&meta.zone=us-east1&meta.lang=python`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServiceSelector {
+    pub version: Option<VersionReq>,
+    pub metadata: BTreeMap<String, String>,
+    pub include_unhealthy: bool,
+}
+
+impl ServiceSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require a semver-compatible version, e.g. `^0.2` or `>=1.0, <2.0`
+    pub fn with_version(mut self, requirement: &str) -> Result<Self> {
+        self.version = Some(parse_version_req(requirement)?);
+        Ok(self)
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn include_unhealthy(mut self, include: bool) -> Self {
+        self.include_unhealthy = include;
+        self
+    }
+
+    /// Parse a selector from query parameters
+    pub fn from_query_pairs<I, K, V>(pairs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut selector = Self::new();
+
+        for (key, value) in pairs {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            match key {
+                "version" => selector.version = Some(parse_version_req(value)?),
+                "include_unhealthy" => {
+                    selector.include_unhealthy = value.parse().map_err(|_| {
+                        SelectorError(format!(
+                            "include_unhealthy must be true or false, got '{}'",
+                            value
+                        ))
+                    })?;
+                }
+                _ => match key.strip_prefix(METADATA_PREFIX) {
+                    Some(meta_key) if !meta_key.is_empty() => {
+                        selector.metadata.insert(meta_key.to_string(), value.to_string());
+                    }
+                    _ => {
+                        return Err(SelectorError(format!(
+                            "unknown query parameter '{}'",
+                            key
+                        )))
+                    }
+                },
+            }
+        }
+
+        Ok(selector)
+    }
+
+    /// Render the selector as query parameters understood by the registry API
+    pub fn to_query_pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+
+        if let Some(version) = &self.version {
+            pairs.push(("version".to_string(), version.to_string()));
+        }
+        for (key, value) in &self.metadata {
+            pairs.push((format!("{}{}", METADATA_PREFIX, key), value.clone()));
+        }
+        if self.include_unhealthy {
+            pairs.push(("include_unhealthy".to_string(), "true".to_string()));
+        }
+
+        pairs
+    }
+
+    /// Whether an instance satisfies every constraint of the selector
+    pub fn matches(&self, service: &ServiceInfo) -> bool {
+        if !self.include_unhealthy && !health::is_routable(service) {
+            return false;
+        }
+
+        if let Some(requirement) = &self.version {
+            match Version::parse(&service.version) {
+                Ok(version) if requirement.matches(&version) => {}
+                _ => return false,
+            }
+        }
+
+        self.metadata
+            .iter()
+            .all(|(key, value)| service.metadata.get(key) == Some(value))
+    }
+}
+
+fn parse_version_req(requirement: &str) -> Result<VersionReq> {
+    VersionReq::parse(requirement).map_err(|e| {
+        SelectorError(format!("invalid version requirement '{}': {}", requirement, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceStatus;
+
+    fn service(version: &str) -> ServiceInfo {
+        ServiceInfo::new("repl-api", "repl-1", "repl-api", 3001)
+            .with_status(ServiceStatus::Healthy)
+            .with_version(version)
+            .with_metadata("zone", "us-east1")
+            .with_metadata("lang", "python")
+    }
+
+    #[test]
+    fn test_parse_query_pairs() {
+        let selector = ServiceSelector::from_query_pairs([
+            ("version", "^0.2"),
+            ("meta.zone", "us-east1"),
+            ("meta.lang", "python"),
+        ])
+        .unwrap();
+
+        assert_eq!(selector.version, Some(VersionReq::parse("^0.2").unwrap()));
+        assert_eq!(selector.metadata.get("zone").map(String::as_str), Some("us-east1"));
+        assert_eq!(selector.metadata.get("lang").map(String::as_str), Some("python"));
+        assert!(!selector.include_unhealthy);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(ServiceSelector::from_query_pairs([("version", "not a version")]).is_err());
+        assert!(ServiceSelector::from_query_pairs([("zone", "us-east1")]).is_err());
+        assert!(ServiceSelector::from_query_pairs([("meta.", "x")]).is_err());
+        assert!(ServiceSelector::from_query_pairs([("include_unhealthy", "yes")]).is_err());
+    }
+
+    #[test]
+    fn test_query_pairs_round_trip() {
+        let selector = ServiceSelector::new()
+            .with_version(">=0.2, <0.4")
+            .unwrap()
+            .with_metadata("zone", "us-east1")
+            .include_unhealthy(true);
+
+        let parsed = ServiceSelector::from_query_pairs(selector.to_query_pairs()).unwrap();
+        assert_eq!(parsed, selector);
+    }
+
+    #[test]
+    fn test_matches_version_and_metadata() {
+        let selector = ServiceSelector::new()
+            .with_version("^0.2")
+            .unwrap()
+            .with_metadata("zone", "us-east1");
+
+        assert!(selector.matches(&service("0.2.5")));
+        assert!(!selector.matches(&service("0.1.0")));
+        assert!(!selector.matches(&service("not-semver")));
+        assert!(!selector.matches(&service("0.2.5").with_metadata("zone", "eu-west1")));
+    }
+
+    #[test]
+    fn test_unhealthy_excluded_by_default() {
+        let unhealthy = service("0.1.0").with_status(ServiceStatus::Unhealthy);
+
+        assert!(!ServiceSelector::new().matches(&unhealthy));
+        assert!(ServiceSelector::new().include_unhealthy(true).matches(&unhealthy));
+    }
+}
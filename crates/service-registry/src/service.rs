@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum ServiceStatus {
     Healthy,
     Unhealthy,
@@ -9,7 +10,102 @@ pub enum ServiceStatus {
     Stopping,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How the registry should probe a service instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HealthCheckKind {
+    /// Plain HTTP GET against `path`; any 2xx counts as healthy
+    Http { path: String },
+    /// HTTPS GET against `path`; `insecure` skips certificate verification
+    /// for services that run with self-signed certs
+    Https {
+        path: String,
+        #[serde(default)]
+        insecure: bool,
+    },
+    /// TCP connect to the service address and port
+    Tcp,
+}
+
+/// Health check declared by a service at registration time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub kind: HealthCheckKind,
+    /// Seconds between probes
+    #[serde(default = "HealthCheck::default_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds before a single probe is considered failed
+    #[serde(default = "HealthCheck::default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Consecutive failures before the instance is marked `Unhealthy`
+    #[serde(default = "HealthCheck::default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Consecutive successes before the instance is marked `Healthy`
+    #[serde(default = "HealthCheck::default_success_threshold")]
+    pub success_threshold: u32,
+}
+
+impl HealthCheck {
+    pub fn new(kind: HealthCheckKind) -> Self {
+        Self {
+            kind,
+            interval_secs: Self::default_interval_secs(),
+            timeout_secs: Self::default_timeout_secs(),
+            failure_threshold: Self::default_failure_threshold(),
+            success_threshold: Self::default_success_threshold(),
+        }
+    }
+
+    pub fn http(path: impl Into<String>) -> Self {
+        Self::new(HealthCheckKind::Http { path: path.into() })
+    }
+
+    pub fn https(path: impl Into<String>, insecure: bool) -> Self {
+        Self::new(HealthCheckKind::Https {
+            path: path.into(),
+            insecure,
+        })
+    }
+
+    pub fn tcp() -> Self {
+        Self::new(HealthCheckKind::Tcp)
+    }
+
+    pub fn with_interval(mut self, secs: u64) -> Self {
+        self.interval_secs = secs;
+        self
+    }
+
+    pub fn with_timeout(mut self, secs: u64) -> Self {
+        self.timeout_secs = secs;
+        self
+    }
+
+    pub fn with_thresholds(mut self, failure_threshold: u32, success_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self.success_threshold = success_threshold;
+        self
+    }
+
+    fn default_interval_secs() -> u64 {
+        10
+    }
+
+    fn default_timeout_secs() -> u64 {
+        2
+    }
+
+    fn default_failure_threshold() -> u32 {
+        3
+    }
+
+    fn default_success_threshold() -> u32 {
+        1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct ServiceInfo {
     pub name: String,
     pub id: String,
@@ -18,6 +114,8 @@ pub struct ServiceInfo {
     pub status: ServiceStatus,
     pub metadata: HashMap<String, String>,
     pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
 }
 
 impl ServiceInfo {
@@ -30,6 +128,7 @@ impl ServiceInfo {
             status: ServiceStatus::Starting,
             metadata: HashMap::new(),
             version: "0.1.0".to_string(),
+            health_check: None,
         }
     }
 
@@ -48,7 +147,51 @@ impl ServiceInfo {
         self
     }
 
+    pub fn with_health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
+
     pub fn service_key(&self) -> String {
         format!("/services/{}/{}", self.name, self.id)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_info_without_health_check_deserializes() {
+        let json = r#"{"name":"coreos","id":"coreos-primary","address":"coreos","port":8085,
+            "status":"Healthy","metadata":{},"version":"0.1.0"}"#;
+        let service: ServiceInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(service.health_check, None);
+        assert_eq!(service.status, ServiceStatus::Healthy);
+    }
+
+    #[test]
+    fn test_health_check_defaults() {
+        let json = r#"{"type":"http","path":"/healthz"}"#;
+        let check: HealthCheck = serde_json::from_str(json).unwrap();
+        assert_eq!(check, HealthCheck::http("/healthz"));
+        assert_eq!(check.interval_secs, 10);
+        assert_eq!(check.failure_threshold, 3);
+        assert_eq!(check.success_threshold, 1);
+    }
+
+    #[test]
+    fn test_health_check_round_trip() {
+        let service = ServiceInfo::new("repl-api", "repl-1", "repl-api", 3001).with_health_check(
+            HealthCheck::https("/api/repl/languages", true)
+                .with_interval(5)
+                .with_thresholds(2, 2),
+        );
+
+        let json = serde_json::to_string(&service).unwrap();
+        assert!(json.contains(r#""type":"https""#));
+
+        let parsed: ServiceInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.health_check, service.health_check);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::service::ServiceInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// A single registry key as captured in a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub service: ServiceInfo,
+    /// Lease the key was attached to, or None for static entries
+    pub lease_id: Option<i64>,
+    /// Seconds left on the lease when the snapshot was taken
+    pub ttl_remaining_secs: Option<i64>,
+}
+
+/// Point-in-time dump of everything under `/services/`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RegistrySnapshot {
+    /// Unix timestamp (seconds) of when the snapshot was taken
+    pub taken_at: u64,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// An entry whose service data differs between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ChangedEntry {
+    pub key: String,
+    pub before: ServiceInfo,
+    pub after: ServiceInfo,
+}
+
+/// Differences between two snapshots, keyed by registry key
+///
+/// Lease ids and remaining TTLs are ignored; they change on every keepalive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotEntry>,
+    pub removed: Vec<SnapshotEntry>,
+    pub changed: Vec<ChangedEntry>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Result of importing a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: Vec<String>,
+    /// Lease the imported entries share, which must be kept alive for them to
+    /// outlive its TTL; None for a static import
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_id: Option<i64>,
+}
+
+impl RegistrySnapshot {
+    pub fn new(entries: Vec<SnapshotEntry>) -> Self {
+        let taken_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self { taken_at, entries }
+    }
+
+    /// Compare this snapshot against a newer one
+    pub fn diff(&self, newer: &RegistrySnapshot) -> SnapshotDiff {
+        let before: BTreeMap<_, _> = self.entries.iter().map(|e| (e.key.as_str(), e)).collect();
+        let after: BTreeMap<_, _> = newer.entries.iter().map(|e| (e.key.as_str(), e)).collect();
+
+        let mut diff = SnapshotDiff::default();
+
+        for (key, entry) in &after {
+            match before.get(key) {
+                None => diff.added.push((*entry).clone()),
+                Some(old) if old.service != entry.service => diff.changed.push(ChangedEntry {
+                    key: key.to_string(),
+                    before: old.service.clone(),
+                    after: entry.service.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for (key, entry) in &before {
+            if !after.contains_key(key) {
+                diff.removed.push((*entry).clone());
+            }
+        }
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceStatus;
+
+    fn entry(name: &str, id: &str, status: ServiceStatus, lease_id: Option<i64>) -> SnapshotEntry {
+        let service = ServiceInfo::new(name, id, name, 3000).with_status(status);
+        SnapshotEntry {
+            key: service.service_key(),
+            service,
+            lease_id,
+            ttl_remaining_secs: lease_id.map(|_| 30),
+        }
+    }
+
+    #[test]
+    fn test_diff_added_removed_changed() {
+        let before = RegistrySnapshot::new(vec![
+            entry("repl-api", "a", ServiceStatus::Healthy, Some(1)),
+            entry("container-api", "b", ServiceStatus::Healthy, Some(2)),
+        ]);
+        let after = RegistrySnapshot::new(vec![
+            entry("repl-api", "a", ServiceStatus::Unhealthy, Some(1)),
+            entry("coreos", "c", ServiceStatus::Healthy, None),
+        ]);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].key, "/services/coreos/c");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].key, "/services/container-api/b");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].after.status, ServiceStatus::Unhealthy);
+    }
+
+    #[test]
+    fn test_diff_ignores_leases() {
+        let before = RegistrySnapshot::new(vec![entry("repl-api", "a", ServiceStatus::Healthy, Some(1))]);
+        let after = RegistrySnapshot::new(vec![entry("repl-api", "a", ServiceStatus::Healthy, Some(99))]);
+
+        assert!(before.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = RegistrySnapshot::new(vec![entry("coreos", "c", ServiceStatus::Healthy, None)]);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: RegistrySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+}
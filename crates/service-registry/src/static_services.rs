@@ -0,0 +1,260 @@
+use crate::service::{HealthCheck, ServiceInfo, ServiceStatus};
+use crate::ServiceRegistry;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How often the registry refreshes the leases of its static entries
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A service the registry registers and keeps alive on its own behalf,
+/// e.g. a Podman host that cannot register itself
+#[derive(Debug, Clone, Deserialize)]
+pub struct StaticService {
+    pub name: String,
+    pub id: String,
+    /// Base URL; an alternative to `address` + `port`
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+}
+
+impl StaticService {
+    pub fn to_service_info(&self) -> anyhow::Result<ServiceInfo> {
+        let (address, port) = match (&self.url, &self.address, self.port) {
+            (Some(url), _, _) => {
+                let url = url::Url::parse(url)
+                    .with_context(|| format!("Invalid url for static service {}: {}", self.id, url))?;
+                let address = url.host_str().unwrap_or("localhost").to_string();
+                let port = url
+                    .port_or_known_default()
+                    .with_context(|| format!("No port in url for static service {}: {}", self.id, url))?;
+                (address, port)
+            }
+            (None, Some(address), Some(port)) => (address.clone(), port),
+            _ => anyhow::bail!(
+                "Static service {} needs either url or address and port",
+                self.id
+            ),
+        };
+
+        let mut service = ServiceInfo::new(&self.name, &self.id, address, port)
+            .with_status(ServiceStatus::Healthy)
+            .with_metadata("static", "true");
+        if let Some(version) = &self.version {
+            service = service.with_version(version);
+        }
+        for (key, value) in &self.metadata {
+            service = service.with_metadata(key, value);
+        }
+        if let Some(health_check) = &self.health_check {
+            service = service.with_health_check(health_check.clone());
+        }
+
+        Ok(service)
+    }
+}
+
+/// Declarative list of static services, loaded from a JSON file
+///
+/// ```json
+/// {
+///   "services": [
+///     { "name": "coreos", "id": "coreos-a", "url": "http://coreos-a:8085",
+///       "metadata": { "zone": "us-east1", "capacity": "8" },
+///       "health_check": { "type": "http", "path": "/_ping" } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StaticServicesConfig {
+    #[serde(default)]
+    pub services: Vec<StaticService>,
+}
+
+impl StaticServicesConfig {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read static services file {}", path))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse static services file {}", path))
+    }
+
+    /// Load `REGISTRY_STATIC_SERVICES` (a JSON file) and, for backwards
+    /// compatibility, a single `coreos` entry from `COREOS_URL`
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = match std::env::var("REGISTRY_STATIC_SERVICES") {
+            Ok(path) => Self::load(&path)?,
+            Err(_) => Self::default(),
+        };
+
+        if let Ok(coreos_url) = std::env::var("COREOS_URL") {
+            if config.services.iter().any(|s| s.id == "coreos-primary") {
+                warn!("COREOS_URL ignored; coreos-primary is already defined in the static services file");
+            } else if let Some(service) = Self::coreos_service(coreos_url) {
+                config.services.push(service);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The `coreos-primary` entry for `COREOS_URL`, or `None` with a warning
+    /// if the URL is invalid, so a bad value doesn't stop the registry
+    fn coreos_service(coreos_url: String) -> Option<StaticService> {
+        let service = StaticService {
+            name: "coreos".to_string(),
+            id: "coreos-primary".to_string(),
+            url: Some(coreos_url),
+            address: None,
+            port: None,
+            version: None,
+            metadata: HashMap::from([("auto_registered".to_string(), "true".to_string())]),
+            health_check: Some(HealthCheck::http("/_ping")),
+        };
+        match service.to_service_info() {
+            Ok(_) => Some(service),
+            Err(e) => {
+                warn!("COREOS_URL ignored: {:#}", e);
+                None
+            }
+        }
+    }
+
+    pub fn service_infos(&self) -> anyhow::Result<Vec<ServiceInfo>> {
+        self.services.iter().map(StaticService::to_service_info).collect()
+    }
+}
+
+/// Registers static services and keeps their leases alive, re-registering
+/// any entry whose lease has been lost
+pub struct StaticServiceKeeper {
+    registry: Arc<Mutex<ServiceRegistry>>,
+    services: Vec<ServiceInfo>,
+    leases: HashMap<String, i64>,
+}
+
+impl StaticServiceKeeper {
+    pub fn new(registry: Arc<Mutex<ServiceRegistry>>, services: Vec<ServiceInfo>) -> Self {
+        Self {
+            registry,
+            services,
+            leases: HashMap::new(),
+        }
+    }
+
+    /// Run the keeper in a background task
+    pub fn spawn(mut self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(KEEPALIVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.run_once().await;
+            }
+        })
+    }
+
+    async fn run_once(&mut self) {
+        let mut registry = self.registry.lock().await;
+
+        for service in &self.services {
+            let key = service.service_key();
+
+            if let Some(lease_id) = self.leases.get(&key).copied() {
+                match registry.keep_alive(lease_id).await {
+                    Ok(()) => continue,
+                    Err(e) => {
+                        crate::metrics::lease_renew_failed(&service.name);
+                        warn!("Lost lease {} for static service {}: {}", lease_id, key, e);
+                        self.leases.remove(&key);
+                    }
+                }
+            }
+
+            match registry.register(service).await {
+                Ok(lease_id) => {
+                    info!("Registered static service {} with lease {}", key, lease_id);
+                    self.leases.insert(key, lease_id);
+                }
+                Err(e) => warn!("Failed to register static service {}: {}", key, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthCheckKind;
+
+    #[test]
+    fn test_parse_config_with_multiple_podman_hosts() {
+        let json = r#"{
+            "services": [
+                { "name": "coreos", "id": "coreos-a", "url": "http://coreos-a:8085",
+                  "metadata": { "zone": "us-east1", "capacity": "8" },
+                  "health_check": { "type": "http", "path": "/_ping" } },
+                { "name": "coreos", "id": "coreos-b", "address": "10.0.0.12", "port": 8085,
+                  "version": "5.2.0" }
+            ]
+        }"#;
+        let config: StaticServicesConfig = serde_json::from_str(json).unwrap();
+        let services = config.service_infos().unwrap();
+
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].address, "coreos-a");
+        assert_eq!(services[0].port, 8085);
+        assert_eq!(services[0].metadata.get("capacity").map(String::as_str), Some("8"));
+        assert_eq!(services[0].metadata.get("static").map(String::as_str), Some("true"));
+        assert!(matches!(
+            services[0].health_check.as_ref().map(|c| &c.kind),
+            Some(HealthCheckKind::Http { .. })
+        ));
+        assert_eq!(services[1].address, "10.0.0.12");
+        assert_eq!(services[1].version, "5.2.0");
+        assert_eq!(services[1].status, ServiceStatus::Healthy);
+    }
+
+    #[test]
+    fn test_missing_address_is_rejected() {
+        let json = r#"{ "services": [ { "name": "coreos", "id": "coreos-a", "port": 8085 } ] }"#;
+        let config: StaticServicesConfig = serde_json::from_str(json).unwrap();
+        assert!(config.service_infos().is_err());
+    }
+
+    #[test]
+    fn test_url_port_defaults_to_scheme() {
+        let json = r#"{ "services": [
+            { "name": "coreos", "id": "coreos-a", "url": "https://coreos-a" },
+            { "name": "coreos", "id": "coreos-b", "url": "http://coreos-b" }
+        ] }"#;
+        let config: StaticServicesConfig = serde_json::from_str(json).unwrap();
+        let services = config.service_infos().unwrap();
+        assert_eq!(services[0].port, 443);
+        assert_eq!(services[1].port, 80);
+
+        let json = r#"{ "services": [ { "name": "coreos", "id": "coreos-a", "url": "tcp://coreos-a" } ] }"#;
+        let config: StaticServicesConfig = serde_json::from_str(json).unwrap();
+        assert!(config.service_infos().is_err());
+    }
+
+    #[test]
+    fn test_invalid_coreos_url_is_skipped() {
+        assert!(StaticServicesConfig::coreos_service("not a url".to_string()).is_none());
+        let service = StaticServicesConfig::coreos_service("http://coreos:8085".to_string()).unwrap();
+        assert_eq!(service.to_service_info().unwrap().port, 8085);
+    }
+}
@@ -0,0 +1,183 @@
+//! Tracing setup and W3C trace context propagation
+//!
+//! Every service calls [`init`] instead of `tracing_subscriber::fmt::init()`.
+//! Incoming `traceparent` headers become the parent of the request span (see
+//! [`make_span`]), and outgoing registry and container-api calls carry the
+//! current context through [`inject`], so one execution shows up as a single
+//! trace across repl-api, container-api and the Podman operations it makes.
+//!
+//! Spans are only exported when `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) is set, over OTLP/HTTP. The exporter
+//! reads the other standard `OTEL_EXPORTER_OTLP_*` variables itself, and
+//! `OTEL_SERVICE_NAME` overrides the service name.
+
+use axum::http::{HeaderMap, Request};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, Context};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Flushes buffered spans when dropped; keep it alive for the life of `main`
+#[must_use = "spans are only flushed while the guard is alive"]
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Whether an OTLP collector has been configured
+pub fn otlp_enabled() -> bool {
+    ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+        .iter()
+        .any(|name| std::env::var(name).is_ok_and(|value| !value.is_empty()))
+}
+
+fn tracer_provider(service_name: &str) -> SdkTracerProvider {
+    let mut resource = Resource::builder();
+    if std::env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(service_name.to_string());
+    }
+    let mut builder = SdkTracerProvider::builder().with_resource(resource.build());
+
+    if otlp_enabled() {
+        match opentelemetry_otlp::SpanExporter::builder().with_http().build() {
+            Ok(exporter) => builder = builder.with_batch_exporter(exporter),
+            Err(e) => eprintln!("Failed to build OTLP exporter, spans will not be exported: {}", e),
+        }
+    }
+
+    builder.build()
+}
+
+/// Install the global subscriber (log output filtered by `RUST_LOG`, default
+/// `info`) and the trace context propagator
+pub fn init(service_name: &str) -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = tracer_provider(service_name);
+    let tracer = provider.tracer(service_name.to_string());
+    global::set_tracer_provider(provider.clone());
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .init();
+
+    Telemetry { provider }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// The trace context carried by incoming request headers
+pub fn extract(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Request span for `TraceLayer::make_span_with`, parented to the caller's
+/// `traceparent` when there is one
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+    );
+    // Fails only when no OpenTelemetry layer is installed, e.g. in tests
+    let _ = span.set_parent(extract(request.headers()));
+    span
+}
+
+/// Headers propagating the current span's trace context
+pub fn current_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+    headers
+}
+
+/// Attach the current trace context to an outgoing request
+pub fn inject(mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    for (name, value) in current_headers() {
+        request = request.header(name, value);
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use opentelemetry::trace::TraceContextExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    fn with_tracing(test: impl FnOnce()) {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, test);
+    }
+
+    #[test]
+    fn test_request_span_continues_incoming_trace() {
+        with_tracing(|| {
+            let request = Request::get("/api/repl/execute")
+                .header("traceparent", format!("00-{}-00f067aa0ba902b7-01", TRACE_ID))
+                .body(Body::empty())
+                .unwrap();
+
+            let span = make_span(&request);
+            let context = span.context();
+            assert_eq!(context.span().span_context().trace_id().to_string(), TRACE_ID);
+
+            // Outgoing calls made inside the request carry the same trace
+            let headers = span.in_scope(current_headers);
+            let traceparent = &headers["traceparent"];
+            assert!(traceparent.starts_with(&format!("00-{}-", TRACE_ID)));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+
+    #[test]
+    fn test_request_without_traceparent_starts_new_trace() {
+        with_tracing(|| {
+            let request = Request::get("/health").body(Body::empty()).unwrap();
+            let span = make_span(&request);
+            let trace_id = span.context().span().span_context().trace_id();
+            assert_ne!(trace_id, opentelemetry::trace::TraceId::INVALID);
+        });
+    }
+
+    #[test]
+    fn test_no_headers_outside_a_span() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        assert!(current_headers().is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::sync::Arc;
+
+/// Build the registry's TLS config from the environment
+///
+/// - `REGISTRY_TLS_CERT` / `REGISTRY_TLS_KEY`: PEM server certificate and key
+/// - `REGISTRY_TLS_CLIENT_CA`: PEM CA bundle; when set, clients must present a
+///   certificate signed by it (mutual TLS)
+///
+/// Returns None when no server certificate is configured (plain HTTP).
+pub async fn config_from_env() -> anyhow::Result<Option<RustlsConfig>> {
+    let (cert_path, key_path) = match (
+        std::env::var("REGISTRY_TLS_CERT"),
+        std::env::var("REGISTRY_TLS_KEY"),
+    ) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        (Err(_), Err(_)) => return Ok(None),
+        _ => anyhow::bail!("REGISTRY_TLS_CERT and REGISTRY_TLS_KEY must be set together"),
+    };
+
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .with_context(|| format!("Failed to read {}", cert_path))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificates in {}", cert_path))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .with_context(|| format!("Failed to read private key {}", key_path))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let config = match std::env::var("REGISTRY_TLS_CLIENT_CA") {
+        Ok(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(&ca_path)
+                .with_context(|| format!("Failed to read {}", ca_path))?
+            {
+                roots.add(cert?)?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .context("Failed to build client certificate verifier")?;
+
+            tracing::info!("Requiring client certificates signed by {}", ca_path);
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)?
+        }
+        Err(_) => builder.with_no_client_auth().with_single_cert(certs, key)?,
+    };
+
+    Ok(Some(RustlsConfig::from_config(Arc::new(config))))
+}
@@ -0,0 +1,164 @@
+//! Versioned API routes shared by every service
+//!
+//! Each service serves its API under `/api/v1` and keeps the unversioned
+//! `/api` paths it shipped with as aliases. Responses from the aliases carry
+//! `Deprecation` and `Sunset` headers (RFC 9745 and RFC 8594) and a `Link` to
+//! the versioned path. `GET /api/version` lists the versions served, so
+//! clients can check for a compatible server before sending requests.
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::{Json, Router};
+use std::sync::Arc;
+use utoipa::openapi::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+pub use xxx_types::version::{ApiVersions, API_VERSION};
+
+/// API versions every service serves
+pub const SUPPORTED: &[&str] = &[API_VERSION];
+
+/// `Deprecation` value of the unversioned routes: deprecated since 2026-10-18
+pub const DEPRECATED_AT: &str = "@1792281600";
+
+/// `Sunset` value of the unversioned routes
+pub const LEGACY_SUNSET: &str = "Sun, 18 Apr 2027 00:00:00 GMT";
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// `api` under `/api/v1`, and again under `/api` as a deprecated alias
+///
+/// Paths in `api` are relative to `/api`, e.g. `/containers/create`.
+pub fn versioned<S>(api: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .nest(&format!("/api/{}", API_VERSION), api.clone())
+        .nest("/api", api.layer(middleware::from_fn(deprecated)))
+}
+
+/// Mark a response from an unversioned route as deprecated
+async fn deprecated(request: Request, next: Next) -> Response {
+    // Nesting under `/api` stripped the prefix, leaving the path within the API
+    let successor = format!("</api/{}{}>; rel=\"successor-version\"", API_VERSION, request.uri().path());
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION, HeaderValue::from_static(DEPRECATED_AT));
+    headers.insert(SUNSET, HeaderValue::from_static(LEGACY_SUNSET));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
+    }
+    response
+}
+
+/// `GET /api/version` for `service` at release `version`
+pub fn routes(service: &str, version: &str) -> Router {
+    let versions = ApiVersions {
+        service: service.to_string(),
+        version: version.to_string(),
+        supported: SUPPORTED.iter().map(|version| version.to_string()).collect(),
+        legacy_sunset: Some(LEGACY_SUNSET.to_string()),
+    };
+    let (router, _) = api().split_for_parts();
+    router.with_state(Arc::new(versions))
+}
+
+/// `spec`, whose paths are relative to `/api`, with them moved under
+/// `/api/v1` and `GET /api/version` added
+pub fn document(mut spec: OpenApi) -> OpenApi {
+    let paths = std::mem::take(&mut spec.paths.paths);
+    spec.paths.paths = paths
+        .into_iter()
+        .map(|(path, item)| (format!("/api/{}{}", API_VERSION, path), item))
+        .collect();
+    spec.merge(api().into_openapi());
+    spec
+}
+
+fn api() -> OpenApiRouter<Arc<ApiVersions>> {
+    OpenApiRouter::new().routes(routes!(version))
+}
+
+/// API versions this service serves
+///
+/// The unversioned `/api` paths remain as deprecated aliases of `v1` until
+/// `legacy_sunset`.
+#[utoipa::path(
+    get,
+    path = "/api/version",
+    tag = "version",
+    responses((status = OK, description = "Served API versions", body = ApiVersions))
+)]
+async fn version(State(versions): State<Arc<ApiVersions>>) -> Json<ApiVersions> {
+    Json(versions.as_ref().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        versioned(Router::new().route("/things/{id}", get(|| async { "thing" }))).merge(routes("things", "1.2.3"))
+    }
+
+    async fn get_response(uri: &str) -> Response {
+        app()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_versioned_route_is_current() {
+        let response = get_response("/api/v1/things/a").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(DEPRECATION).is_none());
+        assert!(response.headers().get(SUNSET).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_route_is_deprecated() {
+        let response = get_response("/api/things/a?x=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[DEPRECATION], DEPRECATED_AT);
+        assert_eq!(response.headers()[SUNSET], LEGACY_SUNSET);
+        assert_eq!(
+            response.headers()[header::LINK],
+            "</api/v1/things/a>; rel=\"successor-version\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"thing");
+    }
+
+    #[tokio::test]
+    async fn test_version_endpoint() {
+        let response = get_response("/api/version").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let versions: ApiVersions = serde_json::from_slice(&body).unwrap();
+        assert_eq!(versions.service, "things");
+        assert_eq!(versions.version, "1.2.3");
+        assert!(versions.supports_current());
+        assert_eq!(versions.legacy_sunset.as_deref(), Some(LEGACY_SUNSET));
+    }
+
+    #[test]
+    fn test_document_moves_paths_under_v1() {
+        #[utoipa::path(get, path = "/things/{id}", responses((status = OK)))]
+        #[allow(dead_code)]
+        async fn thing() {}
+
+        let (_, spec) = OpenApiRouter::<()>::new().routes(routes!(thing)).split_for_parts();
+        let paths: Vec<String> = document(spec).paths.paths.into_keys().collect();
+        assert_eq!(paths, vec!["/api/v1/things/{id}", "/api/version"]);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::history::InstanceHistory;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use utoipa::ToSchema;
+
+/// Probes considered by latency rules unless the rule says otherwise
+const DEFAULT_LATENCY_WINDOW: usize = 20;
+
+fn default_latency_window() -> usize {
+    DEFAULT_LATENCY_WINDOW
+}
+
+/// When a rule fires for an instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// The last `checks` probes all failed
+    Down { checks: usize },
+    /// p95 probe latency over the last `window` probes exceeds `threshold_ms`
+    LatencyP95 {
+        threshold_ms: u64,
+        #[serde(default = "default_latency_window")]
+        window: usize,
+    },
+}
+
+/// A named condition, optionally limited to one service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+impl AlertRule {
+    fn applies_to(&self, service: &str) -> bool {
+        self.service.as_deref().is_none_or(|s| s == service)
+    }
+
+    /// A description of the problem when the rule fires
+    fn check(&self, history: &InstanceHistory) -> Option<String> {
+        match self.condition {
+            AlertCondition::Down { checks } => {
+                let failures = history.consecutive_failures();
+                (failures >= checks.max(1)).then(|| format!("{} consecutive failed health checks", failures))
+            }
+            AlertCondition::LatencyP95 { threshold_ms, window } => {
+                let p95 = history.p95_latency_ms(window)?;
+                (p95 > threshold_ms).then(|| format!("p95 probe latency {}ms exceeds {}ms", p95, threshold_ms))
+            }
+        }
+    }
+}
+
+/// Rules used when none are configured
+pub fn default_rules() -> Vec<AlertRule> {
+    vec![AlertRule {
+        name: "instance-down".to_string(),
+        service: None,
+        condition: AlertCondition::Down { checks: 3 },
+    }]
+}
+
+/// Read a JSON array of rules from `path`
+pub fn load_rules(path: &Path) -> anyhow::Result<Vec<AlertRule>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// A rule currently firing for an instance
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Alert {
+    pub rule: String,
+    pub service: String,
+    pub instance: String,
+    pub message: String,
+    /// Unix timestamp (milliseconds) of when the alert started firing
+    pub since: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// Webhook payload, sent when an alert starts firing and when it resolves
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertEvent {
+    pub status: AlertStatus,
+    #[serde(flatten)]
+    pub alert: Alert,
+    pub timestamp: u64,
+}
+
+/// Evaluates rules against probe history and tracks which alerts are firing
+#[derive(Debug)]
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    /// Keyed by (rule, instance)
+    active: HashMap<(String, String), Alert>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            active: HashMap::new(),
+        }
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    /// Firing alerts, oldest first
+    pub fn active(&self) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self.active.values().cloned().collect();
+        alerts.sort_by(|a, b| (a.since, &a.instance, &a.rule).cmp(&(b.since, &b.instance, &b.rule)));
+        alerts
+    }
+
+    /// Re-evaluate every rule for one instance after a probe; returns the
+    /// alerts that started firing or resolved
+    pub fn evaluate(&mut self, service: &str, instance: &str, history: &InstanceHistory, now: u64) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+
+        for rule in self.rules.iter().filter(|rule| rule.applies_to(service)) {
+            let key = (rule.name.clone(), instance.to_string());
+            match (rule.check(history), self.active.contains_key(&key)) {
+                (Some(message), false) => {
+                    let alert = Alert {
+                        rule: rule.name.clone(),
+                        service: service.to_string(),
+                        instance: instance.to_string(),
+                        message,
+                        since: now,
+                    };
+                    self.active.insert(key, alert.clone());
+                    events.push(AlertEvent {
+                        status: AlertStatus::Firing,
+                        alert,
+                        timestamp: now,
+                    });
+                }
+                (None, true) => {
+                    if let Some(alert) = self.active.remove(&key) {
+                        events.push(AlertEvent {
+                            status: AlertStatus::Resolved,
+                            alert,
+                            timestamp: now,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    /// Resolve the alerts of instances that are no longer registered
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool, now: u64) -> Vec<AlertEvent> {
+        let gone: Vec<(String, String)> = self
+            .active
+            .keys()
+            .filter(|(_, instance)| !keep(instance))
+            .cloned()
+            .collect();
+
+        gone.into_iter()
+            .filter_map(|key| self.active.remove(&key))
+            .map(|mut alert| {
+                alert.message = "instance deregistered".to_string();
+                AlertEvent {
+                    status: AlertStatus::Resolved,
+                    alert,
+                    timestamp: now,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Posts alert events to the configured webhook URLs
+#[derive(Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    urls: Vec<String>,
+}
+
+impl Notifier {
+    pub fn new(client: reqwest::Client, urls: Vec<String>) -> Self {
+        Self { client, urls }
+    }
+
+    /// Webhook URLs from the comma-separated `SUPERVISOR_WEBHOOK_URLS`
+    pub fn from_env(client: reqwest::Client) -> Self {
+        let urls = std::env::var("SUPERVISOR_WEBHOOK_URLS")
+            .map(|urls| {
+                urls.split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self::new(client, urls)
+    }
+
+    /// Deliver `event` to every webhook; failures are logged, not retried
+    pub async fn notify(&self, event: &AlertEvent) {
+        for url in &self.urls {
+            match self.client.post(url).json(event).send().await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::warn!("Alert webhook {} returned {}", url, response.status());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to deliver alert to {}: {}", url, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{HealthHistory, Sample};
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    fn record(history: &mut HealthHistory, timestamp: u64, healthy: bool, latency_ms: u64) {
+        history.record(
+            "repl-api-1",
+            Sample {
+                timestamp,
+                healthy,
+                latency_ms,
+            },
+        );
+    }
+
+    #[test]
+    fn test_down_rule_fires_once_and_resolves() {
+        let mut engine = AlertEngine::new(default_rules());
+        let mut history = HealthHistory::new(10);
+
+        for timestamp in 1..=2 {
+            record(&mut history, timestamp, false, 1);
+            let events = engine.evaluate("repl-api", "repl-api-1", history.get("repl-api-1").unwrap(), timestamp);
+            assert!(events.is_empty());
+        }
+
+        record(&mut history, 3, false, 1);
+        let events = engine.evaluate("repl-api", "repl-api-1", history.get("repl-api-1").unwrap(), 3);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, AlertStatus::Firing);
+        assert_eq!(events[0].alert.message, "3 consecutive failed health checks");
+
+        // Still down: already firing, nothing new to send
+        record(&mut history, 4, false, 1);
+        assert!(engine.evaluate("repl-api", "repl-api-1", history.get("repl-api-1").unwrap(), 4).is_empty());
+        assert_eq!(engine.active().len(), 1);
+
+        record(&mut history, 5, true, 1);
+        let events = engine.evaluate("repl-api", "repl-api-1", history.get("repl-api-1").unwrap(), 5);
+        assert_eq!(events[0].status, AlertStatus::Resolved);
+        assert_eq!(events[0].alert.since, 3);
+        assert!(engine.active().is_empty());
+    }
+
+    #[test]
+    fn test_latency_rule_is_scoped_to_service() {
+        let rules: Vec<AlertRule> = serde_json::from_value(serde_json::json!([
+            { "name": "slow-repl", "service": "repl-api", "kind": "latency_p95", "threshold_ms": 500, "window": 5 }
+        ]))
+        .unwrap();
+        let mut engine = AlertEngine::new(rules);
+        let mut history = HealthHistory::new(10);
+        for timestamp in 1..=5 {
+            record(&mut history, timestamp, true, 800);
+        }
+        let instance = history.get("repl-api-1").unwrap();
+
+        assert!(engine.evaluate("container-api", "repl-api-1", instance, 5).is_empty());
+        let events = engine.evaluate("repl-api", "repl-api-1", instance, 5);
+        assert_eq!(events[0].alert.message, "p95 probe latency 800ms exceeds 500ms");
+    }
+
+    #[test]
+    fn test_deregistered_instances_resolve() {
+        let mut engine = AlertEngine::new(default_rules());
+        let mut history = HealthHistory::new(10);
+        for timestamp in 1..=3 {
+            record(&mut history, timestamp, false, 1);
+        }
+        engine.evaluate("repl-api", "repl-api-1", history.get("repl-api-1").unwrap(), 3);
+
+        let events = engine.retain(|_| false, 4);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, AlertStatus::Resolved);
+        assert_eq!(events[0].alert.message, "instance deregistered");
+    }
+
+    #[tokio::test]
+    async fn test_notifier_posts_to_webhook() {
+        let received = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(|State(received): State<Arc<Mutex<Vec<serde_json::Value>>>>, Json(body): Json<serde_json::Value>| async move {
+                    received.lock().unwrap().push(body);
+                }),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let notifier = Notifier::new(reqwest::Client::new(), vec![format!("http://{}/hook", addr)]);
+        notifier
+            .notify(&AlertEvent {
+                status: AlertStatus::Firing,
+                alert: Alert {
+                    rule: "instance-down".to_string(),
+                    service: "repl-api".to_string(),
+                    instance: "repl-api-1".to_string(),
+                    message: "3 consecutive failed health checks".to_string(),
+                    since: 3,
+                },
+                timestamp: 3,
+            })
+            .await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["status"], "firing");
+        assert_eq!(received[0]["rule"], "instance-down");
+        assert_eq!(received[0]["instance"], "repl-api-1");
+    }
+}
@@ -0,0 +1,197 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+/// One probe of one instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Sample {
+    /// Unix timestamp (milliseconds) of the probe
+    pub timestamp: u64,
+    pub healthy: bool,
+    pub latency_ms: u64,
+}
+
+/// When an instance last changed between healthy and unhealthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Transition {
+    pub timestamp: u64,
+    pub healthy: bool,
+}
+
+/// Rolling probe results for a single instance
+#[derive(Debug, Clone)]
+pub struct InstanceHistory {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+    last_transition: Option<Transition>,
+}
+
+impl InstanceHistory {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            last_transition: None,
+        }
+    }
+
+    fn record(&mut self, sample: Sample) {
+        if self.latest().is_none_or(|latest| latest.healthy != sample.healthy) {
+            self.last_transition = Some(Transition {
+                timestamp: sample.timestamp,
+                healthy: sample.healthy,
+            });
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+
+    /// Oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    pub fn last_transition(&self) -> Option<Transition> {
+        self.last_transition
+    }
+
+    /// Share of healthy probes in the retained history
+    pub fn uptime_percent(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let healthy = self.samples.iter().filter(|sample| sample.healthy).count();
+        Some(healthy as f64 * 100.0 / self.samples.len() as f64)
+    }
+
+    /// Failed probes since the last healthy one
+    pub fn consecutive_failures(&self) -> usize {
+        self.samples.iter().rev().take_while(|sample| !sample.healthy).count()
+    }
+
+    /// 95th percentile probe latency over the last `window` probes
+    pub fn p95_latency_ms(&self, window: usize) -> Option<u64> {
+        let mut latencies: Vec<u64> = self
+            .samples
+            .iter()
+            .rev()
+            .take(window)
+            .map(|sample| sample.latency_ms)
+            .collect();
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort_unstable();
+        // Nearest-rank percentile
+        let rank = (latencies.len() * 95).div_ceil(100);
+        Some(latencies[rank.max(1) - 1])
+    }
+}
+
+/// Probe history of every instance the supervisor has seen
+#[derive(Debug)]
+pub struct HealthHistory {
+    capacity: usize,
+    instances: HashMap<String, InstanceHistory>,
+}
+
+impl HealthHistory {
+    /// Keep the last `capacity` probes per instance
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            instances: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, instance: &str, sample: Sample) -> &InstanceHistory {
+        let capacity = self.capacity;
+        let history = self
+            .instances
+            .entry(instance.to_string())
+            .or_insert_with(|| InstanceHistory::new(capacity));
+        history.record(sample);
+        history
+    }
+
+    pub fn get(&self, instance: &str) -> Option<&InstanceHistory> {
+        self.instances.get(instance)
+    }
+
+    /// Drop instances that are no longer registered
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.instances.retain(|instance, _| keep(instance));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u64, healthy: bool, latency_ms: u64) -> Sample {
+        Sample {
+            timestamp,
+            healthy,
+            latency_ms,
+        }
+    }
+
+    #[test]
+    fn test_uptime_and_transitions() {
+        let mut history = HealthHistory::new(10);
+        history.record("a", sample(1, true, 5));
+        history.record("a", sample(2, true, 5));
+        history.record("a", sample(3, false, 5));
+        let instance = history.record("a", sample(4, false, 5));
+
+        assert_eq!(instance.uptime_percent(), Some(50.0));
+        assert_eq!(instance.consecutive_failures(), 2);
+        assert_eq!(
+            instance.last_transition(),
+            Some(Transition {
+                timestamp: 3,
+                healthy: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = HealthHistory::new(3);
+        for timestamp in 0..5 {
+            history.record("a", sample(timestamp, timestamp % 2 == 0, 1));
+        }
+        let instance = history.get("a").unwrap();
+        let timestamps: Vec<u64> = instance.samples().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_p95_latency_uses_window() {
+        let mut history = HealthHistory::new(100);
+        for latency in 1..=100 {
+            history.record("a", sample(latency, true, latency));
+        }
+        let instance = history.get("a").unwrap();
+        assert_eq!(instance.p95_latency_ms(100), Some(95));
+        // The last 10 probes took 91..=100ms
+        assert_eq!(instance.p95_latency_ms(10), Some(100));
+        assert_eq!(HealthHistory::new(1).get("missing").and_then(|h| h.p95_latency_ms(5)), None);
+    }
+
+    #[test]
+    fn test_retain_drops_deregistered_instances() {
+        let mut history = HealthHistory::new(5);
+        history.record("a", sample(1, true, 1));
+        history.record("b", sample(1, true, 1));
+        history.retain(|instance| instance == "b");
+        assert!(history.get("a").is_none());
+        assert!(history.get("b").is_some());
+    }
+}
@@ -0,0 +1,138 @@
+pub mod alerts;
+pub mod history;
+pub mod monitor;
+pub mod probes;
+pub mod remediation;
+
+use alerts::{Alert, AlertRule};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{response::IntoResponse, Json, Router};
+use history::Sample;
+use monitor::{Monitor, ServiceStatus};
+use remediation::{RemediationRecord, RemediationRule};
+use serde::Serialize;
+use service_registry::openapi::Defaults;
+use service_registry::versioning;
+use std::sync::Arc;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[derive(Clone)]
+struct AppState {
+    monitor: Arc<Monitor>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Health probes, alerts and remediation for registered services"),
+    modifiers(&Defaults),
+    tags((name = "supervisor", description = "Probe results, health history, alerts and remediations"))
+)]
+struct ApiDoc;
+
+#[derive(Serialize, ToSchema)]
+struct HealthSummary {
+    services: Vec<ServiceStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AlertsResponse {
+    active: Vec<Alert>,
+    rules: Vec<AlertRule>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RemediationsResponse {
+    records: Vec<RemediationRecord>,
+    rules: Vec<RemediationRule>,
+}
+
+/// The `/api/v1/supervisor` routes and their deprecated `/api/supervisor`
+/// aliases, backed by `monitor`
+pub fn routes(monitor: Arc<Monitor>) -> Router {
+    let (router, _) = api().split_for_parts();
+    versioning::versioned(router).with_state(AppState { monitor })
+}
+
+/// OpenAPI document of the `/api/v1/supervisor` routes
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    versioning::document(api().into_openapi())
+}
+
+fn api() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(status))
+        .routes(routes!(history))
+        .routes(routes!(alerts))
+        .routes(routes!(remediations))
+}
+
+/// Latest probe round, with each instance's uptime and last transition
+#[utoipa::path(
+    get,
+    path = "/supervisor/status",
+    tag = "supervisor",
+    responses((status = OK, description = "Every instance the supervisor probes", body = HealthSummary))
+)]
+async fn status(State(state): State<AppState>) -> impl IntoResponse {
+    Json(HealthSummary {
+        services: state.monitor.statuses().await,
+    })
+}
+
+/// Retained probes of one instance, oldest first
+#[utoipa::path(
+    get,
+    path = "/supervisor/history/{id}",
+    tag = "supervisor",
+    params(("id" = String, Path, description = "Instance id")),
+    responses(
+        (status = OK, description = "Probe samples, oldest first", body = Vec<Sample>),
+        (status = NOT_FOUND, description = "The instance has not been probed", body = String),
+    )
+)]
+async fn history(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
+    match state.monitor.history(&id).await {
+        Some(samples) => Json(samples).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("No probe history for instance '{}'", id)).into_response(),
+    }
+}
+
+/// Firing alerts and the configured rules
+#[utoipa::path(
+    get,
+    path = "/supervisor/alerts",
+    tag = "supervisor",
+    responses((status = OK, description = "Active alerts and rules", body = AlertsResponse))
+)]
+async fn alerts(State(state): State<AppState>) -> impl IntoResponse {
+    let (active, rules) = state.monitor.alerts().await;
+    Json(AlertsResponse { active, rules })
+}
+
+/// Remediation audit trail, newest first
+#[utoipa::path(
+    get,
+    path = "/supervisor/remediations",
+    tag = "supervisor",
+    responses((status = OK, description = "Remediation attempts and rules", body = RemediationsResponse))
+)]
+async fn remediations(State(state): State<AppState>) -> impl IntoResponse {
+    let (records, rules) = state.monitor.remediations().await;
+    Json(RemediationsResponse { records, rules })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_matches_published() {
+        let published = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if let Err(e) = service_registry::openapi::check_published(&openapi(), published) {
+            panic!("{}", e);
+        }
+    }
+}
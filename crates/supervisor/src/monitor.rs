@@ -0,0 +1,422 @@
+use crate::alerts::{self, Alert, AlertEngine, AlertEvent, AlertRule, Notifier};
+use crate::history::{HealthHistory, Sample, Transition};
+use crate::probes::{self, Prober, ProbeSpec};
+use crate::remediation::{self, ActionRunner, RemediationOutcome, RemediationRecord, RemediationRule, Remediator};
+use serde::Serialize;
+use service_registry::ServiceInfo;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+const DEFAULT_INTERVAL_SECS: u64 = 15;
+const DEFAULT_HISTORY_SIZE: usize = 240;
+
+/// Background probing settings
+///
+/// - `SUPERVISOR_PROBE_INTERVAL_SECS`: seconds between probe rounds (default 15)
+/// - `SUPERVISOR_HISTORY_SIZE`: probes kept per instance (default 240, one hour at the default interval)
+/// - `SUPERVISOR_ALERT_RULES`: JSON file of alert rules (default: alert after 3 failed probes)
+/// - `SUPERVISOR_PROBES`: JSON file of probes by service name (see [`probes::resolve`])
+/// - `SUPERVISOR_PROBE_CA_DIR`: directory that `probe.ca_cert` metadata may name CA files in (default: none allowed)
+/// - `SUPERVISOR_REMEDIATION`: JSON file of remediation rules (default: none)
+/// - `SUPERVISOR_REMEDIATION_LOG`: JSONL file every remediation attempt is appended to
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    pub interval: Duration,
+    pub history_size: usize,
+    pub rules: Vec<AlertRule>,
+    pub probes: HashMap<String, ProbeSpec>,
+    pub probe_ca_dir: Option<PathBuf>,
+    pub remediation: Vec<RemediationRule>,
+    pub remediation_log: Option<PathBuf>,
+}
+
+impl MonitorConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let read = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or(default)
+        };
+        let rules = match std::env::var("SUPERVISOR_ALERT_RULES") {
+            Ok(path) => alerts::load_rules(&PathBuf::from(&path))
+                .map_err(|e| anyhow::anyhow!("Failed to load alert rules from {}: {}", path, e))?,
+            Err(_) => alerts::default_rules(),
+        };
+        let probes = match std::env::var("SUPERVISOR_PROBES") {
+            Ok(path) => probes::load_probes(&PathBuf::from(&path))
+                .map_err(|e| anyhow::anyhow!("Failed to load probes from {}: {}", path, e))?,
+            Err(_) => HashMap::new(),
+        };
+        let remediation = match std::env::var("SUPERVISOR_REMEDIATION") {
+            Ok(path) => remediation::load_rules(&PathBuf::from(&path))
+                .map_err(|e| anyhow::anyhow!("Failed to load remediation rules from {}: {}", path, e))?,
+            Err(_) => Vec::new(),
+        };
+        Ok(Self {
+            interval: Duration::from_secs(read("SUPERVISOR_PROBE_INTERVAL_SECS", DEFAULT_INTERVAL_SECS)),
+            history_size: read("SUPERVISOR_HISTORY_SIZE", DEFAULT_HISTORY_SIZE as u64) as usize,
+            rules,
+            probes,
+            probe_ca_dir: std::env::var("SUPERVISOR_PROBE_CA_DIR").ok().map(PathBuf::from),
+            remediation,
+            remediation_log: std::env::var("SUPERVISOR_REMEDIATION_LOG").ok().map(PathBuf::from),
+        })
+    }
+}
+
+/// An instance as the supervisor last saw it
+// Named `InstanceStatus` in the OpenAPI document, apart from service-registry's `ServiceStatus`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[schema(as = InstanceStatus)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub id: String,
+    pub address: String,
+    pub port: u16,
+    pub registered: bool,
+    pub http_health: Option<bool>,
+    pub health_endpoint: Option<String>,
+    pub notes: Option<String>,
+    /// Share of healthy probes in the retained history
+    pub uptime_percent: Option<f64>,
+    pub consecutive_failures: usize,
+    pub last_transition: Option<Transition>,
+    pub latency_ms: Option<u64>,
+    /// Unix timestamp (milliseconds) of the latest probe
+    pub last_checked: Option<u64>,
+}
+
+struct MonitorState {
+    services: Vec<ServiceStatus>,
+    history: HealthHistory,
+    alerts: AlertEngine,
+    remediator: Remediator,
+}
+
+/// Probes registered services on a schedule, keeping their history and alerts
+pub struct Monitor {
+    registry_url: String,
+    registry_client: reqwest::Client,
+    prober: Prober,
+    probes: HashMap<String, ProbeSpec>,
+    probe_ca_dir: Option<PathBuf>,
+    notifier: Notifier,
+    runner: ActionRunner,
+    state: RwLock<MonitorState>,
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Monitor {
+    pub fn new(
+        registry_url: String,
+        registry_client: reqwest::Client,
+        notifier: Notifier,
+        config: &MonitorConfig,
+    ) -> Self {
+        Self {
+            runner: ActionRunner::new(registry_url.clone(), registry_client.clone()),
+            registry_url,
+            registry_client,
+            prober: Prober::new(),
+            probes: config.probes.clone(),
+            probe_ca_dir: config.probe_ca_dir.clone(),
+            notifier,
+            state: RwLock::new(MonitorState {
+                services: Vec::new(),
+                history: HealthHistory::new(config.history_size),
+                alerts: AlertEngine::new(config.rules.clone()),
+                remediator: Remediator::new(config.remediation.clone(), config.remediation_log.clone()),
+            }),
+        }
+    }
+
+    /// Probe every `interval` until the process exits
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.run_once().await;
+            }
+        });
+    }
+
+    async fn registered_services(&self) -> Option<Vec<ServiceInfo>> {
+        let url = format!("{}/api/v1/registry/services", self.registry_url);
+        match self.registry_client.get(&url).send().await {
+            Ok(resp) => match resp.json::<Vec<ServiceInfo>>().await {
+                Ok(svcs) => Some(svcs),
+                Err(e) => {
+                    tracing::warn!("Failed to parse services from registry: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                tracing::warn!("Failed to query service registry: {}", e);
+                None
+            }
+        }
+    }
+
+    /// One probe round; the previous snapshot is kept if the registry is unreachable
+    pub async fn run_once(&self) {
+        let Some(services) = self.registered_services().await else {
+            return;
+        };
+
+        let mut probes = Vec::new();
+        for svc in &services {
+            let spec = probes::resolve(&self.probes, self.probe_ca_dir.as_deref(), svc);
+            let result = match &spec {
+                Some(spec) => Some(self.prober.probe(spec, &svc.address, svc.port).await),
+                None => None,
+            };
+            probes.push((spec, result, now_millis()));
+        }
+
+        let mut events = Vec::new();
+        let mut planned = Vec::new();
+        let mut state = self.state.write().await;
+        let MonitorState {
+            services: statuses,
+            history,
+            alerts,
+            remediator,
+        } = &mut *state;
+
+        statuses.clear();
+        for (svc, (spec, result, timestamp)) in services.iter().zip(probes) {
+            let instance = match &result {
+                Some(probe) => {
+                    let instance = history.record(
+                        &svc.id,
+                        Sample {
+                            timestamp,
+                            healthy: probe.healthy,
+                            latency_ms: probe.latency_ms,
+                        },
+                    );
+                    events.extend(alerts.evaluate(&svc.name, &svc.id, instance, timestamp));
+                    Some(instance)
+                }
+                None => None,
+            };
+            planned.extend(remediator.plan(svc, instance.map(|i| i.consecutive_failures()), timestamp));
+
+            let (health_endpoint, notes) = match (&spec, &result) {
+                (Some(spec), Some(result)) => (Some(spec.endpoint(&svc.address, svc.port)), result.error.clone()),
+                _ => (None, Some("no health check configured".to_string())),
+            };
+            statuses.push(ServiceStatus {
+                name: svc.name.clone(),
+                id: svc.id.clone(),
+                address: svc.address.clone(),
+                port: svc.port,
+                registered: true,
+                http_health: result.as_ref().map(|result| result.healthy),
+                health_endpoint,
+                notes,
+                uptime_percent: instance.and_then(|i| i.uptime_percent()),
+                consecutive_failures: instance.map_or(0, |i| i.consecutive_failures()),
+                last_transition: instance.and_then(|i| i.last_transition()),
+                latency_ms: result.as_ref().map(|result| result.latency_ms),
+                last_checked: result.as_ref().map(|_| timestamp),
+            });
+        }
+
+        let registered: HashSet<&str> = services.iter().map(|svc| svc.id.as_str()).collect();
+        history.retain(|instance| registered.contains(instance));
+        remediator.retain(&registered);
+        events.extend(alerts.retain(|instance| registered.contains(instance), now_millis()));
+        drop(state);
+
+        self.dispatch(events).await;
+        self.remediate(planned).await;
+    }
+
+    /// Run planned remediation actions and record their outcome
+    async fn remediate(&self, planned: Vec<remediation::Planned>) {
+        for planned in planned {
+            let now = now_millis();
+            let (outcome, detail) = match self.runner.run(&planned.rule.action, &planned.service, now).await {
+                Ok(detail) => (RemediationOutcome::Success, detail),
+                Err(e) => (RemediationOutcome::Failed, e),
+            };
+            let mut state = self.state.write().await;
+            state.remediator.record(&planned.rule, &planned.service, outcome, detail, now);
+        }
+    }
+
+    async fn dispatch(&self, events: Vec<AlertEvent>) {
+        for event in events {
+            tracing::warn!(
+                "Alert {:?}: {} on {} ({})",
+                event.status,
+                event.alert.rule,
+                event.alert.instance,
+                event.alert.message
+            );
+            self.notifier.notify(&event).await;
+        }
+    }
+
+    pub async fn statuses(&self) -> Vec<ServiceStatus> {
+        self.state.read().await.services.clone()
+    }
+
+    /// Retained probes of one instance, oldest first
+    pub async fn history(&self, instance: &str) -> Option<Vec<Sample>> {
+        let state = self.state.read().await;
+        state.history.get(instance).map(|history| history.samples().copied().collect())
+    }
+
+    pub async fn alerts(&self) -> (Vec<Alert>, Vec<AlertRule>) {
+        let state = self.state.read().await;
+        (state.alerts.active(), state.alerts.rules().to_vec())
+    }
+
+    /// Remediation attempts, newest first
+    pub async fn remediations(&self) -> (Vec<RemediationRecord>, Vec<RemediationRule>) {
+        let state = self.state.read().await;
+        (state.remediator.records(), state.remediator.rules().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::{AlertCondition, AlertStatus};
+    use axum::{extract::State, http::StatusCode, routing::get, routing::post, Json, Router};
+    use std::sync::Mutex;
+
+    async fn serve(app: Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_failing_instance_raises_alert_webhook() {
+        // container-api instance whose health check always fails
+        let instance = serve(Router::new().route("/healthz", get(|| async { StatusCode::SERVICE_UNAVAILABLE }))).await;
+        let services = vec![ServiceInfo::new("container-api", "container-api-1", "127.0.0.1", instance.port())];
+        let registry = serve(Router::new().route("/api/v1/registry/services", get(move || async move { Json(services) }))).await;
+
+        let received = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let webhook = serve(
+            Router::new()
+                .route(
+                    "/hook",
+                    post(|State(received): State<Arc<Mutex<Vec<serde_json::Value>>>>, Json(body): Json<serde_json::Value>| async move {
+                        received.lock().unwrap().push(body);
+                    }),
+                )
+                .with_state(received.clone()),
+        )
+        .await;
+
+        let config = MonitorConfig {
+            interval: Duration::from_secs(1),
+            history_size: 10,
+            rules: vec![AlertRule {
+                name: "down".to_string(),
+                service: None,
+                condition: AlertCondition::Down { checks: 2 },
+            }],
+            probes: HashMap::new(),
+            probe_ca_dir: None,
+            remediation: Vec::new(),
+            remediation_log: None,
+        };
+        let monitor = Monitor::new(
+            format!("http://{}", registry),
+            reqwest::Client::new(),
+            Notifier::new(reqwest::Client::new(), vec![format!("http://{}/hook", webhook)]),
+            &config,
+        );
+
+        monitor.run_once().await;
+        assert!(received.lock().unwrap().is_empty());
+        monitor.run_once().await;
+
+        let statuses = monitor.statuses().await;
+        assert_eq!(statuses[0].http_health, Some(false));
+        assert_eq!(statuses[0].uptime_percent, Some(0.0));
+        assert_eq!(statuses[0].consecutive_failures, 2);
+        assert_eq!(monitor.history("container-api-1").await.unwrap().len(), 2);
+
+        let (active, _) = monitor.alerts().await;
+        assert_eq!(active.len(), 1);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["status"], serde_json::json!(AlertStatus::Firing));
+        assert_eq!(received[0]["instance"], "container-api-1");
+    }
+
+    #[tokio::test]
+    async fn test_failing_instance_is_marked_unhealthy() {
+        let instance = serve(Router::new().route("/healthz", get(|| async { StatusCode::SERVICE_UNAVAILABLE }))).await;
+        let services = vec![ServiceInfo::new("container-api", "container-api-1", "127.0.0.1", instance.port())];
+        let marked = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let registry = serve(
+            Router::new()
+                .route("/api/v1/registry/services", get(move || async move { Json(services) }))
+                .route(
+                    "/api/v1/registry/admin/services/container-api/container-api-1/status",
+                    axum::routing::put(
+                        |State(marked): State<Arc<Mutex<Vec<serde_json::Value>>>>, Json(body): Json<serde_json::Value>| async move {
+                            marked.lock().unwrap().push(body);
+                        },
+                    ),
+                )
+                .with_state(marked.clone()),
+        )
+        .await;
+
+        let remediation = serde_json::from_value(serde_json::json!([
+            { "name": "quarantine", "action": "mark_unhealthy", "after_failures": 2 }
+        ]))
+        .unwrap();
+        let config = MonitorConfig {
+            interval: Duration::from_secs(1),
+            history_size: 10,
+            rules: Vec::new(),
+            probes: HashMap::new(),
+            probe_ca_dir: None,
+            remediation,
+            remediation_log: None,
+        };
+        let monitor = Monitor::new(
+            format!("http://{}", registry),
+            reqwest::Client::new(),
+            Notifier::new(reqwest::Client::new(), Vec::new()),
+            &config,
+        );
+
+        monitor.run_once().await;
+        assert!(monitor.remediations().await.0.is_empty());
+        monitor.run_once().await;
+        // Still failing, but within the cooldown
+        monitor.run_once().await;
+
+        assert_eq!(*marked.lock().unwrap(), vec![serde_json::json!({ "status": "Unhealthy" })]);
+        let (records, _) = monitor.remediations().await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].outcome, RemediationOutcome::Success);
+        assert_eq!(records[0].instance, "container-api-1");
+    }
+}
@@ -0,0 +1,484 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use service_registry::{HealthCheckKind, ServiceInfo};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// What a probe checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProbeKind {
+    /// GET `path`; healthy on `expect_status` (any 2xx when unset) and, when
+    /// set, a body containing `body_contains`
+    Http {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expect_status: Option<u16>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body_contains: Option<String>,
+    },
+    /// Like `http` over TLS; `insecure` skips certificate verification and
+    /// `ca_cert` verifies against a PEM CA instead of the system roots
+    Https {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expect_status: Option<u16>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body_contains: Option<String>,
+        #[serde(default)]
+        insecure: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ca_cert: Option<PathBuf>,
+    },
+    /// TCP connect to the instance address and port
+    Tcp,
+    /// Podman API `GET /_ping`, which answers `OK`
+    Podman,
+}
+
+/// A probe and its timeout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeSpec {
+    #[serde(flatten)]
+    pub kind: ProbeKind,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl ProbeSpec {
+    fn new(kind: ProbeKind) -> Self {
+        Self {
+            kind,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+        }
+    }
+
+    /// The URL an HTTP-based probe requests, which must stay on the instance's address and port
+    fn url(&self, address: &str, port: u16) -> Result<Url, String> {
+        let (scheme, path) = match &self.kind {
+            ProbeKind::Http { path, .. } => ("http", path.as_str()),
+            ProbeKind::Https { path, .. } => ("https", path.as_str()),
+            ProbeKind::Podman => ("http", "/_ping"),
+            ProbeKind::Tcp => return Err("TCP probes have no URL".to_string()),
+        };
+        let base = Url::parse(&format!("{}://{}:{}/", scheme, address, port))
+            .map_err(|e| format!("invalid instance address {}:{}: {}", address, port, e))?;
+        if !path.starts_with('/') {
+            return Err(format!("probe path '{}' must start with '/'", path));
+        }
+        let url = base.join(path).map_err(|e| format!("invalid probe path '{}': {}", path, e))?;
+        if url.origin() != base.origin() {
+            return Err(format!("probe path '{}' leaves the instance", path));
+        }
+        Ok(url)
+    }
+
+    /// Human-readable target, reported as `health_endpoint`
+    pub fn endpoint(&self, address: &str, port: u16) -> String {
+        match &self.kind {
+            ProbeKind::Http { path, .. } => format!("http://{}:{}{}", address, port, path),
+            ProbeKind::Https { path, .. } => format!("https://{}:{}{}", address, port, path),
+            ProbeKind::Tcp => format!("tcp://{}:{}", address, port),
+            ProbeKind::Podman => format!("http://{}:{}/_ping", address, port),
+        }
+    }
+
+    /// Read a probe from `probe.*` metadata keys, e.g. `probe.type=https`,
+    /// `probe.path=/healthz`, `probe.expect_status=204`, `probe.body_contains=ok`,
+    /// `probe.insecure=true`, `probe.ca_cert=/etc/ca.pem`, `probe.timeout_secs=3`
+    ///
+    /// Metadata comes from whoever registered the instance, so `probe.ca_cert`
+    /// must name a file inside `ca_dir` and is rejected without one.
+    pub fn from_metadata(metadata: &HashMap<String, String>, ca_dir: Option<&Path>) -> Result<Option<Self>, String> {
+        let Some(kind) = metadata.get("probe.type") else {
+            return Ok(None);
+        };
+        let get = |key: &str| metadata.get(&format!("probe.{}", key));
+        let path = get("path").cloned().unwrap_or_else(|| "/".to_string());
+        if !path.starts_with('/') {
+            return Err(format!("probe.path '{}' must start with '/'", path));
+        }
+        let expect_status = get("expect_status")
+            .map(|value| value.parse::<u16>().map_err(|_| format!("invalid probe.expect_status '{}'", value)))
+            .transpose()?;
+        let body_contains = get("body_contains").cloned();
+
+        let kind = match kind.as_str() {
+            "http" => ProbeKind::Http {
+                path,
+                expect_status,
+                body_contains,
+            },
+            "https" => ProbeKind::Https {
+                path,
+                expect_status,
+                body_contains,
+                insecure: get("insecure").is_some_and(|value| value == "true"),
+                ca_cert: get("ca_cert").map(|path| ca_cert_in(Path::new(path), ca_dir)).transpose()?,
+            },
+            "tcp" => ProbeKind::Tcp,
+            "podman" => ProbeKind::Podman,
+            other => return Err(format!("unknown probe.type '{}'", other)),
+        };
+        let timeout_secs = match get("timeout_secs") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("invalid probe.timeout_secs '{}'", value))?,
+            None => DEFAULT_TIMEOUT_SECS,
+        };
+        Ok(Some(Self { kind, timeout_secs }))
+    }
+}
+
+/// `path` resolved inside `ca_dir`, or an error if it points anywhere else
+fn ca_cert_in(path: &Path, ca_dir: Option<&Path>) -> Result<PathBuf, String> {
+    let Some(ca_dir) = ca_dir else {
+        return Err("probe.ca_cert requires SUPERVISOR_PROBE_CA_DIR".to_string());
+    };
+    let outside = || format!("probe.ca_cert '{}' is not inside {}", path.display(), ca_dir.display());
+    let (Ok(dir), Ok(cert)) = (ca_dir.canonicalize(), ca_dir.join(path).canonicalize()) else {
+        return Err(outside());
+    };
+    if cert.starts_with(&dir) && cert.is_file() {
+        Ok(cert)
+    } else {
+        Err(outside())
+    }
+}
+
+/// Probes for services that predate declarative configuration
+fn builtin(service: &str) -> Option<ProbeSpec> {
+    let kind = match service {
+        "container-api" => ProbeKind::Http {
+            path: "/healthz".to_string(),
+            expect_status: None,
+            body_contains: None,
+        },
+        "coreos" => ProbeKind::Podman,
+        _ => return None,
+    };
+    Some(ProbeSpec::new(kind))
+}
+
+/// Per-service probes from the `SUPERVISOR_PROBES` JSON file, keyed by service name
+pub fn load_probes(path: &Path) -> anyhow::Result<HashMap<String, ProbeSpec>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Pick the probe for an instance, first match wins:
+/// 1. the supervisor's probe config for its service name
+/// 2. `probe.*` keys in its metadata, with CA certificates from `ca_dir`
+/// 3. the health check it declared when registering
+/// 4. a built-in probe for container-api and coreos
+pub fn resolve(configured: &HashMap<String, ProbeSpec>, ca_dir: Option<&Path>, svc: &ServiceInfo) -> Option<ProbeSpec> {
+    if let Some(spec) = configured.get(&svc.name) {
+        return Some(spec.clone());
+    }
+    match ProbeSpec::from_metadata(&svc.metadata, ca_dir) {
+        Ok(Some(spec)) => return Some(spec),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Ignoring probe metadata of {}: {}", svc.id, e),
+    }
+    if let Some(check) = &svc.health_check {
+        let kind = match &check.kind {
+            HealthCheckKind::Http { path } => ProbeKind::Http {
+                path: path.clone(),
+                expect_status: None,
+                body_contains: None,
+            },
+            HealthCheckKind::Https { path, insecure } => ProbeKind::Https {
+                path: path.clone(),
+                expect_status: None,
+                body_contains: None,
+                insecure: *insecure,
+                ca_cert: None,
+            },
+            HealthCheckKind::Tcp => ProbeKind::Tcp,
+        };
+        return Some(ProbeSpec {
+            kind,
+            timeout_secs: check.timeout_secs,
+        });
+    }
+    builtin(&svc.name)
+}
+
+/// Result of one probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    /// Why the probe failed
+    pub error: Option<String>,
+}
+
+/// Runs probes, holding one HTTP client per TLS configuration
+pub struct Prober {
+    client: reqwest::Client,
+    insecure_client: reqwest::Client,
+    ca_clients: Mutex<HashMap<PathBuf, reqwest::Client>>,
+}
+
+impl Default for Prober {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Prober {
+    pub fn new() -> Self {
+        let insecure_client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .expect("failed building HTTP client");
+        Self {
+            client: reqwest::Client::new(),
+            insecure_client,
+            ca_clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn ca_client(&self, path: &Path) -> Result<reqwest::Client, String> {
+        let mut clients = self.ca_clients.lock().unwrap();
+        if let Some(client) = clients.get(path) {
+            return Ok(client.clone());
+        }
+        let pem = std::fs::read(path).map_err(|e| format!("failed to read CA {}: {}", path.display(), e))?;
+        let cert = reqwest::Certificate::from_pem(&pem).map_err(|e| format!("invalid CA {}: {}", path.display(), e))?;
+        let client = reqwest::Client::builder()
+            .add_root_certificate(cert)
+            .build()
+            .map_err(|e| e.to_string())?;
+        clients.insert(path.to_path_buf(), client.clone());
+        Ok(client)
+    }
+
+    pub async fn probe(&self, spec: &ProbeSpec, address: &str, port: u16) -> ProbeResult {
+        let started = Instant::now();
+        let timeout = Duration::from_secs(spec.timeout_secs);
+        let endpoint = spec.endpoint(address, port);
+
+        let outcome = async {
+            match &spec.kind {
+                ProbeKind::Http {
+                    expect_status,
+                    body_contains,
+                    ..
+                } => {
+                    let url = spec.url(address, port)?;
+                    check_http(&self.client, url, timeout, *expect_status, body_contains.as_deref()).await
+                }
+                ProbeKind::Https {
+                    expect_status,
+                    body_contains,
+                    insecure,
+                    ca_cert,
+                    ..
+                } => {
+                    let url = spec.url(address, port)?;
+                    let client = match (insecure, ca_cert) {
+                        (true, _) => self.insecure_client.clone(),
+                        (false, Some(path)) => self.ca_client(path)?,
+                        (false, None) => self.client.clone(),
+                    };
+                    check_http(&client, url, timeout, *expect_status, body_contains.as_deref()).await
+                }
+                ProbeKind::Tcp => match tokio::time::timeout(timeout, tokio::net::TcpStream::connect((address, port))).await {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err(e)) => Err(format!("connect failed: {}", e)),
+                    Err(_) => Err("connect timed out".to_string()),
+                },
+                ProbeKind::Podman => {
+                    let url = spec.url(address, port)?;
+                    check_http(&self.client, url, timeout, Some(200), Some("OK")).await
+                }
+            }
+        }
+        .await;
+
+        if let Err(e) = &outcome {
+            tracing::debug!("Probe failed for {}: {}", endpoint, e);
+        }
+        ProbeResult {
+            healthy: outcome.is_ok(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error: outcome.err(),
+        }
+    }
+}
+
+async fn check_http(
+    client: &reqwest::Client,
+    url: Url,
+    timeout: Duration,
+    expect_status: Option<u16>,
+    body_contains: Option<&str>,
+) -> Result<(), String> {
+    let response = client.get(url).timeout(timeout).send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let status_ok = match expect_status {
+        Some(expected) => status.as_u16() == expected,
+        None => status.is_success(),
+    };
+    if !status_ok {
+        return Err(format!("unexpected status {}", status));
+    }
+    if let Some(needle) = body_contains {
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        if !body.contains(needle) {
+            return Err(format!("response body does not contain '{}'", needle));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+    use service_registry::HealthCheck;
+
+    async fn serve(app: Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    fn http(path: &str, expect_status: Option<u16>, body_contains: Option<&str>) -> ProbeSpec {
+        ProbeSpec::new(ProbeKind::Http {
+            path: path.to_string(),
+            expect_status,
+            body_contains: body_contains.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let mut svc = ServiceInfo::new("coreos", "coreos-a", "10.0.0.5", 8085);
+        assert_eq!(resolve(&HashMap::new(), None, &svc).unwrap().kind, ProbeKind::Podman);
+
+        svc = svc.with_health_check(HealthCheck::tcp().with_timeout(1));
+        assert_eq!(
+            resolve(&HashMap::new(), None, &svc),
+            Some(ProbeSpec {
+                kind: ProbeKind::Tcp,
+                timeout_secs: 1
+            })
+        );
+
+        svc.metadata.insert("probe.type".to_string(), "http".to_string());
+        svc.metadata.insert("probe.path".to_string(), "/v1/_ping".to_string());
+        svc.metadata.insert("probe.expect_status".to_string(), "204".to_string());
+        assert_eq!(resolve(&HashMap::new(), None, &svc), Some(http("/v1/_ping", Some(204), None)));
+
+        let configured = HashMap::from([("coreos".to_string(), ProbeSpec::new(ProbeKind::Podman))]);
+        assert_eq!(resolve(&configured, None, &svc).unwrap().kind, ProbeKind::Podman);
+
+        assert_eq!(resolve(&HashMap::new(), None, &ServiceInfo::new("other", "other-1", "h", 1)), None);
+        // repl-api registers its own health check, so it has no built-in probe
+        assert_eq!(resolve(&HashMap::new(), None, &ServiceInfo::new("repl-api", "repl-api-1", "h", 1)), None);
+    }
+
+    #[test]
+    fn test_invalid_metadata_is_rejected() {
+        let metadata = HashMap::from([("probe.type".to_string(), "smtp".to_string())]);
+        assert!(ProbeSpec::from_metadata(&metadata, None).is_err());
+    }
+
+    #[test]
+    fn test_probe_urls_stay_on_the_instance() {
+        assert_eq!(
+            http("/healthz?full=1", None, None).url("10.0.0.5", 8080).unwrap().as_str(),
+            "http://10.0.0.5:8080/healthz?full=1"
+        );
+        assert_eq!(
+            ProbeSpec::new(ProbeKind::Podman).url("10.0.0.5", 8085).unwrap().as_str(),
+            "http://10.0.0.5:8085/_ping"
+        );
+        for path in ["@evil.example/x", "//evil.example/x", "/\\evil.example/x", "", "healthz"] {
+            assert!(http(path, None, None).url("10.0.0.5", 8080).is_err(), "{}", path);
+        }
+
+        let metadata = HashMap::from([
+            ("probe.type".to_string(), "http".to_string()),
+            ("probe.path".to_string(), "@evil.example/x".to_string()),
+        ]);
+        assert!(ProbeSpec::from_metadata(&metadata, None).is_err());
+    }
+
+    #[test]
+    fn test_metadata_ca_cert_must_be_in_the_ca_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca_dir = dir.path().join("ca");
+        std::fs::create_dir(&ca_dir).unwrap();
+        std::fs::write(ca_dir.join("internal.pem"), "pem").unwrap();
+        std::fs::write(dir.path().join("secret.key"), "key").unwrap();
+        let metadata = |ca_cert: &str| {
+            HashMap::from([
+                ("probe.type".to_string(), "https".to_string()),
+                ("probe.ca_cert".to_string(), ca_cert.to_string()),
+            ])
+        };
+
+        let spec = ProbeSpec::from_metadata(&metadata("internal.pem"), Some(&ca_dir)).unwrap().unwrap();
+        assert!(matches!(spec.kind, ProbeKind::Https { ca_cert: Some(path), .. } if path.ends_with("ca/internal.pem")));
+        let absolute = ca_dir.join("internal.pem");
+        assert!(ProbeSpec::from_metadata(&metadata(absolute.to_str().unwrap()), Some(&ca_dir)).is_ok());
+
+        assert!(ProbeSpec::from_metadata(&metadata("internal.pem"), None).is_err());
+        assert!(ProbeSpec::from_metadata(&metadata("../secret.key"), Some(&ca_dir)).is_err());
+        let outside = dir.path().join("secret.key");
+        assert!(ProbeSpec::from_metadata(&metadata(outside.to_str().unwrap()), Some(&ca_dir)).is_err());
+        assert!(ProbeSpec::from_metadata(&metadata("missing.pem"), Some(&ca_dir)).is_err());
+    }
+
+    #[test]
+    fn test_probe_config_file_format() {
+        let probes: HashMap<String, ProbeSpec> = serde_json::from_value(serde_json::json!({
+            "billing": { "type": "https", "path": "/status", "body_contains": "ready", "ca_cert": "/etc/ca.pem", "timeout_secs": 2 },
+            "cache": { "type": "tcp" }
+        }))
+        .unwrap();
+        assert_eq!(probes["cache"], ProbeSpec::new(ProbeKind::Tcp));
+        assert_eq!(probes["billing"].timeout_secs, 2);
+        assert!(matches!(&probes["billing"].kind, ProbeKind::Https { insecure: false, ca_cert: Some(_), .. }));
+    }
+
+    #[tokio::test]
+    async fn test_http_status_and_body_checks() {
+        let addr = serve(
+            Router::new()
+                .route("/ready", get(|| async { "status: ready" }))
+                .route("/accepted", get(|| async { StatusCode::ACCEPTED }))
+                .route("/_ping", get(|| async { "OK" })),
+        )
+        .await;
+        let prober = Prober::new();
+        let probe = |spec: ProbeSpec| {
+            let prober = &prober;
+            async move { prober.probe(&spec, "127.0.0.1", addr.port()).await }
+        };
+
+        assert!(probe(http("/ready", None, Some("ready"))).await.healthy);
+        let result = probe(http("/ready", None, Some("draining"))).await;
+        assert!(!result.healthy);
+        assert_eq!(result.error.as_deref(), Some("response body does not contain 'draining'"));
+
+        assert!(probe(http("/accepted", Some(202), None)).await.healthy);
+        assert!(!probe(http("/accepted", Some(200), None)).await.healthy);
+        assert!(!probe(http("/missing", None, None)).await.healthy);
+
+        assert!(probe(ProbeSpec::new(ProbeKind::Podman)).await.healthy);
+        assert!(probe(ProbeSpec::new(ProbeKind::Tcp)).await.healthy);
+    }
+}
@@ -0,0 +1,705 @@
+use serde::{Deserialize, Serialize};
+use service_registry::{ServiceInfo, ServiceStatus};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+use xxx_types::labels::{reap_reason, MANAGED_LABEL, OWNER_SERVICE};
+
+/// Remediation records kept in memory for `GET /api/supervisor/remediations`
+const RECORD_CAPACITY: usize = 500;
+const HOUR_MILLIS: u64 = 60 * 60 * 1000;
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+fn default_max_per_hour() -> usize {
+    6
+}
+
+/// What to do about an instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RemediationAction {
+    /// Set the instance `Unhealthy` in service-registry, so discovery stops returning it
+    MarkUnhealthy,
+    /// Remove the instance from service-registry regardless of its lease
+    Deregister,
+    /// Restart a named container through the instance's Podman API
+    RestartContainer { container: String },
+    /// Remove execution containers and volumes past their deadline, or whose
+    /// container-api no longer holds a registry lease, through the instance's Podman API
+    ReapOrphans,
+    /// Set an instance that `mark_unhealthy` took out of discovery back to
+    /// `Healthy` once its probe passes; planned by the supervisor, not configured
+    #[serde(skip_deserializing)]
+    RestoreHealthy,
+}
+
+impl RemediationAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RemediationAction::MarkUnhealthy => "mark_unhealthy",
+            RemediationAction::Deregister => "deregister",
+            RemediationAction::RestartContainer { .. } => "restart_container",
+            RemediationAction::ReapOrphans => "reap_orphans",
+            RemediationAction::RestoreHealthy => "restore_healthy",
+        }
+    }
+}
+
+/// An action, the instances it applies to, and its rate limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RemediationRule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Consecutive failed probes before the action runs; without it the
+    /// action runs every probe round, subject to the rate limits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_failures: Option<usize>,
+    /// Minimum seconds between two runs against the same instance
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// Maximum runs of this rule per hour, across all instances
+    #[serde(default = "default_max_per_hour")]
+    pub max_per_hour: usize,
+    #[serde(flatten)]
+    pub action: RemediationAction,
+}
+
+impl RemediationRule {
+    fn triggered(&self, svc: &ServiceInfo, consecutive_failures: Option<usize>) -> bool {
+        if self.service.as_deref().is_some_and(|service| service != svc.name) {
+            return false;
+        }
+        let condition = match self.after_failures {
+            Some(threshold) => consecutive_failures.is_some_and(|failures| failures >= threshold.max(1)),
+            None => true,
+        };
+        // Already out of discovery, nothing to do
+        let pointless = self.action == RemediationAction::MarkUnhealthy && svc.status == ServiceStatus::Unhealthy;
+        condition && !pointless
+    }
+}
+
+/// Read a JSON array of remediation rules from `path`
+pub fn load_rules(path: &Path) -> anyhow::Result<Vec<RemediationRule>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RemediationOutcome {
+    Success,
+    Failed,
+    /// Skipped because the rule used up its hourly budget
+    RateLimited,
+}
+
+/// Audit trail entry for one remediation attempt
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RemediationRecord {
+    /// Unix timestamp (milliseconds)
+    pub timestamp: u64,
+    pub rule: String,
+    pub action: &'static str,
+    pub service: String,
+    pub instance: String,
+    pub outcome: RemediationOutcome,
+    pub detail: String,
+}
+
+/// An action due to run against an instance
+#[derive(Debug, Clone)]
+pub struct Planned {
+    pub rule: RemediationRule,
+    pub service: ServiceInfo,
+}
+
+/// Decides which remediation rules are due and keeps the audit trail
+#[derive(Debug)]
+pub struct Remediator {
+    rules: Vec<RemediationRule>,
+    /// Last run per (rule, instance), for cooldowns
+    last_run: HashMap<(String, String), u64>,
+    /// Instances this supervisor marked unhealthy, with the rule that did it
+    quarantined: HashMap<String, String>,
+    /// Recent runs per rule, for the hourly budget
+    runs: HashMap<String, VecDeque<u64>>,
+    records: VecDeque<RemediationRecord>,
+    log_file: Option<PathBuf>,
+}
+
+impl Remediator {
+    /// `log_file`, when set, receives every record as a JSON line
+    pub fn new(rules: Vec<RemediationRule>, log_file: Option<PathBuf>) -> Self {
+        Self {
+            rules,
+            last_run: HashMap::new(),
+            quarantined: HashMap::new(),
+            runs: HashMap::new(),
+            records: VecDeque::new(),
+            log_file,
+        }
+    }
+
+    /// The actions to run against `svc` now; actions over their hourly budget
+    /// are recorded as rate limited instead
+    ///
+    /// An instance this supervisor marked unhealthy is restored to `Healthy`
+    /// once its probe passes again, instead of running any rule.
+    pub fn plan(&mut self, svc: &ServiceInfo, consecutive_failures: Option<usize>, now: u64) -> Vec<Planned> {
+        if let Some(rule) = self.quarantined.get(&svc.id) {
+            if svc.status != ServiceStatus::Unhealthy {
+                // Restored by someone else
+                self.quarantined.remove(&svc.id);
+            } else if consecutive_failures == Some(0) {
+                let rule = self.rules.iter().find(|r| r.name == *rule).cloned();
+                return rule
+                    .map(|rule| Planned {
+                        rule: RemediationRule {
+                            action: RemediationAction::RestoreHealthy,
+                            ..rule
+                        },
+                        service: svc.clone(),
+                    })
+                    .into_iter()
+                    .collect();
+            }
+        }
+
+        let mut planned = Vec::new();
+        let mut limited = Vec::new();
+
+        for rule in self.rules.iter().filter(|rule| rule.triggered(svc, consecutive_failures)) {
+            let key = (rule.name.clone(), svc.id.clone());
+            if self
+                .last_run
+                .get(&key)
+                .is_some_and(|&last| now.saturating_sub(last) < rule.cooldown_secs * 1000)
+            {
+                continue;
+            }
+
+            let runs = self.runs.entry(rule.name.clone()).or_default();
+            while runs.front().is_some_and(|&run| now.saturating_sub(run) >= HOUR_MILLIS) {
+                runs.pop_front();
+            }
+            if runs.len() >= rule.max_per_hour {
+                limited.push(rule.clone());
+                continue;
+            }
+            runs.push_back(now);
+            self.last_run.insert(key, now);
+            planned.push(Planned {
+                rule: rule.clone(),
+                service: svc.clone(),
+            });
+        }
+
+        for rule in limited {
+            let detail = format!("more than {} runs in the last hour", rule.max_per_hour);
+            self.record(&rule, svc, RemediationOutcome::RateLimited, detail, now);
+        }
+        planned
+    }
+
+    pub fn record(
+        &mut self,
+        rule: &RemediationRule,
+        svc: &ServiceInfo,
+        outcome: RemediationOutcome,
+        detail: String,
+        now: u64,
+    ) {
+        if outcome == RemediationOutcome::Success {
+            match rule.action {
+                RemediationAction::MarkUnhealthy => {
+                    self.quarantined.insert(svc.id.clone(), rule.name.clone());
+                }
+                RemediationAction::RestoreHealthy | RemediationAction::Deregister => {
+                    self.quarantined.remove(&svc.id);
+                }
+                _ => {}
+            }
+        }
+
+        let record = RemediationRecord {
+            timestamp: now,
+            rule: rule.name.clone(),
+            action: rule.action.kind(),
+            service: svc.name.clone(),
+            instance: svc.id.clone(),
+            outcome,
+            detail,
+        };
+        tracing::warn!(
+            "Remediation {} ({}) on {}: {:?}: {}",
+            record.rule,
+            record.action,
+            record.instance,
+            record.outcome,
+            record.detail
+        );
+
+        if let Some(path) = &self.log_file {
+            let written = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(&record).unwrap_or_default()));
+            if let Err(e) = written {
+                tracing::error!("Failed to write remediation log {}: {}", path.display(), e);
+            }
+        }
+
+        if self.records.len() == RECORD_CAPACITY {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Forget instances that are no longer registered
+    pub fn retain(&mut self, registered: &HashSet<&str>) {
+        self.quarantined.retain(|instance, _| registered.contains(instance.as_str()));
+        self.last_run.retain(|(_, instance), _| registered.contains(instance.as_str()));
+    }
+
+    /// Newest first
+    pub fn records(&self) -> Vec<RemediationRecord> {
+        self.records.iter().rev().cloned().collect()
+    }
+
+    pub fn rules(&self) -> &[RemediationRule] {
+        &self.rules
+    }
+}
+
+#[derive(Deserialize)]
+struct PodmanContainer {
+    #[serde(rename = "Id")]
+    id: String,
+    #[serde(rename = "Labels", default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct PodmanVolumes {
+    #[serde(rename = "Volumes", default)]
+    volumes: Option<Vec<PodmanVolume>>,
+}
+
+#[derive(Deserialize)]
+struct PodmanVolume {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Labels", default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+/// Carries out remediation actions against service-registry and Podman
+pub struct ActionRunner {
+    registry_url: String,
+    registry_client: reqwest::Client,
+    client: reqwest::Client,
+}
+
+impl ActionRunner {
+    /// `registry_client` needs an admin registry token when the registry requires tokens
+    pub fn new(registry_url: String, registry_client: reqwest::Client) -> Self {
+        Self {
+            registry_url,
+            registry_client,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Run `action` against `svc`; returns what was done
+    pub async fn run(&self, action: &RemediationAction, svc: &ServiceInfo, now: u64) -> Result<String, String> {
+        let registry_path = format!("{}/api/v1/registry/admin/services/{}/{}", self.registry_url, svc.name, svc.id);
+        let podman = format!("http://{}:{}", svc.address, svc.port);
+
+        match action {
+            RemediationAction::MarkUnhealthy => {
+                let response = self
+                    .registry_client
+                    .put(format!("{}/status", registry_path))
+                    .json(&serde_json::json!({ "status": ServiceStatus::Unhealthy }))
+                    .send()
+                    .await;
+                expect_success(response).await?;
+                Ok("marked Unhealthy".to_string())
+            }
+            RemediationAction::RestoreHealthy => {
+                let response = self
+                    .registry_client
+                    .put(format!("{}/status", registry_path))
+                    .json(&serde_json::json!({ "status": ServiceStatus::Healthy }))
+                    .send()
+                    .await;
+                expect_success(response).await?;
+                Ok("restored Healthy".to_string())
+            }
+            RemediationAction::Deregister => {
+                expect_success(self.registry_client.delete(&registry_path).send().await).await?;
+                Ok("deregistered".to_string())
+            }
+            RemediationAction::RestartContainer { container } => {
+                let response = self
+                    .client
+                    .post(format!("{}/containers/{}/restart", podman, container))
+                    .send()
+                    .await;
+                expect_success(response).await?;
+                Ok(format!("restarted container {}", container))
+            }
+            RemediationAction::ReapOrphans => {
+                let live_owners = self.live_owners().await;
+                let now = now / 1000;
+                let filters = serde_json::json!({ "label": [format!("{}=true", MANAGED_LABEL)] }).to_string();
+
+                let response = self
+                    .client
+                    .get(format!("{}/containers/json", podman))
+                    .query(&[("all", "true"), ("filters", filters.as_str())])
+                    .send()
+                    .await;
+                let containers: Vec<PodmanContainer> = expect_success(response)
+                    .await?
+                    .json()
+                    .await
+                    .map_err(|e| format!("invalid container list: {}", e))?;
+
+                let mut reaped = Vec::new();
+                for container in containers {
+                    let Some(reason) = container
+                        .labels
+                        .and_then(|labels| reap_reason(&labels, now, live_owners.as_ref()))
+                    else {
+                        continue;
+                    };
+                    let response = self
+                        .client
+                        .delete(format!("{}/containers/{}", podman, container.id))
+                        .query(&[("force", "true")])
+                        .send()
+                        .await;
+                    match expect_success(response).await {
+                        Ok(_) => {
+                            xxx_metrics::container_reaped(reason.as_str());
+                            reaped.push(container.id);
+                        }
+                        Err(e) => tracing::warn!("Failed to reap container {}: {}", container.id, e),
+                    }
+                }
+
+                // After the containers, so none still uses a volume being removed
+                let response = self
+                    .client
+                    .get(format!("{}/volumes", podman))
+                    .query(&[("filters", filters.as_str())])
+                    .send()
+                    .await;
+                let volumes: PodmanVolumes = expect_success(response)
+                    .await?
+                    .json()
+                    .await
+                    .map_err(|e| format!("invalid volume list: {}", e))?;
+
+                let mut reaped_volumes = Vec::new();
+                for volume in volumes.volumes.unwrap_or_default() {
+                    let orphaned = volume
+                        .labels
+                        .is_some_and(|labels| reap_reason(&labels, now, live_owners.as_ref()).is_some());
+                    if !orphaned {
+                        continue;
+                    }
+                    let response = self
+                        .client
+                        .delete(format!("{}/volumes/{}", podman, volume.name))
+                        .query(&[("force", "true")])
+                        .send()
+                        .await;
+                    match expect_success(response).await {
+                        Ok(_) => reaped_volumes.push(volume.name),
+                        Err(e) => tracing::warn!("Failed to reap volume {}: {}", volume.name, e),
+                    }
+                }
+
+                Ok(format!(
+                    "reaped {} orphaned container(s): {}; {} volume(s): {}",
+                    reaped.len(),
+                    reaped.join(", "),
+                    reaped_volumes.len(),
+                    reaped_volumes.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// Registry instance ids of the container-api instances that own execution containers
+    ///
+    /// `None` when the registry cannot be queried or lists none, e.g. after it
+    /// lost its entries; owners are then assumed alive and only expired
+    /// containers and volumes are reaped.
+    async fn live_owners(&self) -> Option<HashSet<String>> {
+        let url = format!("{}/api/v1/registry/services/{}", self.registry_url, OWNER_SERVICE);
+        let response = self
+            .registry_client
+            .get(&url)
+            .query(&[("include_unhealthy", "true")])
+            .send()
+            .await;
+        let owners: Vec<ServiceInfo> = match expect_success(response).await {
+            Ok(response) => response.json().await.ok()?,
+            Err(e) => {
+                tracing::warn!("Failed to list {} owners: {}; only reaping expired containers", OWNER_SERVICE, e);
+                return None;
+            }
+        };
+        let owners: HashSet<String> = owners.into_iter().map(|owner| owner.id).collect();
+        (!owners.is_empty()).then_some(owners)
+    }
+}
+
+async fn expect_success(response: reqwest::Result<reqwest::Response>) -> Result<reqwest::Response, String> {
+    let response = response.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(format!("{} {}", status, body.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, State};
+    use axum::routing::{delete, get, put};
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Calls = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    fn rule(action: RemediationAction, after_failures: Option<usize>) -> RemediationRule {
+        RemediationRule {
+            name: action.kind().to_string(),
+            service: Some("coreos".to_string()),
+            after_failures,
+            cooldown_secs: 60,
+            max_per_hour: 2,
+            action,
+        }
+    }
+
+    fn coreos(id: &str, port: u16) -> ServiceInfo {
+        ServiceInfo::new("coreos", id, "127.0.0.1", port).with_status(ServiceStatus::Healthy)
+    }
+
+    async fn serve(app: Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    #[test]
+    fn test_failure_threshold_and_service_filter() {
+        let mut remediator = Remediator::new(vec![rule(RemediationAction::MarkUnhealthy, Some(3))], None);
+        assert!(remediator.plan(&coreos("a", 1), Some(2), 0).is_empty());
+        assert!(remediator.plan(&coreos("a", 1), None, 0).is_empty());
+        assert!(remediator
+            .plan(&ServiceInfo::new("repl-api", "r", "h", 1), Some(5), 0)
+            .is_empty());
+        assert_eq!(remediator.plan(&coreos("a", 1), Some(3), 0).len(), 1);
+
+        // Marking an instance that is already unhealthy is pointless
+        let unhealthy = coreos("b", 1).with_status(ServiceStatus::Unhealthy);
+        assert!(remediator.plan(&unhealthy, Some(3), 0).is_empty());
+    }
+
+    #[test]
+    fn test_cooldown_and_hourly_budget() {
+        let mut remediator = Remediator::new(vec![rule(RemediationAction::Deregister, None)], None);
+        let minute = 60_000;
+
+        assert_eq!(remediator.plan(&coreos("a", 1), None, 0).len(), 1);
+        // Within the cooldown for the same instance
+        assert!(remediator.plan(&coreos("a", 1), None, minute / 2).is_empty());
+        // Another instance has its own cooldown but shares the hourly budget
+        assert_eq!(remediator.plan(&coreos("b", 1), None, minute / 2).len(), 1);
+
+        assert!(remediator.plan(&coreos("a", 1), None, 59 * minute).is_empty());
+        let records = remediator.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].outcome, RemediationOutcome::RateLimited);
+        assert_eq!(records[0].instance, "a");
+
+        // The budget frees up an hour after the first run, and a rate limited
+        // attempt does not start a cooldown
+        assert_eq!(remediator.plan(&coreos("a", 1), None, 60 * minute).len(), 1);
+    }
+
+    #[test]
+    fn test_marked_instance_is_restored_once_healthy() {
+        let mark = rule(RemediationAction::MarkUnhealthy, Some(3));
+        let mut remediator = Remediator::new(vec![mark.clone()], None);
+        let unhealthy = coreos("a", 1).with_status(ServiceStatus::Unhealthy);
+
+        let planned = remediator.plan(&coreos("a", 1), Some(3), 0);
+        assert_eq!(planned.len(), 1);
+        // Nothing to restore until the mark succeeded
+        assert!(remediator.plan(&unhealthy, Some(0), 1).is_empty());
+        remediator.record(&mark, &coreos("a", 1), RemediationOutcome::Success, "marked".to_string(), 1);
+
+        // Still failing: nothing to do
+        assert!(remediator.plan(&unhealthy, Some(4), 2).is_empty());
+        let planned = remediator.plan(&unhealthy, Some(0), 3);
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].rule.action, RemediationAction::RestoreHealthy);
+        assert_eq!(planned[0].rule.name, "mark_unhealthy");
+
+        remediator.record(&planned[0].rule, &unhealthy, RemediationOutcome::Success, "restored".to_string(), 4);
+        assert!(remediator.plan(&coreos("a", 1), Some(0), 5).is_empty());
+        assert_eq!(remediator.records()[0].action, "restore_healthy");
+
+        // Instances set unhealthy by someone else are left alone
+        assert!(remediator.plan(&coreos("b", 1).with_status(ServiceStatus::Unhealthy), Some(0), 6).is_empty());
+    }
+
+    #[test]
+    fn test_restore_healthy_is_not_configurable() {
+        let rules: Result<Vec<RemediationRule>, _> =
+            serde_json::from_value(serde_json::json!([{ "name": "r", "action": "restore_healthy" }]));
+        assert!(rules.is_err());
+    }
+
+    #[test]
+    fn test_records_are_appended_to_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("remediation.jsonl");
+        let rule = rule(RemediationAction::Deregister, None);
+        let mut remediator = Remediator::new(vec![rule.clone()], Some(path.clone()));
+
+        remediator.record(&rule, &coreos("a", 1), RemediationOutcome::Success, "deregistered".to_string(), 1);
+        remediator.record(&rule, &coreos("b", 1), RemediationOutcome::Failed, "404".to_string(), 2);
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["outcome"], "failed");
+        assert_eq!(lines[1]["action"], "deregister");
+        assert_eq!(remediator.records()[0].instance, "b");
+    }
+
+    #[tokio::test]
+    async fn test_mark_unhealthy_calls_registry() {
+        let calls: Calls = Arc::new(Mutex::new(Vec::new()));
+        let registry = serve(
+            Router::new()
+                .route(
+                    "/api/v1/registry/admin/services/{name}/{id}/status",
+                    put(
+                        |State(calls): State<Calls>,
+                         Path((_, id)): Path<(String, String)>,
+                         Json(body): Json<serde_json::Value>| async move {
+                            calls.lock().unwrap().push((id, body));
+                        },
+                    ),
+                )
+                .with_state(calls.clone()),
+        )
+        .await;
+
+        let runner = ActionRunner::new(format!("http://{}", registry), reqwest::Client::new());
+        let detail = runner
+            .run(&RemediationAction::MarkUnhealthy, &coreos("coreos-a", 1), 0)
+            .await
+            .unwrap();
+        assert_eq!(detail, "marked Unhealthy");
+        assert_eq!(
+            calls.lock().unwrap()[0],
+            ("coreos-a".to_string(), serde_json::json!({ "status": "Unhealthy" }))
+        );
+
+        let err = runner
+            .run(&RemediationAction::Deregister, &coreos("coreos-a", 1), 0)
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("404"));
+    }
+
+    #[tokio::test]
+    async fn test_reap_orphans_removes_expired_and_ownerless_executions() {
+        let registry = serve(Router::new().route(
+            "/api/v1/registry/services/container-api",
+            get(|| async { Json(vec![ServiceInfo::new("container-api", "container-api-1", "127.0.0.1", 3000)]) }),
+        ))
+        .await;
+
+        let removed = Arc::new(Mutex::new(Vec::<String>::new()));
+        let execution = |owner: &str, deadline: u64| {
+            serde_json::json!({ "xxx.managed": "true", "xxx.owner": owner, "xxx.deadline": deadline.to_string() })
+        };
+        let containers = serde_json::json!([
+            { "Id": "running", "Labels": execution("container-api-1", 3_000) },
+            { "Id": "expired", "Labels": execution("container-api-1", 1_000) },
+            { "Id": "ownerless", "Labels": execution("container-api-2", 3_000) },
+            { "Id": "unlabelled", "Labels": { "xxx.managed": "true" } }
+        ]);
+        let volumes = serde_json::json!({
+            "Volumes": [
+                { "Name": "in-use", "Labels": execution("container-api-1", 3_000) },
+                { "Name": "left-over", "Labels": execution("container-api-2", 3_000) }
+            ]
+        });
+        let podman = serve(
+            Router::new()
+                .route("/containers/json", get(move || async move { Json(containers) }))
+                .route("/volumes", get(move || async move { Json(volumes) }))
+                .route(
+                    "/containers/{id}",
+                    delete(
+                        |State(removed): State<Arc<Mutex<Vec<String>>>>, Path(id): Path<String>| async move {
+                            removed.lock().unwrap().push(id);
+                        },
+                    ),
+                )
+                .route(
+                    "/volumes/{name}",
+                    delete(
+                        |State(removed): State<Arc<Mutex<Vec<String>>>>, Path(name): Path<String>| async move {
+                            removed.lock().unwrap().push(name);
+                        },
+                    ),
+                )
+                .with_state(removed.clone()),
+        )
+        .await;
+
+        let runner = ActionRunner::new(format!("http://{}", registry), reqwest::Client::new());
+        let detail = runner
+            .run(&RemediationAction::ReapOrphans, &coreos("coreos-a", podman.port()), 2_000_000)
+            .await
+            .unwrap();
+        assert_eq!(detail, "reaped 2 orphaned container(s): expired, ownerless; 1 volume(s): left-over");
+        assert_eq!(*removed.lock().unwrap(), vec!["expired", "ownerless", "left-over"]);
+
+        // Without a registry, owners are assumed alive
+        removed.lock().unwrap().clear();
+        let runner = ActionRunner::new("http://127.0.0.1:1".to_string(), reqwest::Client::new());
+        let detail = runner
+            .run(&RemediationAction::ReapOrphans, &coreos("coreos-a", podman.port()), 2_000_000)
+            .await
+            .unwrap();
+        assert_eq!(detail, "reaped 1 orphaned container(s): expired; 0 volume(s): ");
+    }
+}
@@ -83,8 +83,8 @@ export function APITester() {
                     <Input
                         type="text"
                         name="endpoint"
-                        defaultValue="/api/repl/languages"
-                        placeholder="/api/repl/languages"
+                        defaultValue="/api/v1/repl/languages"
+                        placeholder="/api/v1/repl/languages"
                         flex={1}
                     />
                     <Button type="submit" colorScheme="blue">
@@ -4,7 +4,6 @@ import {
     Button,
     Flex,
     Heading,
-    Input,
     Text,
     Textarea,
     VStack,
@@ -27,9 +26,6 @@ export function ReplTester() {
     const [error, setError] = useState<string | null>(null);
     const [response, setResponse] = useState<ExecuteResponse | null>(null);
     const [rawResponse, setRawResponse] = useState<string>("");
-    const [upstream, setUpstream] = useState<string>(() =>
-        localStorage.getItem("repl_upstream") || "http://localhost:3002"
-    );
 
     const canRun = useMemo(() => !!selectedLang && code.trim().length > 0, [
         selectedLang,
@@ -41,9 +37,7 @@ export function ReplTester() {
             setLoadingLangs(true);
             setError(null);
             try {
-                const url = new URL("/api/repl/languages", location.origin);
-                if (upstream) url.searchParams.set("upstream", upstream);
-                const res = await fetch(url);
+                const res = await fetch("/api/v1/repl/languages");
                 if (!res.ok) throw new Error(`Failed to load languages: ${res.status}`);
                 const data = await res.json();
                 const langs = (data?.languages ?? []) as string[];
@@ -56,7 +50,7 @@ export function ReplTester() {
             }
         };
         load();
-    }, [upstream]);
+    }, []);
 
     const onRun = async () => {
         if (!canRun) return;
@@ -65,9 +59,7 @@ export function ReplTester() {
         setResponse(null);
         setRawResponse("");
         try {
-            const url = new URL("/api/repl/execute", location.origin);
-            if (upstream) url.searchParams.set("upstream", upstream);
-            const res = await fetch(url, {
+            const res = await fetch("/api/v1/repl/execute", {
                 method: "POST",
                 headers: { "Content-Type": "application/json" },
                 body: JSON.stringify({ language: selectedLang, code }),
@@ -146,21 +138,6 @@ export function ReplTester() {
                     </Select.Root>
                     </Box>
                 </HStack>
-                <Spacer />
-                <HStack gap={2} alignItems="center" flex="0 0 auto">
-                    <Text fontWeight="bold" fontSize="sm" flexShrink={0}>Upstream</Text>
-                    <Input
-                        size="sm"
-                        width="300px"
-                        placeholder="http://localhost:3002"
-                        value={upstream}
-                        onChange={(e) => {
-                            const v = e.currentTarget.value;
-                            setUpstream(v);
-                            localStorage.setItem("repl_upstream", v);
-                        }}
-                    />
-                </HStack>
             </Flex>
 
             <Textarea
@@ -194,7 +171,7 @@ export function ReplTester() {
                             {response.success ? "Success" : "Failure"}
                         </Text>
                         <Spacer />
-                        <Text fontSize="sm" color="fg.muted">via /api/repl/execute</Text>
+                        <Text fontSize="sm" color="fg.muted">via /api/v1/repl/execute</Text>
                     </Flex>
                     <Textarea
                         readOnly
@@ -1,16 +1,17 @@
 import { serve } from "bun";
 import index from "./index.html";
 
-const REPL_API_URL = process.env.REPL_API_URL || "http://localhost:3001";
+// The Rust `ui` server, which discovers the services and proxies their APIs
+const UI_API_URL = process.env.UI_API_URL || "http://localhost:4000";
 
-function upstreamFrom(req: Request, fallback: string) {
+// Forward a request as is, streaming both bodies (SSE included)
+async function forward(req: Request) {
   const url = new URL(req.url);
-  const override = url.searchParams.get("upstream");
-  try {
-    return override && override.trim().length > 0 ? override : fallback;
-  } catch {
-    return fallback;
-  }
+  return fetch(`${UI_API_URL}${url.pathname}${url.search}`, {
+    method: req.method,
+    headers: req.headers,
+    body: req.body,
+  });
 }
 
 const server = serve({
@@ -33,40 +34,8 @@ const server = serve({
       },
     },
 
-    // Proxy to the REPL API to avoid CORS in the browser
-    "/api/repl/languages": {
-      async GET(req) {
-        const base = upstreamFrom(req, REPL_API_URL);
-        const upstream = `${base}/api/repl/languages`;
-        const res = await fetch(upstream);
-        return new Response(await res.text(), {
-          status: res.status,
-          headers: { "content-type": res.headers.get("content-type") ?? "application/json" },
-        });
-      },
-    },
-    
-    "/api/repl/execute": {
-      async POST(req) {
-        const base = upstreamFrom(req, REPL_API_URL);
-        const upstream = `${base}/api/repl/execute`;
-        let body: unknown = undefined;
-        try {
-          body = await req.json();
-        } catch (_) {
-          body = undefined;
-        }
-        const res = await fetch(upstream, {
-          method: "POST",
-          headers: { "content-type": "application/json" },
-          body: body ? JSON.stringify(body) : undefined,
-        });
-        return new Response(await res.text(), {
-          status: res.status,
-          headers: { "content-type": res.headers.get("content-type") ?? "application/json" },
-        });
-      },
-    },
+    // Everything else under /api goes through the Rust ui server, avoiding CORS in the browser
+    "/api/*": forward,
 
     "/api/hello/:name": async (req) => {
       const name = req.params.name;
@@ -0,0 +1,79 @@
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use rust_embed::RustEmbed;
+
+/// The built client (`bun run build` in `crates/ui/client`), embedded at compile time
+///
+/// Debug builds read the files from disk instead, so a rebuilt client shows
+/// up without recompiling.
+#[derive(RustEmbed)]
+#[folder = "client/dist/"]
+#[allow_missing = true]
+struct Assets;
+
+const INDEX: &str = "index.html";
+
+/// Serve an embedded asset
+///
+/// Paths without a file extension get `index.html`, so client-side routes
+/// load the app; missing files with an extension are a 404.
+pub async fn serve(uri: Uri, headers: HeaderMap) -> Response {
+    let path = match uri.path().trim_start_matches('/') {
+        "" => INDEX,
+        path => path,
+    };
+    let is_file = path.rsplit('/').next().is_some_and(|name| name.contains('.'));
+
+    let (path, asset) = match Assets::get(path) {
+        Some(asset) => (path, asset),
+        None if is_file => return StatusCode::NOT_FOUND.into_response(),
+        None => match Assets::get(INDEX) {
+            Some(asset) => (INDEX, asset),
+            None => {
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "The UI client has not been built; run `bun install && bun run build` in crates/ui/client",
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    let etag = format!(
+        "\"{}\"",
+        asset.metadata.sha256_hash().iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    );
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|value| value.as_bytes() == etag.as_bytes()) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    // The bundle's file names change with their contents, index.html does not
+    let cache_control = if path == INDEX { "no-cache" } else { "public, max-age=3600" };
+    (
+        [
+            (header::CONTENT_TYPE, asset.metadata.mimetype().to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control.to_string()),
+        ],
+        asset.data,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_missing_files_404_and_routes_get_the_app() {
+        let missing = serve(Uri::from_static("/does-not-exist.js"), HeaderMap::new()).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        // Whether or not the client has been built, a route never 404s
+        let route = serve(Uri::from_static("/some/client/route"), HeaderMap::new()).await;
+        match Assets::get(INDEX) {
+            Some(_) => assert_eq!(route.headers()[header::CONTENT_TYPE], "text/html"),
+            None => assert_eq!(route.status(), StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
@@ -1,3 +1,46 @@
-fn main() {
-    println!("Hello, world!");
+mod assets;
+mod proxy;
+
+use axum::{Router, middleware, routing::{any, get}};
+use proxy::{Upstream, Upstreams};
+use service_registry::{RegistryClientConfig, metrics, telemetry, versioning};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower_http::trace::TraceLayer;
+
+#[tokio::main]
+async fn main() {
+    let _telemetry = telemetry::init("ui");
+
+    // Browser requests to the registry carry their own credentials, not the UI's token
+    let registry_config = RegistryClientConfig {
+        token: None,
+        ..RegistryClientConfig::from_env()
+    };
+    let registry = Upstream {
+        client: registry_config
+            .build_client()
+            .expect("failed building service registry client"),
+        base_url: registry_config.url,
+    };
+
+    let app = Router::new()
+        .route("/healthz", get(|| async { "Ok" }))
+        .route("/metrics", get(metrics::handler))
+        .route("/api/{*path}", any(proxy::forward))
+        .with_state(Arc::new(Upstreams::new(registry)))
+        .merge(versioning::routes("ui", env!("CARGO_PKG_VERSION")))
+        .fallback(assets::serve)
+        .layer(middleware::from_fn_with_state("ui", metrics::track))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span));
+
+    let port = std::env::var("UI_PORT")
+        .ok()
+        .and_then(|value| value.parse::<u16>().ok())
+        .unwrap_or(4000);
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await.unwrap();
+    tracing::info!("ui listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
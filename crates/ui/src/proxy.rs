@@ -0,0 +1,375 @@
+use axum::body::Body;
+use axum::Extension;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures_util::TryStreamExt;
+use service_registry::{HealthCheckKind, ServiceInfo, ServiceSelector};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a discovered instance is reused before asking the registry again
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Name under which the registry's own API is proxied
+const REGISTRY: &str = "service-registry";
+
+/// Headers that describe one connection and are not forwarded
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+];
+
+/// The service behind a proxied request, and the path to request from it
+///
+/// Exposed are repl-api's execution and language routes, container-api's
+/// container routes, the supervisor, and registry discovery reads; the
+/// services check the forwarded `Authorization` header themselves. Audit
+/// logs, secrets, artifacts, blobs and the registry's admin and write routes
+/// stay on the services' own ports. `/api/v1` paths and their unversioned
+/// aliases are forwarded as requested, so the service answers the aliases
+/// with its deprecation headers.
+pub fn route(method: &Method, path: &str) -> Option<(&'static str, String)> {
+    let rest = match path.strip_prefix("/api/v1") {
+        Some(rest) => rest,
+        None => path.strip_prefix("/api")?,
+    };
+
+    let service = if rest == "/repl/languages" || rest == "/repl/execute" || rest == "/repl/execute/stream" {
+        "repl-api"
+    } else if rest == "/containers" || rest.starts_with("/containers/") {
+        "container-api"
+    } else if rest.starts_with("/supervisor/") {
+        "supervisor"
+    } else if method == Method::GET && (rest == "/registry/services" || rest.starts_with("/registry/services/")) {
+        REGISTRY
+    } else {
+        return None;
+    };
+    Some((service, path.to_string()))
+}
+
+/// Where requests for one service go
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub base_url: String,
+    pub client: reqwest::Client,
+}
+
+/// Upstream services, discovered through service-registry
+pub struct Upstreams {
+    registry: Upstream,
+    client: reqwest::Client,
+    insecure_client: reqwest::Client,
+    cache: Mutex<HashMap<String, (Upstream, Instant)>>,
+    ttl: Duration,
+}
+
+impl Upstreams {
+    /// `registry` serves `/api/registry` itself; other services are looked up in it
+    pub fn new(registry: Upstream) -> Self {
+        let insecure_client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .expect("failed building HTTP client");
+
+        Self {
+            registry,
+            client: reqwest::Client::new(),
+            insecure_client,
+            cache: Mutex::new(HashMap::new()),
+            ttl: CACHE_TTL,
+        }
+    }
+
+    /// Use `upstream` for `service` until the cache entry expires
+    pub fn remember(&self, service: &str, upstream: Upstream) {
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(service.to_string(), (upstream, Instant::now()));
+    }
+
+    /// Forget `service`'s instance, e.g. after it failed to answer
+    pub fn forget(&self, service: &str) {
+        self.cache.lock().unwrap().remove(service);
+    }
+
+    async fn resolve(&self, service: &str) -> Option<Upstream> {
+        if service == REGISTRY {
+            return Some(self.registry.clone());
+        }
+
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(service)
+            .filter(|(_, at)| at.elapsed() < self.ttl)
+            .map(|(upstream, _)| upstream.clone());
+        if cached.is_some() {
+            return cached;
+        }
+
+        let services = service_registry::discover_services(service, &ServiceSelector::default()).await?;
+        let upstream = self.upstream_for(services.first()?);
+        self.remember(service, upstream.clone());
+        Some(upstream)
+    }
+
+    /// Base URL of an instance; HTTPS when it registered an HTTPS health check,
+    /// skipping verification when that check does
+    fn upstream_for(&self, service: &ServiceInfo) -> Upstream {
+        let (scheme, client) = match service.health_check.as_ref().map(|check| &check.kind) {
+            Some(HealthCheckKind::Https { insecure: true, .. }) => ("https", &self.insecure_client),
+            Some(HealthCheckKind::Https { .. }) => ("https", &self.client),
+            _ => ("http", &self.client),
+        };
+        Upstream {
+            base_url: format!("{}://{}:{}", scheme, service.address, service.port),
+            client: client.clone(),
+        }
+    }
+}
+
+/// Forward an `/api` request to the service that owns its path
+///
+/// Request and response bodies are streamed, so SSE output reaches the
+/// browser as it is produced. `X-Forwarded-For` is replaced with the
+/// browser's address, so services that trust the UI see who called them and
+/// callers cannot name someone else.
+pub async fn forward(
+    State(upstreams): State<Arc<Upstreams>>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    request: Request,
+) -> Response {
+    let Some((service, path)) = route(request.method(), request.uri().path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(upstream) = upstreams.resolve(service).await else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("No {} instance is registered", service),
+        )
+            .into_response();
+    };
+
+    let mut url = format!("{}{}", upstream.base_url.trim_end_matches('/'), path);
+    if let Some(query) = request.uri().query() {
+        url.push('?');
+        url.push_str(query);
+    }
+
+    let method = reqwest::Method::from_bytes(request.method().as_str().as_bytes())
+        .expect("HTTP methods are valid in both versions of the http crate");
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in request.headers() {
+        if HOP_BY_HOP.contains(&name.as_str()) || name == "x-forwarded-for" {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(name.as_str().as_bytes()),
+            reqwest::header::HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    if let Some(Extension(ConnectInfo(peer))) = peer {
+        headers.insert("x-forwarded-for", peer.ip().to_string().parse().expect("IP addresses are valid headers"));
+    }
+    let body = reqwest::Body::wrap_stream(request.into_body().into_data_stream());
+
+    let response = match upstream.client.request(method, &url).headers(headers).body(body).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Proxying to {} at {} failed: {}", service, url, e);
+            upstreams.forget(service);
+            return (StatusCode::BAD_GATEWAY, format!("{} is not answering", service)).into_response();
+        }
+    };
+
+    let mut builder = Response::builder().status(response.status().as_u16());
+    for (name, value) in response.headers() {
+        if HOP_BY_HOP.contains(&name.as_str()) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_str().as_bytes()),
+            HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            builder = builder.header(name, value);
+        }
+    }
+    let stream = response.bytes_stream().map_err(std::io::Error::other);
+    builder
+        .body(Body::from_stream(stream))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::sse::{Event, Sse};
+    use axum::routing::{any, get, post};
+    use axum::Router;
+    use futures_util::StreamExt;
+    use std::convert::Infallible;
+
+    async fn spawn(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap()
+        });
+        format!("http://{}", addr)
+    }
+
+    fn upstream(base_url: &str) -> Upstream {
+        Upstream {
+            base_url: base_url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    #[test]
+    fn test_route() {
+        let get = &Method::GET;
+        let post = &Method::POST;
+        assert_eq!(route(post, "/api/repl/execute"), Some(("repl-api", "/api/repl/execute".to_string())));
+        assert_eq!(route(get, "/api/repl/languages"), Some(("repl-api", "/api/repl/languages".to_string())));
+        assert_eq!(route(get, "/api/registry/services"), Some((REGISTRY, "/api/registry/services".to_string())));
+        assert_eq!(route(get, "/api/unknown"), None);
+
+        assert_eq!(route(post, "/api/v1/repl/execute/stream"), Some(("repl-api", "/api/v1/repl/execute/stream".to_string())));
+        assert_eq!(
+            route(get, "/api/v1/registry/services/repl-api"),
+            Some((REGISTRY, "/api/v1/registry/services/repl-api".to_string()))
+        );
+        assert_eq!(route(get, "/api/v1/containers"), Some(("container-api", "/api/v1/containers".to_string())));
+        assert_eq!(
+            route(post, "/api/v1/containers/create"),
+            Some(("container-api", "/api/v1/containers/create".to_string()))
+        );
+        assert_eq!(
+            route(get, "/api/supervisor/status"),
+            Some(("supervisor", "/api/supervisor/status".to_string()))
+        );
+        assert_eq!(route(get, "/api/v1/containersx"), None);
+        assert_eq!(route(get, "/api/v1repl/languages"), None);
+        assert_eq!(route(get, "/api/v2/repl/languages"), None);
+    }
+
+    #[test]
+    fn test_route_refuses_operator_apis() {
+        for (method, path) in [
+            (Method::GET, "/api/v1/repl/audit"),
+            (Method::GET, "/api/v1/audit"),
+            (Method::GET, "/api/v1/secrets"),
+            (Method::PUT, "/api/v1/secrets/db"),
+            (Method::GET, "/api/v1/blobs/abc"),
+            (Method::GET, "/api/v1/artifacts/abc"),
+            (Method::POST, "/api/v1/registry/register"),
+            (Method::POST, "/api/v1/registry/keepalive"),
+            (Method::GET, "/api/v1/registry/admin/snapshot"),
+            (Method::DELETE, "/api/v1/registry/admin/services/repl-api/1"),
+            (Method::DELETE, "/api/v1/registry/services/repl-api"),
+        ] {
+            assert_eq!(route(&method, path), None, "{} {}", method, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwards_requests_and_streams_sse() {
+        let repl = spawn(
+            Router::new()
+                .route(
+                    "/api/repl/execute",
+                    post(|headers: axum::http::HeaderMap, body: String| async move {
+                        let header = |name| {
+                            let values: Vec<_> = headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).collect();
+                            values.join(",")
+                        };
+                        let (auth, forwarded) = (header("authorization"), header("x-forwarded-for"));
+                        (StatusCode::CREATED, [("x-upstream", "repl")], format!("{}|{}|{}", auth, forwarded, body))
+                    }),
+                )
+                .route(
+                    "/api/repl/execute/stream",
+                    post(|| async {
+                        let stream = async_stream::stream! {
+                            yield Ok::<_, Infallible>(Event::default().data("first"));
+                            // Never finishes, so only a streaming proxy delivers the first event
+                            futures_util::future::pending::<()>().await;
+                        };
+                        Sse::new(stream)
+                    }),
+                )
+                .route("/api/audit", get(|| async { "repl audit" })),
+        )
+        .await;
+        let registry = spawn(Router::new().route("/api/registry/services", any(|| async { "[]" }))).await;
+
+        let upstreams = Arc::new(Upstreams::new(upstream(&registry)));
+        upstreams.remember("repl-api", upstream(&repl));
+        let ui = spawn(Router::new().route("/api/{*path}", any(forward)).with_state(upstreams)).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/api/repl/execute?x=1", ui))
+            .bearer_auth("t0k")
+            .header("x-forwarded-for", "203.0.113.7")
+            .body("print(1)")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+        assert_eq!(response.headers()["x-upstream"], "repl");
+        // The browser's own X-Forwarded-For is replaced with its address
+        assert_eq!(response.text().await.unwrap(), "Bearer t0k|127.0.0.1|print(1)");
+
+        let audit = client.get(format!("{}/api/repl/audit", ui)).send().await.unwrap();
+        assert_eq!(audit.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let services = client.get(format!("{}/api/registry/services", ui)).send().await.unwrap();
+        assert_eq!(services.text().await.unwrap(), "[]");
+
+        let unknown = client.get(format!("{}/api/nothing", ui)).send().await.unwrap();
+        assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let stream = client
+            .post(format!("{}/api/repl/execute/stream", ui))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stream.headers()["content-type"], "text/event-stream");
+        let first = tokio::time::timeout(Duration::from_secs(5), stream.bytes_stream().next())
+            .await
+            .expect("the first event arrives before the stream ends")
+            .unwrap()
+            .unwrap();
+        assert_eq!(&first[..], b"data: first\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_upstream_is_forgotten() {
+        let upstreams = Arc::new(Upstreams::new(upstream("http://127.0.0.1:1")));
+        upstreams.remember("repl-api", upstream("http://127.0.0.1:1"));
+
+        let ui = spawn(Router::new().route("/api/{*path}", any(forward)).with_state(upstreams.clone())).await;
+        let response = reqwest::get(format!("{}/api/repl/languages", ui)).await.unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+        assert!(upstreams.cache.lock().unwrap().get("repl-api").is_none());
+    }
+}
@@ -0,0 +1,547 @@
+//! Execution audit log shared by container-api and repl-api
+//!
+//! Both services record every execution as an [`AuditRecord`] in a rotated
+//! JSONL log and serve it, filtered and paginated, as `GET /api/v1/audit`.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// How an audited execution ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    /// Ran but exited non-zero
+    Failed,
+    /// Rejected before running, e.g. by code validation or policy
+    Blocked,
+    Timeout,
+    /// Could not be run or observed to completion
+    Error,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failed => "failed",
+            AuditOutcome::Blocked => "blocked",
+            AuditOutcome::Timeout => "timeout",
+            AuditOutcome::Error => "error",
+        }
+    }
+
+    /// Success for exit code 0 (or unknown), Failed otherwise
+    pub fn from_exit_code(exit_code: Option<i64>) -> Self {
+        match exit_code {
+            Some(code) if code != 0 => AuditOutcome::Failed,
+            _ => AuditOutcome::Success,
+        }
+    }
+}
+
+/// A finding from code validation, as recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditVerdict {
+    pub description: String,
+    pub severity: String,
+    pub blocked: bool,
+}
+
+/// One execution, as written to the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditRecord {
+    /// Unix timestamp (milliseconds) of when the execution was requested
+    pub timestamp: u64,
+    /// Service that handled the execution
+    pub service: String,
+    pub caller: String,
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// sha256 of the submitted code or command
+    pub code_hash: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verdicts: Vec<AuditVerdict>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// A successful record stamped with the current time; fill in the rest as the execution ends
+    pub fn new(
+        service: impl Into<String>,
+        caller: impl Into<String>,
+        image: impl Into<String>,
+        code_hash: impl Into<String>,
+    ) -> Self {
+        Self {
+            timestamp: now_millis(),
+            service: service.into(),
+            caller: caller.into(),
+            image: image.into(),
+            language: None,
+            code_hash: code_hash.into(),
+            verdicts: Vec::new(),
+            outcome: AuditOutcome::Success,
+            exit_code: None,
+            duration_ms: 0,
+            container_id: None,
+            host: None,
+            error: None,
+        }
+    }
+
+    /// Set the duration from the record's timestamp to now
+    pub fn finish(mut self, outcome: AuditOutcome) -> Self {
+        self.outcome = outcome;
+        self.duration_ms = now_millis().saturating_sub(self.timestamp);
+        self
+    }
+}
+
+/// An execution in progress; its record is written when the entry is dropped
+///
+/// The record starts out as an `error` outcome, so executions abandoned midway
+/// (for example by a client disconnecting from a stream) are still logged.
+/// Execution metrics are recorded on drop whether or not there is a log.
+#[derive(Debug)]
+pub struct AuditEntry {
+    log: Option<AuditLog>,
+    pub record: AuditRecord,
+}
+
+impl AuditEntry {
+    pub fn new(log: Option<&AuditLog>, mut record: AuditRecord) -> Self {
+        record.outcome = AuditOutcome::Error;
+        record.error = Some("execution did not complete".to_string());
+        Self {
+            log: log.cloned(),
+            record,
+        }
+    }
+
+    pub fn fail(&mut self, outcome: AuditOutcome, error: impl Into<String>) {
+        self.record.outcome = outcome;
+        self.record.error = Some(error.into());
+    }
+
+    pub fn complete(&mut self, exit_code: Option<i64>) {
+        self.record.outcome = AuditOutcome::from_exit_code(exit_code);
+        self.record.exit_code = exit_code;
+        self.record.error = None;
+    }
+}
+
+impl Drop for AuditEntry {
+    fn drop(&mut self) {
+        let outcome = self.record.outcome;
+        let record = self.record.clone().finish(outcome);
+        let language = xxx_metrics::language_label(record.language.as_deref(), &record.image);
+        xxx_metrics::observe_execution(
+            &record.service,
+            &language,
+            outcome.as_str(),
+            Duration::from_millis(record.duration_ms),
+        );
+        if outcome == AuditOutcome::Timeout {
+            xxx_metrics::execution_timed_out(&record.service, &language);
+        }
+        if let Some(log) = &self.log {
+            log.record(record);
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// sha256 of submitted code, hex encoded
+pub fn code_hash(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
+
+/// Filters and pagination for `GET /api/audit`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub caller: Option<String>,
+    pub image: Option<String>,
+    pub language: Option<String>,
+    pub outcome: Option<String>,
+    /// Only records at or after this Unix timestamp (milliseconds)
+    pub since: Option<u64>,
+    /// Only records before this Unix timestamp (milliseconds)
+    pub until: Option<u64>,
+    /// Page size (default 50, at most 500)
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.caller.as_ref().is_none_or(|c| &record.caller == c)
+            && self.image.as_ref().is_none_or(|i| &record.image == i)
+            && self
+                .language
+                .as_ref()
+                .is_none_or(|l| record.language.as_ref().is_some_and(|rl| rl.eq_ignore_ascii_case(l)))
+            && self.outcome.as_ref().is_none_or(|o| record.outcome.as_str() == o)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+/// A page of audit records, newest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditPage {
+    pub records: Vec<AuditRecord>,
+    /// Number of records matching the filters
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Append-only JSONL audit log with size-based rotation
+///
+/// Records go to `<dir>/<name>.jsonl`. Once that file reaches the size limit it
+/// is renamed to `<name>.jsonl.1`, shifting older files up; files beyond the
+/// retention count are deleted. Queries read across all retained files.
+///
+/// Records are written by a background thread, so recording one never blocks
+/// the caller. Queries first wait for the records sent before them, then read
+/// on the blocking thread pool.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    files: Arc<AuditFiles>,
+    writer: mpsc::Sender<Command>,
+}
+
+/// The log's files, owned by its writer thread
+#[derive(Debug)]
+struct AuditFiles {
+    dir: PathBuf,
+    name: String,
+    max_file_bytes: u64,
+    max_files: usize,
+    /// Held for writing while appending or rotating, so queries see whole files
+    lock: RwLock<()>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Append(Box<AuditRecord>),
+    /// Answered once every record sent before it is written
+    Flush(oneshot::Sender<()>),
+}
+
+impl AuditLog {
+    /// Open the log and start its writer thread, which stops with the last clone
+    pub fn open(
+        dir: impl Into<PathBuf>,
+        name: &str,
+        max_file_bytes: u64,
+        max_files: usize,
+    ) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let files = Arc::new(AuditFiles {
+            dir,
+            name: name.to_string(),
+            max_file_bytes,
+            max_files,
+            lock: RwLock::new(()),
+        });
+
+        let (writer, commands) = mpsc::channel();
+        let background = files.clone();
+        std::thread::Builder::new()
+            .name(format!("audit-{}", name))
+            .spawn(move || background.write_all(commands))?;
+        Ok(Self { files, writer })
+    }
+
+    /// Open the log for `name` as configured by the environment
+    ///
+    /// - `AUDIT_DIR`: log directory (default `<tmp>/xxx-audit`)
+    /// - `AUDIT_MAX_FILE_BYTES`: rotation size (default 10 MiB)
+    /// - `AUDIT_MAX_FILES`: rotated files kept besides the current one (default 5)
+    pub fn from_env(name: &str) -> std::io::Result<Self> {
+        let dir = std::env::var("AUDIT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("xxx-audit"));
+        let max_file_bytes = std::env::var("AUDIT_MAX_FILE_BYTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_FILE_BYTES);
+        let max_files = std::env::var("AUDIT_MAX_FILES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_FILES);
+        Self::open(dir, name, max_file_bytes, max_files)
+    }
+
+    /// Queue a record for writing; failures are logged, not returned to the caller
+    pub fn record(&self, record: AuditRecord) {
+        if self.writer.send(Command::Append(Box::new(record))).is_err() {
+            tracing::error!("Audit writer has stopped; dropping audit record");
+        }
+    }
+
+    /// The page of records matching `query`, including every record sent before the call
+    pub async fn query(&self, query: AuditQuery) -> std::io::Result<AuditPage> {
+        let (done, flushed) = oneshot::channel();
+        if self.writer.send(Command::Flush(done)).is_ok() {
+            let _ = flushed.await;
+        }
+        let files = self.files.clone();
+        tokio::task::spawn_blocking(move || files.query(&query))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+}
+
+impl AuditFiles {
+    fn write_all(&self, commands: mpsc::Receiver<Command>) {
+        for command in commands {
+            match command {
+                Command::Append(record) => {
+                    if let Err(e) = self.append(&record) {
+                        tracing::error!("Failed to write audit record: {}", e);
+                    }
+                }
+                Command::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// Path of the current file (`index` 0) or a rotated one
+    fn file(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(format!("{}.jsonl", self.name)),
+            n => self.dir.join(format!("{}.jsonl.{}", self.name, n)),
+        }
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(self.file(self.max_files));
+        for index in (0..self.max_files).rev() {
+            let from = self.file(index);
+            if from.exists() {
+                std::fs::rename(&from, self.file(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&self, record: &AuditRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.write().unwrap();
+        let current = self.file(0);
+        let size = std::fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)?
+            .write_all(&line)
+    }
+
+    /// Only the newest `offset + limit` matches are held while reading
+    fn query(&self, query: &AuditQuery) -> std::io::Result<AuditPage> {
+        let offset = query.offset.unwrap_or(0);
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let keep = offset.saturating_add(limit);
+        let mut newest = VecDeque::new();
+        let mut total = 0;
+
+        let _guard = self.lock.read().unwrap();
+
+        // Oldest file first, so records end up in write order
+        for index in (0..=self.max_files).rev() {
+            let file = match File::open(self.file(index)) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in BufReader::new(file).lines() {
+                match serde_json::from_str::<AuditRecord>(&line?) {
+                    Ok(record) if query.matches(&record) => {
+                        total += 1;
+                        if newest.len() == keep {
+                            newest.pop_front();
+                        }
+                        newest.push_back(record);
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Skipping malformed audit record: {}", e),
+                }
+            }
+        }
+
+        let records = newest.into_iter().rev().skip(offset).take(limit).collect();
+
+        Ok(AuditPage {
+            records,
+            total,
+            offset,
+            limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(caller: &str, timestamp: u64, outcome: AuditOutcome) -> AuditRecord {
+        AuditRecord {
+            timestamp,
+            language: Some("Python".to_string()),
+            ..AuditRecord::new("repl-api", caller, "python:3.11-slim", code_hash("print(1)"))
+                .finish(outcome)
+        }
+    }
+
+    #[test]
+    fn test_code_hash() {
+        assert_eq!(
+            code_hash("hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_paginates_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(dir.path(), "test", 1024 * 1024, 2).unwrap();
+        for ts in 1..=5 {
+            log.record(record("alice", ts, AuditOutcome::Success));
+        }
+        log.record(record("bob", 6, AuditOutcome::Blocked));
+
+        let page = log
+            .query(AuditQuery {
+                caller: Some("alice".to_string()),
+                limit: Some(2),
+                offset: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(
+            page.records.iter().map(|r| r.timestamp).collect::<Vec<_>>(),
+            vec![4, 3]
+        );
+
+        let page = log
+            .query(AuditQuery {
+                outcome: Some("blocked".to_string()),
+                language: Some("python".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.records[0].caller, "bob");
+
+        let page = log
+            .query(AuditQuery {
+                since: Some(2),
+                until: Some(4),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+
+        let page = log
+            .query(AuditQuery {
+                offset: Some(usize::MAX),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 6);
+        assert!(page.records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_bounded_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let line_len = serde_json::to_vec(&record("alice", 1, AuditOutcome::Success))
+            .unwrap()
+            .len() as u64
+            + 1;
+        // Two records per file, two rotated files kept
+        let log = AuditLog::open(dir.path(), "test", line_len * 2, 2).unwrap();
+        for ts in 1..=9 {
+            log.record(record("alice", ts, AuditOutcome::Success));
+        }
+
+        let page = log.query(AuditQuery::default()).await.unwrap();
+        assert!(dir.path().join("test.jsonl.2").exists());
+        assert!(!dir.path().join("test.jsonl.3").exists());
+        assert_eq!(
+            page.records.iter().map(|r| r.timestamp).collect::<Vec<_>>(),
+            vec![9, 8, 7, 6, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entry_is_written_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(dir.path(), "test", 1024 * 1024, 1).unwrap();
+
+        drop(AuditEntry::new(Some(&log), record("alice", 1, AuditOutcome::Success)));
+        let mut entry = AuditEntry::new(Some(&log), record("alice", 2, AuditOutcome::Success));
+        entry.complete(Some(0));
+        drop(entry);
+
+        let page = log.query(AuditQuery::default()).await.unwrap();
+        assert_eq!(page.records[0].outcome, AuditOutcome::Success);
+        assert_eq!(page.records[0].exit_code, Some(0));
+        assert_eq!(page.records[1].outcome, AuditOutcome::Error);
+        assert!(page.records[1].error.is_some());
+    }
+
+    #[test]
+    fn test_outcome_from_exit_code() {
+        assert_eq!(AuditOutcome::from_exit_code(Some(0)), AuditOutcome::Success);
+        assert_eq!(AuditOutcome::from_exit_code(None), AuditOutcome::Success);
+        assert_eq!(AuditOutcome::from_exit_code(Some(137)), AuditOutcome::Failed);
+    }
+}
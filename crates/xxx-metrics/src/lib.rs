@@ -0,0 +1,180 @@
+//! Prometheus metrics for code executions and the containers behind them
+//!
+//! container-api, repl-api, the audit log and the supervisor record these.
+//! They live in the default Prometheus registry, next to the HTTP metrics in
+//! `service_registry::metrics`, so every service's `/metrics` endpoint
+//! exposes whatever its process recorded.
+
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+use xxx_types::repl::Language;
+
+/// Buckets for executions and image pulls, which run from milliseconds to minutes
+const LONG_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Label for image and language values outside the fixed label set
+pub const OTHER: &str = "other";
+
+/// Repositories of repl-api's language images, the only images labelled by name
+const LANGUAGE_IMAGES: &[(&str, Language)] = &[
+    ("python", Language::Python),
+    ("node", Language::Node),
+    ("rust", Language::Rust),
+    ("golang", Language::Go),
+    ("ruby", Language::Ruby),
+];
+
+static EXECUTION_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "execution_duration_seconds",
+        "Wall-clock duration of code executions, by language and outcome",
+        &["service", "language", "outcome"],
+        LONG_BUCKETS.to_vec()
+    )
+    .expect("register execution_duration_seconds")
+});
+
+static EXECUTION_TIMEOUTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "execution_timeouts_total",
+        "Executions terminated for exceeding their time limit",
+        &["service", "language"]
+    )
+    .expect("register execution_timeouts_total")
+});
+
+static IMAGE_PULL_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "image_pull_duration_seconds",
+        "Time spent pulling container images",
+        &["image"],
+        LONG_BUCKETS.to_vec()
+    )
+    .expect("register image_pull_duration_seconds")
+});
+
+static VALIDATION_BLOCKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "validation_blocks_total",
+        "Executions blocked by code validation, by rule",
+        &["rule"]
+    )
+    .expect("register validation_blocks_total")
+});
+
+static PODMAN_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "podman_errors_total",
+        "Errors returned by the Podman API, by operation",
+        &["operation"]
+    )
+    .expect("register podman_errors_total")
+});
+
+static CONTAINERS_REAPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "containers_reaped_total",
+        "Orphaned execution containers removed by the reaper, by reason",
+        &["reason"]
+    )
+    .expect("register containers_reaped_total")
+});
+
+/// The language image `image` was pulled from, ignoring its tag and the Docker Hub prefix
+fn image_language(image: &str) -> Option<(&'static str, &'static Language)> {
+    let image = image.strip_prefix("docker.io/").unwrap_or(image);
+    let image = image.strip_prefix("library/").unwrap_or(image);
+    let repository = image.split(['@', ':']).next().unwrap_or_default();
+    LANGUAGE_IMAGES
+        .iter()
+        .find(|(known, _)| *known == repository)
+        .map(|(known, language)| (*known, language))
+}
+
+/// Bounded label for a caller-supplied image: the repository of a language
+/// image, e.g. `python`, or `other`
+pub fn image_label(image: &str) -> &'static str {
+    image_language(image).map_or(OTHER, |(repository, _)| repository)
+}
+
+/// Bounded label for an execution: its [`Language`], the language of its
+/// image, or `other`
+pub fn language_label(language: Option<&str>, image: &str) -> String {
+    language
+        .and_then(|language| Language::from_str(language).ok())
+        .or_else(|| image_language(image).map(|(_, language)| language.clone()))
+        .map_or_else(|| OTHER.to_string(), |language| language.to_string())
+}
+
+pub fn observe_execution(service: &str, language: &str, outcome: &str, duration: Duration) {
+    EXECUTION_DURATION
+        .with_label_values(&[service, language, outcome])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn execution_timed_out(service: &str, language: &str) {
+    EXECUTION_TIMEOUTS.with_label_values(&[service, language]).inc();
+}
+
+/// Images other than the language images are counted as `other`
+pub fn observe_image_pull(image: &str, duration: Duration) {
+    IMAGE_PULL_DURATION
+        .with_label_values(&[image_label(image)])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn validation_blocked(rule: &str) {
+    VALIDATION_BLOCKS.with_label_values(&[rule]).inc();
+}
+
+pub fn podman_error(operation: &str) {
+    PODMAN_ERRORS.with_label_values(&[operation]).inc();
+}
+
+pub fn container_reaped(reason: &str) {
+    CONTAINERS_REAPED.with_label_values(&[reason]).inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Encoder, TextEncoder};
+
+    fn render() -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&prometheus::gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_recorded_metrics_are_gathered() {
+        validation_blocked("test-rule");
+        podman_error("test-op");
+        observe_execution("test-service", "python", "success", Duration::from_millis(20));
+
+        let text = render();
+        assert!(text.contains(r#"validation_blocks_total{rule="test-rule"} 1"#));
+        assert!(text.contains(r#"podman_errors_total{operation="test-op"} 1"#));
+        assert!(text.contains(
+            r#"execution_duration_seconds_count{language="python",outcome="success",service="test-service"} 1"#
+        ));
+    }
+
+    #[test]
+    fn test_caller_supplied_labels_are_bounded() {
+        assert_eq!(image_label("python:3.11-slim"), "python");
+        assert_eq!(image_label("docker.io/library/golang:1.21-alpine"), "golang");
+        assert_eq!(image_label("ghcr.io/someone/python:latest"), OTHER);
+        assert_eq!(image_label("python-attacker-1234"), OTHER);
+
+        assert_eq!(language_label(Some("Python"), "ignored"), "Python");
+        assert_eq!(language_label(Some("Cobol"), "ruby:3.2-slim"), "Ruby");
+        assert_eq!(language_label(None, "node:20-slim"), "Node");
+        assert_eq!(language_label(None, "alpine:3"), OTHER);
+
+        observe_image_pull("unique-image-a1b2c3:latest", Duration::from_millis(5));
+        assert!(!render().contains("unique-image-a1b2c3"));
+    }
+}
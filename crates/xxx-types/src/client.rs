@@ -0,0 +1,391 @@
+//! Typed async clients for container-api and repl-api
+//!
+//! Both take the base URL of a service, or of the all-in-one `xxx` binary,
+//! and an optional bearer token. Streaming endpoints are returned as a stream
+//! of server-sent [`Event`]s.
+//!
+//! Requests go to the `/api/v1` routes. Calling `negotiate` first checks the
+//! server serves that version, and falls back to the unversioned `/api`
+//! routes of servers released before versioning.
+
+use crate::container::{CreateContainerRequest, CreateContainerResponse, RemoveContainerResponse};
+use crate::repl::{ExecuteReplRequest, ExecuteReplResponse, LanguagesResponse};
+use crate::version::{ApiVersions, API_VERSION};
+use futures_util::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::pin::Pin;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request was not answered, or the answer was not the expected type
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// The service answered with an error status; `message` is the response body
+    #[error("{status}: {message}")]
+    Status { status: u16, message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A server-sent event; `event` is `None` for plain output
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Event {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Events of a streaming endpoint, in the order the service sent them
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<Event>> + Send>>;
+
+/// What a server said about the API versions it serves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Negotiation {
+    /// The server serves [`API_VERSION`]
+    Compatible(ApiVersions),
+    /// The server answered without listing [`API_VERSION`]
+    Incompatible(ApiVersions),
+    /// The server predates versioning and only serves the unversioned `/api` routes
+    Unversioned,
+}
+
+#[derive(Debug, Clone)]
+struct Api {
+    base_url: String,
+    /// Prepended to every path; `/api/v1` unless negotiation fell back
+    prefix: String,
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl Api {
+    fn new(base_url: String, client: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            prefix: format!("/api/{}", API_VERSION),
+            client,
+            token: None,
+        }
+    }
+
+    /// Ask the server for its versions, switching to the unversioned routes
+    /// when it has none
+    async fn negotiate(&mut self) -> Result<Negotiation> {
+        let negotiation = negotiate(&self.client, &self.base_url).await?;
+        if negotiation == Negotiation::Unversioned {
+            self.prefix = "/api".to_string();
+        }
+        Ok(negotiation)
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}{}{}", self.base_url, self.prefix, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = send(self.request(reqwest::Method::GET, path)).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let response = send(self.request(reqwest::Method::POST, path).json(body)).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn post_stream<B: Serialize>(&self, path: &str, body: &B) -> Result<EventStream> {
+        let response = send(self.request(reqwest::Method::POST, path).json(body)).await?;
+        Ok(events(response))
+    }
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let response = request.send().await?;
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let message = response.text().await.unwrap_or_default();
+    Err(Error::Status { status, message })
+}
+
+/// Ask the server at `base_url` which API versions it serves
+///
+/// For clients of services without a typed client here, such as
+/// service-registry; the typed clients have their own `negotiate`.
+pub async fn negotiate(client: &reqwest::Client, base_url: &str) -> Result<Negotiation> {
+    let request = client.get(format!("{}/api/version", base_url.trim_end_matches('/')));
+    let versions: ApiVersions = match send(request).await {
+        Ok(response) => response.json().await?,
+        Err(Error::Status { status: 404, .. }) => return Ok(Negotiation::Unversioned),
+        Err(e) => return Err(e),
+    };
+    if versions.supports_current() {
+        Ok(Negotiation::Compatible(versions))
+    } else {
+        Ok(Negotiation::Incompatible(versions))
+    }
+}
+
+/// Split a `text/event-stream` body into events, separated by blank lines
+fn events(response: reqwest::Response) -> EventStream {
+    let mut body = response.bytes_stream();
+    Box::pin(async_stream::stream! {
+        // Bytes, not text, so characters split across chunks stay intact
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    yield Err(e.into());
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                if let Some(event) = parse_event(&String::from_utf8_lossy(&block)) {
+                    yield Ok(event);
+                }
+            }
+        }
+    })
+}
+
+/// One event from its `event:` and `data:` lines; comments are skipped
+fn parse_event(block: &str) -> Option<Event> {
+    let mut event = Event::default();
+    let mut data: Vec<&str> = Vec::new();
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event.event = Some(value.to_string()),
+            "data" => data.push(value),
+            _ => {}
+        }
+    }
+    if data.is_empty() && event.event.is_none() {
+        return None;
+    }
+    event.data = data.join("\n");
+    Some(event)
+}
+
+/// Client for container-api
+#[derive(Debug, Clone)]
+pub struct ContainerClient {
+    api: Api,
+}
+
+impl ContainerClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured client, e.g. one trusting a private CA
+    pub fn with_client(base_url: impl Into<String>, client: reqwest::Client) -> Self {
+        Self {
+            api: Api::new(base_url.into(), client),
+        }
+    }
+
+    /// Send `token` as the caller's bearer token
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.api.token = token;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.api.base_url
+    }
+
+    /// Check the server serves [`API_VERSION`]; see [`Negotiation`]
+    pub async fn negotiate(&mut self) -> Result<Negotiation> {
+        self.api.negotiate().await
+    }
+
+    /// Names of the running containers, one list per container
+    pub async fn list_containers(&self) -> Result<Vec<Vec<String>>> {
+        self.api.get("/containers/list").await
+    }
+
+    /// Run an execution and wait for it to finish
+    pub async fn create_container(&self, request: &CreateContainerRequest) -> Result<CreateContainerResponse> {
+        self.api.post("/containers/create", request).await
+    }
+
+    /// Run an execution, streaming its output and `network`, `truncated`,
+    /// `exit` and `artifacts` events
+    pub async fn create_container_stream(&self, request: &CreateContainerRequest) -> Result<EventStream> {
+        self.api.post_stream("/containers/create/stream", request).await
+    }
+
+    pub async fn remove_container(&self, id: &str) -> Result<RemoveContainerResponse> {
+        let request = self.api.request(reqwest::Method::DELETE, &format!("/containers/{}", id));
+        Ok(send(request).await?.json().await?)
+    }
+}
+
+/// Client for repl-api
+#[derive(Debug, Clone)]
+pub struct ReplClient {
+    api: Api,
+}
+
+impl ReplClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured client, e.g. one trusting repl-api's CA
+    pub fn with_client(base_url: impl Into<String>, client: reqwest::Client) -> Self {
+        Self {
+            api: Api::new(base_url.into(), client),
+        }
+    }
+
+    /// Send `token` as the bearer token
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.api.token = token;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.api.base_url
+    }
+
+    /// Check the server serves [`API_VERSION`]; see [`Negotiation`]
+    pub async fn negotiate(&mut self) -> Result<Negotiation> {
+        self.api.negotiate().await
+    }
+
+    pub async fn list_languages(&self) -> Result<LanguagesResponse> {
+        self.api.get("/repl/languages").await
+    }
+
+    /// Run code and wait for its output
+    pub async fn execute(&self, request: &ExecuteReplRequest) -> Result<ExecuteReplResponse> {
+        self.api.post("/repl/execute", request).await
+    }
+
+    /// Run code, streaming its output until a `done` event
+    ///
+    /// Failures, including blocked code, arrive as `ERROR: ...` data.
+    pub async fn execute_stream(&self, request: &ExecuteReplRequest) -> Result<EventStream> {
+        self.api.post_stream("/repl/execute/stream", request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repl::Language;
+
+    #[test]
+    fn test_parse_event() {
+        assert_eq!(
+            parse_event("data: hello\n\n"),
+            Some(Event {
+                event: None,
+                data: "hello".to_string()
+            })
+        );
+        assert_eq!(
+            parse_event("event: exit\ndata: {\"exit_code\":0}\n\n"),
+            Some(Event {
+                event: Some("exit".to_string()),
+                data: r#"{"exit_code":0}"#.to_string()
+            })
+        );
+        assert_eq!(parse_event("data: one\ndata:  two\n\n").unwrap().data, "one\n two");
+        assert_eq!(parse_event(": keep-alive\n\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_streams_events_across_chunks() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v1/repl/execute/stream")
+            .match_header("authorization", "Bearer t0k")
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| {
+                w.write_all(b"data: caf\xc3")?;
+                w.flush()?;
+                w.write_all(b"\xa9\n\nevent: done\ndata: \n\n")
+            })
+            .create_async()
+            .await;
+
+        let client = ReplClient::new(format!("{}/", server.url())).with_token(Some("t0k".to_string()));
+        let events: Vec<Event> = client
+            .execute_stream(&ExecuteReplRequest::new(Language::Python, "print('café')"))
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events[0].data, "café");
+        assert_eq!(events[1].event.as_deref(), Some("done"));
+        assert_eq!(events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_error_status_keeps_the_body() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("DELETE", "/api/v1/containers/abc")
+            .with_status(404)
+            .with_body("Container 'abc' not found on any Podman host")
+            .create_async()
+            .await;
+
+        let err = ContainerClient::new(server.url()).remove_container("abc").await.unwrap_err();
+        assert!(matches!(err, Error::Status { status: 404, .. }));
+        assert_eq!(err.to_string(), "404: Container 'abc' not found on any Podman host");
+    }
+
+    #[tokio::test]
+    async fn test_negotiation() {
+        let mut server = mockito::Server::new_async().await;
+        let version = server
+            .mock("GET", "/api/version")
+            .with_body(r#"{"service":"repl-api","version":"0.2.0","supported":["v1"]}"#)
+            .create_async()
+            .await;
+        let mut client = ReplClient::new(server.url());
+        let Negotiation::Compatible(versions) = client.negotiate().await.unwrap() else {
+            panic!("v1 server reported as incompatible");
+        };
+        assert_eq!(versions.service, "repl-api");
+        version.remove_async().await;
+
+        server
+            .mock("GET", "/api/version")
+            .with_body(r#"{"service":"repl-api","version":"9.0.0","supported":["v9"]}"#)
+            .create_async()
+            .await;
+        assert!(matches!(client.negotiate().await.unwrap(), Negotiation::Incompatible(_)));
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_server_uses_legacy_routes() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/api/version").with_status(404).create_async().await;
+        let languages = server
+            .mock("GET", "/api/repl/languages")
+            .with_body(r#"{"languages":["Python"]}"#)
+            .create_async()
+            .await;
+
+        let mut client = ReplClient::new(server.url());
+        assert_eq!(client.negotiate().await.unwrap(), Negotiation::Unversioned);
+        assert_eq!(client.list_languages().await.unwrap().languages, vec!["Python"]);
+        languages.assert_async().await;
+    }
+}
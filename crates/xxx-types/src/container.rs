@@ -0,0 +1,175 @@
+//! container-api requests and responses
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Network egress requested for an execution
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum NetworkPolicy {
+    /// Private network namespace with no interfaces besides loopback
+    #[default]
+    None,
+    /// Unrestricted egress; admin callers only
+    Full,
+}
+
+impl NetworkPolicy {
+    pub fn is_none(&self) -> bool {
+        *self == NetworkPolicy::None
+    }
+}
+
+/// How an execution's network namespace was set up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// Private network namespace with no interfaces besides loopback
+    #[default]
+    None,
+    /// The default bridge network with unrestricted egress
+    Full,
+}
+
+impl NetworkMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkMode::None => "none",
+            NetworkMode::Full => "full",
+        }
+    }
+}
+
+/// The policy actually applied to an execution, reported back to the caller
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AppliedNetwork {
+    pub mode: NetworkMode,
+}
+
+/// Uploaded blobs exposed read-only in a directory of the execution container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct InputMount {
+    /// Absolute directory the files appear in
+    pub path: String,
+    /// File name within `path` → blob id
+    pub files: BTreeMap<String, String>,
+}
+
+/// A file collected from an execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ArtifactInfo {
+    /// sha256 of the contents
+    pub id: String,
+    /// Path of the file inside the container
+    pub path: String,
+    pub size: u64,
+    pub created_at: u64,
+}
+
+impl ArtifactInfo {
+    /// File name used when serving the artifact
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CreateContainerRequest {
+    pub image: String,
+    /// Command to run instead of the image's default
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Network egress for the execution; isolated when omitted
+    #[serde(default, skip_serializing_if = "NetworkPolicy::is_none")]
+    pub network: NetworkPolicy,
+    /// Absolute glob paths of files to collect after the execution
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+    /// Uploaded blobs to mount read-only for the execution
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<InputMount>,
+    /// Plain environment variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Environment variable name → name of a secret held by container-api
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// Result of a finished execution
+// Only `id` and `message` were sent from the start, so everything else is
+// defaulted when missing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CreateContainerResponse {
+    pub id: String,
+    #[serde(default)]
+    pub execution_id: String,
+    /// Podman host the execution ran on
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub network: AppliedNetwork,
+    #[serde(default)]
+    pub artifacts: Vec<ArtifactInfo>,
+    pub message: String,
+    /// Combined stdout and stderr, with secret values masked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+    /// Whether the output limits cut any output
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RemoveContainerResponse {
+    pub id: String,
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_modes() {
+        let policy: NetworkPolicy = serde_json::from_str(r#"{"mode":"full"}"#).unwrap();
+        assert_eq!(policy, NetworkPolicy::Full);
+        assert!(serde_json::from_str::<NetworkPolicy>(r#"{"mode":"open"}"#).is_err());
+        assert_eq!(serde_json::to_string(&NetworkPolicy::Full).unwrap(), r#"{"mode":"full"}"#);
+    }
+
+    #[test]
+    fn test_create_request_omits_defaults() {
+        let request = CreateContainerRequest {
+            image: "nginx:latest".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({"image": "nginx:latest", "command": null})
+        );
+    }
+
+    #[test]
+    fn test_artifact_file_name() {
+        let info = ArtifactInfo {
+            id: "abc".to_string(),
+            path: "/work/out/report.csv".to_string(),
+            size: 1,
+            created_at: 0,
+        };
+        assert_eq!(info.file_name(), "report.csv");
+    }
+}
@@ -0,0 +1,90 @@
+//! Podman labels on what container-api creates for an execution
+//!
+//! container-api labels every execution container and input volume, and both
+//! its own reaper and the supervisor's `reap_orphans` remediation decide from
+//! these labels alone whether to remove one, so they agree on what is orphaned.
+
+use std::collections::{HashMap, HashSet};
+
+/// Label on every container and volume created for executions
+pub const MANAGED_LABEL: &str = "xxx.managed";
+
+/// Registry instance id of the container-api that started the execution
+pub const OWNER_LABEL: &str = "xxx.owner";
+
+/// Name of the caller that requested the execution
+pub const CALLER_LABEL: &str = "xxx.caller";
+
+/// Id of the execution, also returned as `execution_id` by `POST /api/containers/create`
+pub const EXECUTION_LABEL: &str = "xxx.execution";
+
+/// Unix time (seconds) after which the container may be reaped
+pub const DEADLINE_LABEL: &str = "xxx.deadline";
+
+/// Service whose registered instances own execution containers
+pub const OWNER_SERVICE: &str = "container-api";
+
+/// Why a managed container or volume is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReapReason {
+    /// Still around after its deadline
+    Expired,
+    /// Its container-api instance no longer holds a registry lease
+    OwnerGone,
+}
+
+impl ReapReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReapReason::Expired => "expired",
+            ReapReason::OwnerGone => "owner_gone",
+        }
+    }
+}
+
+/// Decide whether a managed container or volume should be removed
+///
+/// `live_owners` is `None` when the registry could not be queried; owners are
+/// then assumed alive. Anything without an owner or deadline label is only
+/// reaped on the criteria it carries.
+pub fn reap_reason(
+    labels: &HashMap<String, String>,
+    now: u64,
+    live_owners: Option<&HashSet<String>>,
+) -> Option<ReapReason> {
+    let deadline = labels.get(DEADLINE_LABEL).and_then(|value| value.parse::<u64>().ok());
+    if deadline.is_some_and(|deadline| deadline < now) {
+        return Some(ReapReason::Expired);
+    }
+
+    match (labels.get(OWNER_LABEL), live_owners) {
+        (Some(owner), Some(live)) if !live.contains(owner) => Some(ReapReason::OwnerGone),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_reap_reason() {
+        let live = HashSet::from(["container-api-1".to_string()]);
+        let running = labels(&[(OWNER_LABEL, "container-api-1"), (DEADLINE_LABEL, "100")]);
+        assert_eq!(reap_reason(&running, 100, Some(&live)), None);
+        assert_eq!(reap_reason(&running, 101, Some(&live)), Some(ReapReason::Expired));
+
+        let orphaned = labels(&[(OWNER_LABEL, "container-api-2"), (DEADLINE_LABEL, "100")]);
+        assert_eq!(reap_reason(&orphaned, 50, Some(&live)), Some(ReapReason::OwnerGone));
+        // Owners are assumed alive while the registry is unreachable
+        assert_eq!(reap_reason(&orphaned, 50, None), None);
+        assert_eq!(reap_reason(&orphaned, 101, None), Some(ReapReason::Expired));
+
+        // Containers from before these labels existed carry neither criterion
+        assert_eq!(reap_reason(&labels(&[(MANAGED_LABEL, "true")]), 1_000, Some(&live)), None);
+    }
+}
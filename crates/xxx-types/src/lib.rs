@@ -0,0 +1,22 @@
+//! Wire types shared by the services and their clients
+//!
+//! container-api and repl-api serve these types, and the CLI sends and reads
+//! them through [`client`], so both sides of every request agree on one
+//! definition. Fields added after a type first shipped are optional or
+//! defaulted, so older payloads keep deserializing; `tests/compat.rs` holds
+//! the payloads of each wire version. [`labels`] holds the Podman labels
+//! container-api puts on execution containers, which the supervisor reads.
+
+pub mod client;
+pub mod container;
+pub mod labels;
+pub mod repl;
+pub mod version;
+
+pub use client::{ContainerClient, Negotiation, ReplClient};
+pub use container::{
+    AppliedNetwork, ArtifactInfo, CreateContainerRequest, CreateContainerResponse, InputMount, NetworkMode,
+    NetworkPolicy, RemoveContainerResponse,
+};
+pub use repl::{ExecuteReplRequest, ExecuteReplResponse, Language, LanguagesResponse};
+pub use version::{ApiVersions, API_VERSION};
@@ -0,0 +1,125 @@
+//! repl-api requests and responses
+
+use crate::container::NetworkPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub enum Language {
+    Python,
+    Node,
+    Rust,
+    Go,
+    Ruby,
+}
+
+impl Language {
+    /// Every supported language, in the order repl-api lists them
+    pub const ALL: [Language; 5] = [
+        Language::Python,
+        Language::Node,
+        Language::Rust,
+        Language::Go,
+        Language::Ruby,
+    ];
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A language name [`Language`] does not know
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Unknown language: {0}")]
+pub struct UnknownLanguage(pub String);
+
+impl FromStr for Language {
+    type Err = UnknownLanguage;
+
+    /// Case-insensitive language name, e.g. `python`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Language::ALL
+            .into_iter()
+            .find(|language| language.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownLanguage(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ExecuteReplRequest {
+    pub language: Language,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Network policy passed through to container-api; isolated when omitted
+    #[serde(default, skip_serializing_if = "NetworkPolicy::is_none")]
+    pub network: NetworkPolicy,
+    /// Environment variables for the user code
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Environment variable name → secret name; values are injected and masked by container-api
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+}
+
+impl ExecuteReplRequest {
+    /// Run `code` with no dependencies, isolated from the network
+    pub fn new(language: Language, code: impl Into<String>) -> Self {
+        Self {
+            language,
+            code: code.into(),
+            dependencies: Vec::new(),
+            network: NetworkPolicy::None,
+            env: BTreeMap::new(),
+            secrets: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ExecuteReplResponse {
+    pub result: String,
+    pub success: bool,
+    /// Whether container-api's output limits cut `result`
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct LanguagesResponse {
+    pub languages: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_names_round_trip() {
+        for language in Language::ALL {
+            let json = serde_json::to_string(&language).unwrap();
+            assert_eq!(json, format!("\"{}\"", language));
+            assert_eq!(language.to_string().to_lowercase().parse::<Language>(), Ok(language));
+        }
+        assert_eq!(
+            "javascript".parse::<Language>().unwrap_err().to_string(),
+            "Unknown language: javascript"
+        );
+    }
+
+    #[test]
+    fn test_execute_request_omits_defaults() {
+        let json = serde_json::to_value(ExecuteReplRequest::new(Language::Python, "print(1)")).unwrap();
+        assert_eq!(json, serde_json::json!({"language": "Python", "code": "print(1)"}));
+    }
+}
@@ -0,0 +1,33 @@
+//! API versions a service serves
+//!
+//! Every service answers `GET /api/version` with [`ApiVersions`], so clients
+//! can tell before sending anything whether the server speaks their version.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// The API version these types describe, served under `/api/<version>`
+pub const API_VERSION: &str = "v1";
+
+/// Answer of `GET /api/version`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ApiVersions {
+    /// Name of the answering service
+    pub service: String,
+    /// Release of the answering service
+    pub version: String,
+    /// API versions served, each under `/api/<version>`
+    pub supported: Vec<String>,
+    /// HTTP date after which the unversioned `/api` aliases may be removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_sunset: Option<String>,
+}
+
+impl ApiVersions {
+    /// Whether the server serves [`API_VERSION`]
+    pub fn supports_current(&self) -> bool {
+        self.supported.iter().any(|version| version == API_VERSION)
+    }
+}
@@ -0,0 +1,84 @@
+//! Wire compatibility across API versions
+//!
+//! `fixtures/<version>/` holds one payload per type as that version sent it.
+//! Every version must still deserialize, and the newest must serialize back
+//! unchanged. When a type changes shape, add a new version directory instead
+//! of editing an existing one.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use xxx_types::{
+    CreateContainerRequest, CreateContainerResponse, ExecuteReplRequest, ExecuteReplResponse, LanguagesResponse,
+    RemoveContainerResponse,
+};
+
+const CURRENT: &str = "v1";
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn versions() -> Vec<String> {
+    let mut versions: Vec<String> = std::fs::read_dir(fixtures())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    versions.sort();
+    versions
+}
+
+/// Deserialize the fixture, and for the current version check it serializes back unchanged
+fn check<T: Serialize + DeserializeOwned>(version: &str, name: &str) {
+    let path = fixtures().join(version).join(format!("{}.json", name));
+    let json: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let parsed: T = serde_json::from_value(json.clone())
+        .unwrap_or_else(|e| panic!("{} no longer deserializes: {}", path.display(), e));
+    if version == CURRENT {
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json, "{} changed shape", path.display());
+    }
+}
+
+#[test]
+fn test_every_version_deserializes() {
+    let versions = versions();
+    assert_eq!(versions.last().map(String::as_str), Some(CURRENT));
+
+    for version in &versions {
+        for entry in std::fs::read_dir(fixtures().join(version)).unwrap() {
+            let file_name = entry.unwrap().file_name().into_string().unwrap();
+            let name = file_name.trim_end_matches(".json");
+            match name {
+                "create_container_request" => check::<CreateContainerRequest>(version, name),
+                "create_container_response" => check::<CreateContainerResponse>(version, name),
+                "remove_container_response" => check::<RemoveContainerResponse>(version, name),
+                "execute_repl_request" => check::<ExecuteReplRequest>(version, name),
+                "execute_repl_response" => check::<ExecuteReplResponse>(version, name),
+                "languages_response" => check::<LanguagesResponse>(version, name),
+                _ => panic!("No type for fixture {}/{}", version, file_name),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_original_payloads_get_defaults() {
+    let path = fixtures().join("v0/create_container_response.json");
+    let response: CreateContainerResponse = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(response.id, "abc123");
+    assert_eq!(response.output, None);
+    assert_eq!(response.exit_code, None);
+    assert!(response.artifacts.is_empty());
+    assert!(!response.truncated);
+
+    let path = fixtures().join("v0/execute_repl_request.json");
+    let request: ExecuteReplRequest = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert!(request.dependencies.is_empty());
+    assert!(request.network.is_none());
+
+    let path = fixtures().join("v0/execute_repl_response.json");
+    let response: ExecuteReplResponse = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert!(response.success);
+    assert!(!response.truncated);
+}
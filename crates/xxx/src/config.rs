@@ -0,0 +1,108 @@
+use container_api::auth::CallerTokens;
+use repl_api::client::TrustedProxies;
+use service_registry::auth::TokenStore;
+
+/// Settings of the all-in-one server
+///
+/// - `XXX_PORT`: port to listen on (default 3000)
+/// - `PODMAN_SOCKET`: the Podman API, as a socket path or a `unix://`, `tcp://`
+///   or `http://` URL (default `$XDG_RUNTIME_DIR/podman/podman.sock` when
+///   `XDG_RUNTIME_DIR` is set, else /run/podman/podman.sock)
+/// - `XXX_RATE_LIMIT_PER_MINUTE`: requests to the REPL routes, including
+///   `/repl/languages`, allowed per client address and minute (default: no limit)
+/// - `XXX_RATE_LIMIT_BURST`: REPL requests allowed at once (default: the per-minute limit)
+/// - `BLOB_MAX_BYTES`: largest blob upload (default 32 MiB)
+/// - `REPL_TRUSTED_PROXIES`: addresses or CIDR ranges of reverse proxies whose
+///   `X-Forwarded-For` header names the REPL client (default: none)
+///
+/// Authentication uses the variables of the individual services:
+/// `CONTAINER_API_TOKENS` for the container routes, `REGISTRY_TOKENS` /
+/// `REGISTRY_TOKENS_FILE` for registry writes and `AUDIT_TOKEN` for the audit logs.
+pub struct Config {
+    pub port: u16,
+    pub podman_socket: String,
+    /// Requests per minute and burst size
+    pub rate_limit: Option<(f64, f64)>,
+    pub caller_tokens: Option<CallerTokens>,
+    pub registry_tokens: Option<TokenStore>,
+    pub blob_max_bytes: usize,
+    pub trusted_proxies: TrustedProxies,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let port = match std::env::var("XXX_PORT") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("XXX_PORT must be a port number, got '{}'", value))?,
+            Err(_) => 3000,
+        };
+        let podman_socket = match std::env::var("PODMAN_SOCKET") {
+            Ok(socket) => podman_endpoint(&socket),
+            Err(_) => default_podman_socket(std::env::var("XDG_RUNTIME_DIR").ok()),
+        };
+
+        let rate = |name: &str| -> anyhow::Result<Option<f64>> {
+            match std::env::var(name) {
+                Ok(value) => match value.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 => Ok(Some(rate)),
+                    _ => anyhow::bail!("{} must be a positive number, got '{}'", name, value),
+                },
+                Err(_) => Ok(None),
+            }
+        };
+        let rate_limit = match rate("XXX_RATE_LIMIT_PER_MINUTE")? {
+            Some(per_minute) => Some((per_minute, rate("XXX_RATE_LIMIT_BURST")?.unwrap_or(per_minute))),
+            None => None,
+        };
+        let blob_max_bytes = match std::env::var("BLOB_MAX_BYTES") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("BLOB_MAX_BYTES must be a number of bytes, got '{}'", value))?,
+            Err(_) => container_api::blobs::DEFAULT_BLOB_MAX_BYTES,
+        };
+
+        Ok(Self {
+            port,
+            podman_socket,
+            rate_limit,
+            caller_tokens: CallerTokens::from_env()
+                .map_err(|e| anyhow::anyhow!("Invalid CONTAINER_API_TOKENS: {}", e))?,
+            registry_tokens: TokenStore::from_env()?,
+            blob_max_bytes,
+            trusted_proxies: TrustedProxies::from_env().map_err(|e| anyhow::anyhow!("Invalid REPL_TRUSTED_PROXIES: {}", e))?,
+        })
+    }
+}
+
+/// Podman endpoint for a socket path, leaving URLs as they are
+fn podman_endpoint(socket: &str) -> String {
+    if socket.contains("://") {
+        socket.to_string()
+    } else {
+        format!("unix://{}", socket)
+    }
+}
+
+fn default_podman_socket(runtime_dir: Option<String>) -> String {
+    match runtime_dir {
+        Some(dir) => format!("unix://{}/podman/podman.sock", dir.trim_end_matches('/')),
+        None => "unix:///run/podman/podman.sock".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_podman_endpoint() {
+        assert_eq!(podman_endpoint("/run/podman/podman.sock"), "unix:///run/podman/podman.sock");
+        assert_eq!(podman_endpoint("tcp://127.0.0.1:8085"), "tcp://127.0.0.1:8085");
+        assert_eq!(
+            default_podman_socket(Some("/run/user/1000/".to_string())),
+            "unix:///run/user/1000/podman/podman.sock"
+        );
+        assert_eq!(default_podman_socket(None), "unix:///run/podman/podman.sock");
+    }
+}
@@ -1,36 +1,149 @@
-use axum::{Router, routing::get, routing::post};
+mod config;
+
+use axum::{Router, middleware, routing::get};
+use config::Config;
+use container_api::reaper::Reaper;
+use container_api::scheduler::Scheduler;
+use container_api::{AppState, health};
+use repl_api::client::identify_client;
+use repl_api::rate_limit::{RateLimitExt, RateLimiter};
+use service_registry::health::HealthChecker;
+use service_registry::static_services::StaticServiceKeeper;
+use service_registry::{
+    HealthCheck, RegistryClientConfig, ServiceInfo, ServiceRegistry, ServiceStatus, api, metrics, openapi,
+    telemetry, versioning,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use supervisor::alerts::Notifier;
+use supervisor::monitor::{Monitor, MonitorConfig};
+use tokio::sync::Mutex;
 use tower_http::trace::TraceLayer;
 
-// Import handlers from other crates
-use container_api::{create_container, health, list_containers};
-use repl_api::{execute_repl, list_languages};
+/// Lease TTL of the embedded registry, the same service-registry uses with etcd
+const REGISTRY_LEASE_TTL_SECS: i64 = 30;
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
-    tracing_subscriber::fmt::init();
+    let _telemetry = telemetry::init("xxx");
 
-    let app = Router::new()
-        // Health check
-        .route("/healthz", get(health))
-        // Container API routes
-        .route("/api/containers/list", get(list_containers))
-        .route("/api/containers/create", post(create_container))
-        // REPL API routes
-        .route("/api/repl/execute", post(execute_repl))
-        .route("/api/repl/languages", get(list_languages))
-        .layer(TraceLayer::new_for_http());
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let config = Config::from_env().expect("Invalid xxx configuration");
+    let local_url = format!("http://127.0.0.1:{}", config.port);
+    let instance_id = format!("xxx-{}", std::process::id());
+    if config.caller_tokens.is_none() {
+        tracing::warn!("No caller tokens configured; all callers are anonymous and full network egress is disabled");
+    }
+
+    // Executions go straight to the configured Podman socket
+    let state = AppState {
+        scheduler: Scheduler::from_env().with_local_host(&config.podman_socket),
+        ..AppState::from_env()
+    }
+    .with_reaper(Reaper::from_env(&instance_id).sole_owner());
+    state.spawn_background_tasks();
+
+    repl_api::init_audit_log().expect("Failed to open audit log");
+    repl_api::use_container_api(&local_url);
+
+    // The embedded registry lists this process, so the registry and supervisor APIs report on it
+    let registry = Arc::new(Mutex::new(ServiceRegistry::in_memory(Some(REGISTRY_LEASE_TTL_SECS))));
+    StaticServiceKeeper::new(registry.clone(), local_services(&instance_id, config.port)).spawn();
+    HealthChecker::new(registry.clone()).spawn();
+
+    let monitor_config = MonitorConfig::from_env().expect("Invalid supervisor configuration");
+    let registry_config = RegistryClientConfig {
+        url: local_url,
+        ..RegistryClientConfig::from_env()
+    };
+    let registry_client = registry_config
+        .build_client()
+        .expect("failed building service registry client");
+    let monitor = Arc::new(Monitor::new(
+        registry_config.url,
+        registry_client,
+        Notifier::from_env(reqwest::Client::new()),
+        &monitor_config,
+    ));
+    monitor.clone().spawn(monitor_config.interval);
+
+    let app = app(&config, state, registry, monitor);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", config.port)).await.unwrap();
     println!("Server listening on {}", listener.local_addr().unwrap());
+    println!("Podman: {}", config.podman_socket);
     println!("Available endpoints:");
-    println!("  GET  /healthz");
-    println!("  GET  /api/containers/list");
-    println!("  POST /api/containers/create");
-    println!("  POST /api/repl/execute");
-    println!("  GET  /api/repl/languages");
+    println!("  GET  /healthz, /metrics, /openapi.json, /docs, /api/version");
+    println!("  /api/v1/containers, /api/v1/artifacts, /api/v1/blobs, /api/v1/secrets, /api/v1/audit");
+    println!("  /api/v1/repl/execute[/stream], /api/v1/repl/languages, /api/v1/repl/audit");
+    println!("  /api/v1/registry/...");
+    println!("  /api/v1/supervisor/...");
+    println!("  (deprecated aliases without /v1 until {})", versioning::LEGACY_SUNSET);
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
+}
+
+/// Registry entries for the services this process runs
+fn local_services(instance_id: &str, port: u16) -> Vec<ServiceInfo> {
+    vec![
+        ServiceInfo::new("container-api", instance_id, "127.0.0.1", port)
+            .with_status(ServiceStatus::Healthy)
+            .with_health_check(HealthCheck::http("/healthz")),
+        ServiceInfo::new("repl-api", instance_id, "127.0.0.1", port)
+            .with_status(ServiceStatus::Healthy)
+            .with_health_check(HealthCheck::http("/api/v1/repl/languages")),
+    ]
+}
+
+/// Every container-api, repl-api, registry and supervisor route on one router
+///
+/// Both services have an audit log: container-api's stays at `/api/v1/audit`
+/// and repl-api's moves to `/api/v1/repl/audit`, each with its unversioned alias.
+fn app(config: &Config, state: AppState, registry: Arc<Mutex<ServiceRegistry>>, monitor: Arc<Monitor>) -> Router {
+    let mut repl = repl_api::routes();
+    if let Some((per_minute, burst)) = config.rate_limit {
+        repl = repl.with_rate_limit(RateLimiter::new(per_minute, burst));
+    }
+    let repl = repl.layer(middleware::from_fn_with_state(
+        Arc::new(config.trusted_proxies.clone()),
+        identify_client,
+    ));
+
+    Router::new()
+        .route("/healthz", get(health))
+        .route("/metrics", get(metrics::handler))
+        .merge(container_api::routes(state, config.caller_tokens.clone(), config.blob_max_bytes))
+        .merge(repl)
+        .merge(versioning::versioned(Router::new().route("/repl/audit", get(repl_api::get_audit))))
+        .merge(api::routes(registry, config.registry_tokens.clone()))
+        .merge(supervisor::routes(monitor))
+        .merge(openapi::routes(openapi()))
+        .merge(versioning::routes("xxx", env!("CARGO_PKG_VERSION")))
+        .layer(middleware::from_fn_with_state("xxx", metrics::track))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span))
+}
 
-    axum::serve(listener, app).await.unwrap();
+/// The services' OpenAPI documents merged into one for `app`
+///
+/// repl-api's audit log is listed under `/api/v1/repl/audit`, where `app` mounts it.
+/// Its CA certificate route is left out, since `app` serves no TLS.
+fn openapi() -> utoipa::openapi::OpenApi {
+    let mut repl = repl_api::openapi();
+    if let Some(audit) = repl.paths.paths.remove("/api/v1/audit") {
+        repl.paths.paths.insert("/api/v1/repl/audit".to_string(), audit);
+    }
+    repl.paths.paths.remove("/api/v1/repl/tls/ca.pem");
+
+    let mut spec = container_api::openapi();
+    spec.merge(repl);
+    spec.merge(api::openapi());
+    spec.merge(supervisor::openapi());
+    spec.info.title = "xxx".to_string();
+    spec.info.description = Some("container-api, repl-api, service-registry and supervisor in one process".to_string());
+    spec.info.version = env!("CARGO_PKG_VERSION").to_string();
+    spec
 }
 
 #[cfg(test)]
@@ -38,6 +151,11 @@ mod tests {
     use super::*;
     use axum::body::Body;
     use axum::http::{Request, StatusCode};
+    use container_api::auth::CallerTokens;
+    use repl_api::list_languages;
+    use service_registry::auth::TokenStore;
+    use std::time::Duration;
+    use supervisor::alerts;
     use tower::ServiceExt;
 
     #[tokio::test]
@@ -91,38 +209,184 @@ mod tests {
         assert!(body_str.contains("Ruby"));
     }
 
+    fn test_config(rate_limit: Option<(f64, f64)>) -> Config {
+        Config {
+            port: 0,
+            podman_socket: "unix:///nonexistent/podman.sock".to_string(),
+            rate_limit,
+            caller_tokens: None,
+            registry_tokens: None,
+            blob_max_bytes: 1024,
+            trusted_proxies: Default::default(),
+        }
+    }
+
+    /// The app with its stores in a temporary directory, which must outlive it
+    fn test_app(config: Config) -> (tempfile::TempDir, Router) {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_tests(dir.path());
+        let registry = Arc::new(Mutex::new(ServiceRegistry::in_memory(None)));
+        let monitor_config = MonitorConfig {
+            interval: Duration::from_secs(15),
+            history_size: 10,
+            rules: alerts::default_rules(),
+            probes: Default::default(),
+            probe_ca_dir: None,
+            remediation: Vec::new(),
+            remediation_log: None,
+        };
+        let monitor = Arc::new(Monitor::new(
+            "http://127.0.0.1:1".to_string(),
+            reqwest::Client::new(),
+            Notifier::new(reqwest::Client::new(), Vec::new()),
+            &monitor_config,
+        ));
+        (dir, app(&config, state, registry, monitor))
+    }
+
+    async fn get_status(app: &Router, uri: &str) -> StatusCode {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
     #[tokio::test]
     async fn test_all_routes_mounted() {
-        let app = Router::new()
-            .route("/healthz", get(health))
-            .route("/api/containers/list", get(list_containers))
-            .route("/api/containers/create", post(create_container))
-            .route("/api/repl/execute", post(execute_repl))
-            .route("/api/repl/languages", get(list_languages));
-
-        // Test health check route
-        let response = app
-            .clone()
-            .oneshot(
-                Request::builder()
-                    .uri("/healthz")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        let (_dir, app) = test_app(test_config(None));
 
-        // Test languages route
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/api/repl/languages")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_status(&app, "/healthz").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/metrics").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/api/version").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/api/v1/repl/languages").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/api/v1/registry/services").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/api/v1/supervisor/status").await, StatusCode::OK);
+        // Anonymous callers have no secrets scope
+        assert_eq!(get_status(&app, "/api/v1/secrets").await, StatusCode::FORBIDDEN);
+
+        // Streaming routes exist even though their requests are incomplete
+        for (method, uri) in [
+            ("POST", "/api/v1/containers/create/stream"),
+            ("POST", "/api/v1/repl/execute/stream"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_ne!(response.status(), StatusCode::NOT_FOUND, "{} {}", method, uri);
+            assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_applies_to_repl_routes() {
+        let (_dir, app) = test_app(test_config(Some((60.0, 1.0))));
+
+        // The unversioned alias shares the versioned route's budget
+        assert_eq!(get_status(&app, "/api/v1/repl/languages").await, StatusCode::OK);
+        assert_eq!(get_status(&app, "/api/repl/languages").await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(get_status(&app, "/healthz").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_aliases_are_deprecated() {
+        let (_dir, app) = test_app(test_config(None));
+
+        for (legacy, current) in [
+            ("/api/repl/languages", "/api/v1/repl/languages"),
+            ("/api/repl/audit", "/api/v1/repl/audit"),
+            ("/api/registry/services", "/api/v1/registry/services"),
+            ("/api/supervisor/status", "/api/v1/supervisor/status"),
+            ("/api/audit", "/api/v1/audit"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(legacy).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_ne!(response.status(), StatusCode::NOT_FOUND, "{}", legacy);
+            assert_eq!(response.headers()["deprecation"], versioning::DEPRECATED_AT, "{}", legacy);
+            assert_eq!(response.headers()["sunset"], versioning::LEGACY_SUNSET, "{}", legacy);
+            assert_eq!(
+                response.headers()["link"],
+                format!("<{}>; rel=\"successor-version\"", current).as_str()
+            );
+
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(current).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(response.headers().get("deprecation").is_none(), "{}", current);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_documented_routes_are_served() {
+        // With tokens configured but none valid, authenticated routes answer
+        // 401 without running their handlers
+        let config = Config {
+            caller_tokens: Some(CallerTokens::new(Vec::new())),
+            registry_tokens: Some(TokenStore::new(Vec::new())),
+            ..test_config(None)
+        };
+        let (_dir, app) = test_app(config);
+        let app = app.fallback(|| async { StatusCode::IM_A_TEAPOT });
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        for (path, operations) in spec["paths"].as_object().unwrap() {
+            let uri = path.replace("{name}", "demo").replace("{id}", "abc123");
+            for method in operations.as_object().unwrap().keys() {
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .method(method.to_uppercase().as_str())
+                            .uri(&uri)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                assert_ne!(response.status(), StatusCode::IM_A_TEAPOT, "{} {} is not routed", method, path);
+                assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} {} is not routed", method, path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_operation_ids_and_tags_are_unique() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        let mut operation_ids: Vec<&str> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|operations| operations.as_object().unwrap().values())
+            .map(|operation| operation["operationId"].as_str().unwrap())
+            .collect();
+        let count = operation_ids.len();
+        operation_ids.sort();
+        operation_ids.dedup();
+        assert_eq!(operation_ids.len(), count);
+
+        let mut tags: Vec<&str> = spec["tags"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tag| tag["name"].as_str().unwrap())
+            .collect();
+        let count = tags.len();
+        tags.sort();
+        tags.dedup();
+        assert_eq!(tags.len(), count);
+    }
+
+    #[test]
+    fn test_openapi_matches_published() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        openapi::check_published(&openapi(), path).unwrap();
     }
 }